@@ -57,17 +57,23 @@ use rayon::prelude::*;
 
 // Spine骨骼动画库（C库的Rust绑定）
 use rusty_spine::{
-    AnimationState, AnimationStateData, Atlas, Skeleton, SkeletonJson, SkeletonBinary, Slot,
+    AnimationEvent, AnimationState, AnimationStateData, Atlas, MixBlend, Skeleton, SkeletonJson, SkeletonBinary, Slot,
 };
 
 // 线程通信
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::thread;
+use std::time::{Duration, Instant};
 
 // IO和序列化
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::time::SystemTime;
 use std::io::Cursor;
 use std::sync::{Arc, Mutex}; // 引入 Mutex 保障线程安全
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
 use rodio::Source;
+use rodio::cpal::traits::{DeviceTrait, HostTrait};
 use serde::{Serialize, Deserialize};
 
 // ============================================================================
@@ -77,9 +83,58 @@ const BASE_HEIGHT: f32 = 720.0;               // 基础画布高度，用于DPI
 const DIALOGUE_BOX_RATIO: f32 = 0.28;         // 对话框占屏幕高度的比例
 const MAX_DT: f32 = 0.033;                    // 最大delta时间，防止卡顿导致的动画跳跃
 const TYPEWRITER_INTERVAL: f32 = 0.03;        // 打字机效果：每个字符显示间隔（秒）
+const SKIP_SCENE_INTERVAL: f32 = 0.12;        // 快进模式：每幕停留时间（秒），保证画面仍可辨认
 const CHAR_BASE_SCALE: f32 = 0.45;            // 角色基础缩放系数
-const CHAR_X_START_PERCENT: f32 = 0.15;       // 1号位角色在屏幕水平方向起始位置（百分比）
-const CHAR_X_STEP_PERCENT: f32 = 0.175;       // 角色槽位之间的水平间距（百分比）
+const FORMATION_DEPTH: f32 = 0.06;            // 阵型纵深（屏幕高度比例）：弧形/V形/交错时后排角色脚底上移的幅度
+const WATCH_POLL_INTERVAL: f32 = 1.0;         // 热重载：源文件修改时间轮询间隔（秒）
+const AUDIO_RETRY_INTERVAL: f32 = 3.0;       // 音频设备检查/自动重连间隔（秒）
+const LIPSYNC_THRESHOLD: f32 = 0.08;          // 口型同步：振幅超过此值视为张嘴（归一化 0~1）
+const LIPSYNC_RELEASE: f32 = 0.9995;          // 口型同步：包络每采样衰减系数（约20ms释放）
+// 序列帧背景最大帧数：每帧常驻显存，1080p RGBA 约 8MB/帧，120 帧约 1GB，超出部分丢弃
+const BG_ANIM_MAX_FRAMES: usize = 120;
+const BG_ANIM_DEFAULT_FPS: f32 = 12.0;        // 序列帧背景默认帧率
+const TYPE_SFX_MIN_INTERVAL: f32 = 0.06;      // 打字音效最小间隔（秒），高速打字时跳过多余的音效
+const TYPE_SFX_VOLUME: f32 = 0.6;             // 打字音效音量
+const LETTERBOX_SLIDE_TIME: f32 = 0.4;        // 电影黑边滑入/滑出时长（秒）
+const LETTERBOX_DEFAULT_HEIGHT: f32 = 0.12;   // 电影黑边默认高度（屏幕高度比例）
+const STAGE_DEFAULT_COLOR: Color32 = Color32::from_rgb(24, 24, 28); // 舞台默认底色（中性深色）
+const LOG_DEFAULT_LIMIT: usize = 1000;        // 控制台日志默认保留条数，超出时丢弃最旧的日志
+const SUSPEND_POLL_INTERVAL: f32 = 0.25;      // 窗口最小化时轮询异步事件的间隔（秒），期间不更新也不绘制
+const RESUME_MAX_DT: f32 = 1.0 / 30.0;        // 从最小化恢复的第一帧帧时间上限，避免动画跳变
+const UNFOCUSED_FPS_DEFAULT: u32 = 30;        // 窗口失去焦点时的默认帧率上限（0 为不限制）
+const CONSOLE_DOCK_HEIGHT: f32 = 0.45;        // 停靠控制台高度（屏幕高度比例），留出上半部分舞台
+const CONSOLE_SLIDE_TIME: f32 = 0.4;          // 停靠控制台滑入/滑出时长（秒）
+const SLOT_COUNT: usize = 5;                   // 角色槽位数（0 ~ SLOT_COUNT-1）
+const SCHED_RESERVE_MIN: usize = 2;           // 保留给系统/UI/音频线程的最少核心数
+const SCHED_RESERVE_FRACTION: f32 = 0.125;    // 默认按比例保留核心（1/8），多核桌面不再过度保留
+const SCHED_WORKER_NICE: i32 = 5;             // 计算线程的 nice 值（越大优先级越低，UI/音频线程保持默认 0）
+const SCHED_STARVE_TIME: f32 = 0.020;         // 自适应调度：平滑帧时间超过此值视为UI线程饥饿，降低并发
+const SCHED_HEADROOM_TIME: f32 = 0.012;       // 自适应调度：平滑帧时间低于此值视为有余量，恢复并发
+const SCHED_ADAPT_INTERVAL: f32 = 1.0;        // 自适应调度：两次调整的最小间隔（秒），避免来回抖动
+const PARALLEL_MIN_CHARS: usize = 2;          // 活跃角色数达到此值才使用线程池并行更新
+const SHAKE_SAMPLE_RATE: f32 = 30.0;          // 画面震动的随机采样频率（Hz），样本间线性插值
+const RECORD_PADDING: f32 = 16.0;             // 录制画布在动画包围盒外的留白（像素）
+const RECORD_MAX_SIZE: f32 = 2048.0;          // 录制画布最长边上限，超出时整体缩小
+const FOCUS_BLUR_OFFSET: f32 = 1.5;           // 焦点虚化：两次错位绘制的偏移（像素）
+const FOCUS_TAP_ALPHA: f32 = 0.6;             // 焦点虚化：每次错位绘制的不透明度（两层叠加后约为 0.84）
+const FOCUS_DESATURATE: f32 = 0.5;            // 焦点虚化：向灰度混合的比例
+const FOCUS_DARKEN: f32 = 0.85;               // 焦点虚化：亮度系数
+const SNAP_GUIDE_DISTANCE: f32 = 6.0;         // 对齐参考线：与其他角色锚点相差不超过此距离（像素）时吸附
+const SHADOW_BLOB_SIZE: f32 = 0.7;            // 脚下阴影默认宽度（角色包围盒宽度的比例）
+const SHADOW_BLOB_OPACITY: f32 = 0.35;        // 脚下阴影默认中心不透明度
+const SHADOW_BLOB_FLATTEN: f32 = 0.18;        // 脚下阴影椭圆的高宽比
+const GIZMO_HANDLE_SIZE: f32 = 12.0;          // 变换手柄边长（像素）
+const GIZMO_ROTATE_DISTANCE: f32 = 28.0;      // 旋转手柄与包围盒顶边的距离（像素）
+const CULL_MARGIN: f32 = 32.0;                // 视口剔除：包围盒外扩的像素，吸收骨骼包围盒的估算误差
+const TEXTURE_BUDGET_DEFAULT_MB: usize = if cfg!(target_os = "android") { 256 } else { 1024 }; // 角色纹理显存预算默认值（MB）
+const TEXTURE_EVICT_IDLE: f32 = 2.0;          // 纹理回收：最近这么多秒内绘制过的角色不会被回收，避免可见角色来回重载
+const LOAD_RETRY_DEFAULT: u32 = 2;            // 角色加载失败后的默认重试次数（导出工具分步写文件/网络抖动）
+const LOAD_RETRY_BASE_MS: u64 = 300;          // 首次重试前的等待（毫秒），之后每次翻倍
+const RENDER_SCALE_MIN: f32 = 0.5;            // 渲染缩放下限（相对系统缩放）
+const RENDER_SCALE_MAX: f32 = 3.0;            // 渲染缩放上限
+const TIMELINE_MIN_WAIT: f32 = 0.05;          // 导出时间线：间隔短于此值的相邻命令之间不插入 wait
+const UNDO_LIMIT: usize = 100;                // 撤销栈最多保留的步数
+const MAX_MACRO_DEPTH: usize = 8;             // 宏/别名最大展开层数，防止相互引用导致无限递归
 
 // ============================================================================
 // 数据结构定义
@@ -88,13 +143,66 @@ const CHAR_X_STEP_PERCENT: f32 = 0.175;       // 角色槽位之间的水平间
 /// 序列化支持：用于保存/加载剧本文件
 #[derive(Serialize, Deserialize, Clone, Default)]
 struct Scene {
-    bg_path: Option<String>,              // 背景图片路径
+    bg_path: Option<String>,              // 背景图片路径（序列帧背景为帧目录）
+    #[serde(default)]
+    bg_anim_fps: Option<f32>,             // 序列帧背景帧率（None 表示静态背景）
+    #[serde(default)]
+    bg_layers: Vec<String>,               // 叠加在背景之上的图层（自下而上，可含透明区域）
     bgm_path: Option<String>,             // 背景音乐路径
-    char_paths:[Option<String>; 5],       // 5个角色槽位的Spine文件路径
-    char_anims:[Option<String>; 5],       // 对应角色的当前动画名称
+    char_paths:[Option<String>; SLOT_COUNT], // 5个角色槽位的Spine文件路径
+    char_anims:[Option<String>; SLOT_COUNT], // 对应角色的当前动画名称
     speaker_name: String,                 // 当前说话角色名称
     speaker_aff: String,                  // 角色所属组织/学校
     dialogue_content: String,             // 对话内容
+    #[serde(default)]
+    speaker_plate: NamePlate,             // 名牌样式（颜色/位置）
+    #[serde(default)]
+    voice_path: Option<String>,           // 本句语音文件
+    #[serde(default)]
+    char_hidden_slots: [Vec<String>; SLOT_COUNT], // 各角色被 slot 命令隐藏的插槽名
+    #[serde(default)]
+    char_mixes: [Vec<(String, String, f32)>; SLOT_COUNT], // 各角色由 mix 命令设置的动画过渡时长 (从, 到, 秒)
+}
+
+/// 名牌水平位置
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+enum PlateAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// 说话者名牌样式：由 talk 命令的附加字段设置
+#[derive(Serialize, Deserialize, Clone, Copy, Default)]
+struct NamePlate {
+    color: Option<[u8; 4]>,               // 名牌底色（None 使用主题默认色）
+    align: PlateAlign,                    // 名牌位置
+}
+
+impl NamePlate {
+    /// 解析 talk 命令的附加字段，如 color=#ffcc00 / pos=center
+    fn apply_field(&mut self, field: &str) -> Result<(), String> {
+        let (key, value) = field.split_once('=')
+            .ok_or_else(|| format!("TALK: 附加字段应为 键=值，收到 \"{}\"", field))?;
+        match key.trim().to_lowercase().as_str() {
+            "color" => {
+                let c = parse_hex_color(value)
+                    .ok_or_else(|| format!("TALK: 颜色格式应为 #RRGGBB 或 #RRGGBBAA，收到 \"{}\"", value))?;
+                self.color = Some(c.to_srgba_unmultiplied());
+            }
+            "pos" => {
+                self.align = match value.trim().to_lowercase().as_str() {
+                    "left" => PlateAlign::Left,
+                    "center" => PlateAlign::Center,
+                    "right" => PlateAlign::Right,
+                    _ => return Err(format!("TALK: pos 只能是 left/center/right，收到 \"{}\"", value)),
+                };
+            }
+            other => return Err(format!("TALK: 未知附加字段 {}（可用: color pos）", other)),
+        }
+        Ok(())
+    }
 }
 
 /// 剧本数据结构：包含多个场景
@@ -102,6 +210,406 @@ struct Scene {
 #[derive(Serialize, Deserialize, Clone, Default)]
 struct Scenario {
     scenes: Vec<Scene>,                   // 场景列表，按时间顺序排列
+    #[serde(default)]
+    macros: HashMap<String, Vec<String>>, // 控制台宏：宏名 -> 依次执行的命令
+    #[serde(default)]
+    aliases: HashMap<String, String>,     // 命令别名：别名 -> 目标命令（可带预设参数）
+}
+
+/// LOADALL 清单中的一项：槽位与资源，以及加载完成后套用的可选属性
+/// 清单为 JSON 数组，相对路径以清单所在目录为基准
+#[derive(Deserialize)]
+struct LoadAllEntry {
+    slot: usize,                          // 目标槽位
+    path: String,                         // 资源路径（.atlas、.zip 或 URL）
+    #[serde(default)]
+    pos: Option<String>,                  // 同 pos 命令参数：锚点名 / "30% 80%" / "640 700"
+    #[serde(default)]
+    scale: Option<f32>,                   // 用户缩放倍率
+    #[serde(default)]
+    anim: Option<String>,                 // 加载后播放的动画
+    #[serde(default = "LoadAllEntry::default_looping")]
+    looping: bool,                        // 动画是否循环（默认循环）
+}
+
+impl LoadAllEntry {
+    fn default_looping() -> bool {
+        true
+    }
+}
+
+/// 对话框主题：控制对话框布局与配色
+/// 可通过 theme 命令逐项修改，或从 JSON 文件整体加载
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+struct DialogueTheme {
+    box_height: f32,                      // 对话框高度：≤1 为屏幕高度比例，>1 为像素
+    bg_color: [u8; 4],                    // 背景颜色 RGBA
+    name_font_size: Option<f32>,          // 名称字号（None 为随对话框高度自适应）
+    content_font_size: Option<f32>,       // 正文字号（None 为随对话框高度自适应）
+    padding: f32,                         // 左右内边距下限（像素）
+    corner_radius: f32,                   // 背景圆角半径
+    plate_color: [u8; 4],                 // 名牌默认底色
+    affiliation_color: [u8; 4],           // 所属文字颜色
+    affiliation_scale: f32,               // 所属字号相对名称字号的比例
+}
+
+impl Default for DialogueTheme {
+    fn default() -> Self {
+        Self {
+            box_height: DIALOGUE_BOX_RATIO,
+            bg_color: [12, 18, 28, 252],
+            name_font_size: None,
+            content_font_size: None,
+            padding: 100.0,
+            corner_radius: 0.0,
+            plate_color: [40, 62, 96, 220],
+            affiliation_color: [100, 200, 255, 255],
+            affiliation_scale: 0.75,
+        }
+    }
+}
+
+impl DialogueTheme {
+    /// 对话框在给定屏幕中的实际高度
+    fn box_height_for(&self, screen: Rect) -> f32 {
+        if self.box_height <= 1.0 {
+            screen.height() * self.box_height
+        } else {
+            self.box_height.min(screen.height())
+        }
+    }
+    
+    /// 按键名设置单项主题参数
+    fn set_value(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let num = || value.parse::<f32>().map_err(|_| format!("THEME: {} 需要数字，收到 \"{}\"", key, value));
+        let size = || -> Result<Option<f32>, String> {
+            if value.eq_ignore_ascii_case("auto") { Ok(None) } else { num().map(Some) }
+        };
+        match key {
+            "boxheight" => self.box_height = num()?.max(0.0),
+            "bg" => {
+                let c = parse_hex_color(value)
+                    .ok_or_else(|| format!("THEME: 颜色格式应为 #RRGGBB 或 #RRGGBBAA，收到 \"{}\"", value))?;
+                self.bg_color = c.to_srgba_unmultiplied();
+            }
+            "alpha" => self.bg_color[3] = num()?.clamp(0.0, 255.0) as u8,
+            "plate" | "affcolor" => {
+                let c = parse_hex_color(value)
+                    .ok_or_else(|| format!("THEME: 颜色格式应为 #RRGGBB 或 #RRGGBBAA，收到 \"{}\"", value))?
+                    .to_srgba_unmultiplied();
+                if key == "plate" { self.plate_color = c } else { self.affiliation_color = c }
+            }
+            "affscale" => self.affiliation_scale = num()?.clamp(0.2, 2.0),
+            "namesize" => self.name_font_size = size()?,
+            "textsize" => self.content_font_size = size()?,
+            "padding" => self.padding = num()?.max(0.0),
+            "radius" => self.corner_radius = num()?.max(0.0),
+            _ => return Err(format!("THEME: 未知参数 {}（可用: boxheight bg alpha plate affcolor affscale namesize textsize padding radius）", key)),
+        }
+        Ok(())
+    }
+}
+
+/// 快捷键绑定：每个动作可绑定多个按键（egui 按键名，如 Space、Enter、Escape、Backtick、1）
+/// 启动时若工作目录下存在 keybindings.json 则从中加载，也可用 bind 命令修改
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+struct KeyBindings {
+    advance: Vec<String>,                 // 推进对话（等同点击对话框）
+    console: Vec<String>,                 // 开关控制台
+    close: Vec<String>,                   // 关闭窗口
+    slots: Vec<String>,                   // 依次选中槽位 0..4
+    freeze: Vec<String>,                  // 冻结/解冻整个模拟
+    skip: Vec<String>,                    // 按住快进（跳过打字效果并连续推进后续各幕）
+    cycle: Vec<String>,                   // 选中下一个已加载角色（按住 Shift 反向）
+    quick_anims: Vec<String>,             // 演示模式：播放选中角色的第 1..N 个动画（按名称排序）
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let keys = |names: &[&str]| names.iter().map(|s| s.to_string()).collect();
+        Self {
+            advance: keys(&["Space", "Enter"]),
+            console: keys(&["Backtick"]),
+            close: keys(&["Escape"]),
+            slots: keys(&["1", "2", "3", "4", "5"]),
+            freeze: keys(&["P"]),
+            skip: keys(&["S"]),
+            cycle: keys(&["Tab"]),
+            quick_anims: keys(&["1", "2", "3", "4", "5", "6", "7", "8", "9"]),
+        }
+    }
+}
+
+impl KeyBindings {
+    const FILE: &'static str = "keybindings.json";
+    
+    /// 从 keybindings.json 加载（文件不存在时使用默认绑定）
+    fn load() -> Result<Self, String> {
+        match std::fs::read_to_string(Self::FILE) {
+            Ok(text) => serde_json::from_str(&text).map_err(|e| format!("{} 格式错误: {}", Self::FILE, e)),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+    
+    /// 按动作名取得绑定列表
+    fn action_mut(&mut self, action: &str) -> Option<&mut Vec<String>> {
+        match action {
+            "advance" => Some(&mut self.advance),
+            "console" => Some(&mut self.console),
+            "close" => Some(&mut self.close),
+            "slots" => Some(&mut self.slots),
+            "freeze" => Some(&mut self.freeze),
+            "skip" => Some(&mut self.skip),
+            "cycle" => Some(&mut self.cycle),
+            "quickanims" => Some(&mut self.quick_anims),
+            _ => None,
+        }
+    }
+}
+
+/// 用户配置：集中调整打字速度、舞台底色、字体、日志、调度策略与对话框主题
+/// 启动时若工作目录下存在 aefr.toml 则从中加载，也可用 config save/load 保存与重新应用
+/// 未知键只产生警告，缺失的键取默认值
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+struct AppConfig {
+    type_interval: f32,                   // 打字机每个字符的基准显示间隔（秒）
+    pacing: TypePacing,                   // 按字符类别的节奏倍率
+    stage_color: String,                  // 舞台底色：#RRGGBB / transparent
+    transparent_window: bool,             // 透明窗口（stage transparent 透出桌面，需要窗口合成支持；重启后生效）
+    font_path: String,                    // 外部字体文件（空为内置字体，外部字体缺字时回退到内置字体）
+    log_limit: usize,                     // 控制台日志保留条数
+    log_level: String,                    // 控制台显示级别：verbose / info / warn / error
+    sched_auto: bool,                     // 自适应并发
+    sched_nice: bool,                     // 计算线程降优先级
+    sched_cores: usize,                   // 手动并发数（0 为自动）
+    sched_reserve: String,                // 核心保留策略：fixed N / fraction F / explicit N
+    texture_budget_mb: usize,             // 角色纹理显存预算（MB，0 为不限制）
+    load_retries: u32,                    // 角色加载失败后的重试次数（0 为不重试）
+    render_scale: f32,                    // 渲染缩放（系统缩放的倍数，见 renderscale 命令）
+    console_docked: bool,                 // 控制台停靠在屏幕底部（false 为浮动窗口）
+    unfocused_fps: u32,                   // 失去焦点时的帧率上限（0 为不限制，最小化时总是暂停）
+    theme: DialogueTheme,                 // 对话框主题
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            type_interval: TYPEWRITER_INTERVAL,
+            pacing: TypePacing::default(),
+            stage_color: color_to_hex(STAGE_DEFAULT_COLOR),
+            transparent_window: false,
+            font_path: String::new(),
+            log_limit: LOG_DEFAULT_LIMIT,
+            log_level: LogLevel::Verbose.name().into(),
+            sched_auto: false,
+            sched_nice: true,
+            sched_cores: 0,
+            sched_reserve: ReservePolicy::default().name(),
+            texture_budget_mb: TEXTURE_BUDGET_DEFAULT_MB,
+            load_retries: LOAD_RETRY_DEFAULT,
+            render_scale: 1.0,
+            console_docked: false,
+            unfocused_fps: UNFOCUSED_FPS_DEFAULT,
+            theme: DialogueTheme::default(),
+        }
+    }
+}
+
+impl AppConfig {
+    const FILE: &'static str = "aefr.toml";
+    
+    /// 从 TOML 文件加载，返回配置与未知键列表
+    fn load(path: &str) -> Result<(Self, Vec<String>), String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("无法读取 {}: {}", path, e))?;
+        let mut unknown = Vec::new();
+        let config = serde_ignored::deserialize(toml::Deserializer::new(&text), |key| unknown.push(key.to_string()))
+            .map_err(|e| format!("{} 格式错误: {}", path, e))?;
+        Ok((config, unknown))
+    }
+    
+    /// 写入 TOML 文件
+    fn save(&self, path: &str) -> Result<(), String> {
+        let text = toml::to_string_pretty(self).map_err(|e| format!("配置序列化失败: {}", e))?;
+        std::fs::write(path, text).map_err(|e| format!("无法写入 {}: {}", path, e))
+    }
+}
+
+/// 打字机节奏：按字符类别缩放每个字符的显示间隔
+/// CJK 字形信息量大，逐字显示时比拉丁字母读得慢，因此拉丁字母默认更快；标点之后额外停顿
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
+struct TypePacing {
+    cjk: f32,                             // 全角字符（汉字、假名、谚文、全角符号）的间隔倍率
+    latin: f32,                           // 其他字符（拉丁字母、数字、空格等）的间隔倍率
+    pause_short: f32,                     // 逗号类标点（，、；：,;:）之后追加的间隔倍数
+    pause_long: f32,                      // 句末标点（。！？…!?.）之后追加的间隔倍数
+}
+
+impl Default for TypePacing {
+    fn default() -> Self {
+        Self { cjk: 1.0, latin: 0.6, pause_short: 3.0, pause_long: 8.0 }
+    }
+}
+
+impl TypePacing {
+    /// 显示字符 c 前需要等待的间隔倍数（prev 为上一个已显示的字符）
+    fn factor(&self, prev: Option<char>, c: char) -> f32 {
+        let base = if is_fullwidth_char(c) { self.cjk } else { self.latin };
+        let pause = match prev {
+            Some('。' | '！' | '？' | '…' | '!' | '?' | '.') if !matches!(c, '。' | '！' | '？' | '…' | '!' | '?' | '.' | '」' | '』' | '”' | '）' | ')') => self.pause_long,
+            Some('，' | '、' | '；' | '：' | ',' | ';' | ':') => self.pause_short,
+            _ => 0.0,
+        };
+        (base + pause).max(0.0)
+    }
+    
+    /// 按键名设置单项倍率
+    fn set(&mut self, key: &str, value: f32) -> Result<(), String> {
+        if !(value >= 0.0) {
+            return Err(format!("PACING: 倍率必须是非负数，收到 {}", value));
+        }
+        match key {
+            "cjk" => self.cjk = value,
+            "latin" => self.latin = value,
+            "short" => self.pause_short = value,
+            "long" => self.pause_long = value,
+            _ => return Err(format!("PACING: 未知参数 {}（可用: cjk latin short long）", key)),
+        }
+        Ok(())
+    }
+}
+
+/// 是否为全角字符：CJK 统一表意文字、假名、谚文及全角符号
+fn is_fullwidth_char(c: char) -> bool {
+    matches!(c as u32,
+        0x3000..=0x30FF     // CJK 标点、平假名、片假名
+        | 0x3400..=0x4DBF   // 扩展A
+        | 0x4E00..=0x9FFF   // 统一表意文字
+        | 0xAC00..=0xD7AF   // 谚文音节
+        | 0xF900..=0xFAFF   // 兼容表意文字
+        | 0xFF00..=0xFFEF   // 全角ASCII与半角片假名
+        | 0x20000..=0x2FFFF // 扩展B及以后
+    )
+}
+
+/// 颜色转为 #RRGGBB 字符串（与 parse_hex_color 互逆，忽略 Alpha）
+fn color_to_hex(color: Color32) -> String {
+    format!("#{:02X}{:02X}{:02X}", color.r(), color.g(), color.b())
+}
+
+/// 列表中是否有按键正被按住
+fn any_key_down(ctx: &egui::Context, names: &[String]) -> bool {
+    names.iter()
+        .filter_map(|n| egui::Key::from_name(n))
+        .any(|k| ctx.input(|i| i.key_down(k)))
+}
+
+/// 本帧是否按下了列表中的任一按键
+fn any_key_pressed(ctx: &egui::Context, names: &[String]) -> bool {
+    names.iter()
+        .filter_map(|n| egui::Key::from_name(n))
+        .any(|k| ctx.input(|i| i.key_pressed(k)))
+}
+
+/// 背景状态：静态图片或循环播放的序列帧
+enum Background {
+    Static(TextureHandle),
+    Animated {
+        frames: Vec<TextureHandle>,       // 按帧号排序的纹理
+        fps: f32,                         // 播放帧率
+        time: f32,                        // 播放进度（秒）
+    },
+}
+
+impl Background {
+    /// 推进序列帧播放进度（静态背景无操作）
+    fn advance(&mut self, dt: f32) {
+        if let Background::Animated { frames, fps, time } = self {
+            // 按整段时长取模，循环无缝衔接且不会累积浮点误差
+            let period = frames.len() as f32 / *fps;
+            *time = (*time + dt).rem_euclid(period);
+        }
+    }
+    
+    /// 当前应显示的纹理
+    fn current(&self) -> &TextureHandle {
+        match self {
+            Background::Static(tex) => tex,
+            Background::Animated { frames, fps, time } => {
+                let idx = (*time * *fps) as usize;
+                &frames[idx.min(frames.len() - 1)]
+            }
+        }
+    }
+}
+
+/// 读取目录中的序列帧图片，按文件名中的帧号排序（frame_2 排在 frame_10 之前）
+fn collect_frame_paths(dir: &str) -> Result<Vec<PathBuf>, String> {
+    let entries = std::fs::read_dir(dir).map_err(|e| format!("无法读取目录 {}: {}", dir, e))?;
+    let mut frames: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| matches!(
+            p.extension().and_then(|e| e.to_str()).map(str::to_lowercase).as_deref(),
+            Some("png" | "jpg" | "jpeg" | "webp" | "bmp")
+        ))
+        .collect();
+    let frame_number = |p: &PathBuf| -> u64 {
+        let stem = p.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+        let digits: String = stem.chars().rev()
+            .skip_while(|c| !c.is_ascii_digit())
+            .take_while(|c| c.is_ascii_digit())
+            .collect::<Vec<_>>().into_iter().rev().collect();
+        digits.parse().unwrap_or(0)
+    };
+    frames.sort_by(|a, b| frame_number(a).cmp(&frame_number(b)).then_with(|| a.cmp(b)));
+    if frames.is_empty() {
+        return Err(format!("目录 {} 中没有图片帧", dir));
+    }
+    Ok(frames)
+}
+
+/// 背景图片适配模式
+#[derive(Clone, Copy, PartialEq, Default)]
+enum BgFit {
+    Stretch,    // 拉伸铺满（可能变形）
+    #[default]
+    Cover,      // 等比铺满，裁掉溢出部分
+    Contain,    // 等比完整显示，留黑边
+    Tile,       // 按原始尺寸平铺
+}
+
+impl BgFit {
+    /// 计算背景的绘制矩形与UV矩形
+    fn placement(self, screen: Rect, img_size: Vec2) -> (Rect, Rect) {
+        let full_uv = Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0));
+        match self {
+            BgFit::Stretch => (screen, full_uv),
+            BgFit::Cover | BgFit::Contain => {
+                let (sx, sy) = (screen.width() / img_size.x, screen.height() / img_size.y);
+                let scale = if self == BgFit::Cover { sx.max(sy) } else { sx.min(sy) };
+                (Rect::from_center_size(screen.center(), img_size * scale), full_uv)
+            }
+            BgFit::Tile => {
+                // 纹理以 Repeat 方式上传，UV 超出 1 的部分自动重复
+                let repeats = screen.size() / img_size;
+                (screen, Rect::from_min_size(Pos2::ZERO, repeats))
+            }
+        }
+    }
+}
+
+/// 解析 #RRGGBB / #RRGGBBAA 颜色字符串
+fn parse_hex_color(s: &str) -> Option<Color32> {
+    let hex = s.trim().trim_start_matches('#');
+    let byte = |i: usize| hex.get(i..i + 2).and_then(|h| u8::from_str_radix(h, 16).ok());
+    match hex.len() {
+        6 => Some(Color32::from_rgb(byte(0)?, byte(2)?, byte(4)?)),
+        8 => Some(Color32::from_rgba_unmultiplied(byte(0)?, byte(2)?, byte(4)?, byte(6)?)),
+        _ => None,
+    }
 }
 
 // ============================================================================
@@ -110,11 +618,15 @@ struct Scenario {
 /// 桌面平台主函数
 #[cfg(not(target_os = "android"))]
 fn main() -> eframe::Result<()> {
+    // 透明窗口只能在创建时决定：仅在配置启用时开启，没有窗口合成的平台上保持不透明窗口
+    let transparent = AppConfig::load(AppConfig::FILE).map_or(false, |(config, _)| config.transparent_window);
+    
     // 配置原生窗口选项
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([1280.0, 720.0])      // 初始窗口大小
-            .with_title("GNU's Not Unix : AEFR's Eternal Freedom & Rust_rendered"), // 窗口标题
+            .with_title("GNU's Not Unix : AEFR's Eternal Freedom & Rust_rendered") // 窗口标题
+            .with_transparent(transparent),        // 透明舞台（stage transparent）透出桌面
         vsync: true,                               // 启用垂直同步，防止画面撕裂
         ..Default::default()
     };
@@ -141,12 +653,89 @@ fn main() -> eframe::Result<()> {
 #[cfg(target_os = "android")]
 #[no_mangle]
 fn android_main(app: android_activity::AndroidApp) {
+    // 安卓没有终端：崩溃与初始化错误都写入 logcat（adb logcat -s AEFR）
+    logcat::install_panic_hook();
+    logcat::write(logcat::INFO, "[系统] android_main 启动");
+    
     // 包装eframe入口，适配Android Activity生命周期
-    let _ = eframe::run_native(
+    // 错误页同样需要 AndroidApp，先克隆一份留给它
+    let fallback_app = app.clone();
+    if let Err(e) = eframe::run_native(
         "AEFR_App", 
-        eframe::NativeOptions::default(), 
+        android_options(app), 
         Box::new(|cc| Box::new(AefrApp::new(cc)))
-    );
+    ) {
+        let message = format!("[错误] eframe 初始化失败: {}", e);
+        logcat::write(logcat::ERROR, &message);
+        // 窗口若还能创建，显示一个最小错误页（eframe 复用已创建的事件循环），否则只能依赖 logcat
+        if let Err(e) = eframe::run_native(
+            "AEFR_App", 
+            android_options(fallback_app), 
+            Box::new(move |_| Box::new(StartupErrorApp(message)))
+        ) {
+            logcat::write(logcat::ERROR, &format!("[错误] 无法显示错误页面: {}", e));
+        }
+    }
+}
+
+/// 安卓窗口配置：winit 在安卓上必须拿到 AndroidApp 才能创建事件循环，否则直接 panic
+#[cfg(target_os = "android")]
+fn android_options(app: android_activity::AndroidApp) -> eframe::NativeOptions {
+    eframe::NativeOptions {
+        event_loop_builder: Some(Box::new(move |builder| {
+            use winit::platform::android::EventLoopBuilderExtAndroid;
+            builder.with_android_app(app);
+        })),
+        ..Default::default()
+    }
+}
+
+/// 启动失败时显示的最小错误页
+#[cfg(target_os = "android")]
+struct StartupErrorApp(String);
+
+#[cfg(target_os = "android")]
+impl eframe::App for StartupErrorApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("GNU:AEFR 启动失败");
+            ui.label(&self.0);
+            ui.label("详细信息：adb logcat -s AEFR");
+        });
+    }
+}
+
+/// Android 日志输出：直接调用系统 liblog 的 __android_log_write，标签为 AEFR
+#[cfg(target_os = "android")]
+mod logcat {
+    use std::ffi::CString;
+    use std::os::raw::{c_char, c_int};
+    
+    pub const INFO: c_int = 4;   // ANDROID_LOG_INFO
+    pub const ERROR: c_int = 6;  // ANDROID_LOG_ERROR
+    const TAG: &[u8] = b"AEFR\0";
+    
+    #[link(name = "log")]
+    extern "C" {
+        fn __android_log_write(prio: c_int, tag: *const c_char, text: *const c_char) -> c_int;
+    }
+    
+    pub fn write(prio: c_int, text: &str) {
+        // CString 不允许内部 NUL，替换掉而不是丢弃整条日志
+        let text = CString::new(text.replace('\0', " ")).unwrap_or_default();
+        unsafe {
+            __android_log_write(prio, TAG.as_ptr() as *const c_char, text.as_ptr());
+        }
+    }
+    
+    /// panic 信息先写入 logcat，再交给默认处理（默认处理输出到 stderr，安卓上不可见）
+    pub fn install_panic_hook() {
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            write(ERROR, &format!("[错误] 程序崩溃: {}", info));
+            default_hook(info);
+        }));
+    }
 }
 
 // ============================================================================
@@ -196,12 +785,11 @@ mod file_picker {
             }
         });
     }
-    
-    /// 选择Spine动画文件（.atlas格式）
+    /// 选择Spine动画文件（.atlas 或 zip 资源包）
     pub fn pick_spine(tx: Sender<AppCommand>, slot: usize) {
         thread::spawn(move || {
             if let Some(p) = rfd::FileDialog::new()
-                .add_filter("Atlas", &["atlas"])
+                .add_filter("Atlas", &["atlas", "zip"])
                 .pick_file() 
             {
                 let _ = tx.send(AppCommand::RequestLoad { 
@@ -286,76 +874,366 @@ mod file_picker {
 // 核心架构组件
 // ============================================================================
 /// 绅士调度器：防止计算线程抢占UI和音频线程
-/// 策略：保留2个CPU核心给系统和关键线程
+/// 策略：1. 按 ReservePolicy 保留CPU核心给系统和关键线程（默认 max(2, 核心数/8)）；
+///       2. 计算线程以较低的系统优先级运行（Linux/Android: nice 值），避免与音频/UI线程争抢CPU
 struct AefrScheduler { 
-    pool: rayon::ThreadPool  // Rayon线程池实例
+    backend: SchedulerBackend, // 执行后端
+    worker_count: usize,       // 计算线程数
+    policy: ReservePolicy,     // 核心保留策略
+    lower_priority: bool,      // 是否尝试降低计算线程优先级
+    priority_result: (usize, usize), // 降低优先级 (成功线程数, 失败线程数)
+}
+
+/// 核心保留策略：决定线程池的计算线程数
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum ReservePolicy {
+    FixedReserve(usize),       // 固定保留 N 个核心
+    Fraction(f32),             // 按比例保留核心，至少保留 SCHED_RESERVE_MIN 个
+    Explicit(usize),           // 直接指定计算线程数
+}
+
+impl Default for ReservePolicy {
+    fn default() -> Self {
+        Self::Fraction(SCHED_RESERVE_FRACTION)
+    }
+}
+
+impl ReservePolicy {
+    /// 解析策略：fixed <N> / fraction <F> / explicit <N>
+    fn parse(kind: &str, value: &str) -> Option<Self> {
+        match kind.to_lowercase().as_str() {
+            "fixed" => value.parse().ok().map(Self::FixedReserve),
+            "fraction" => value.parse::<f32>().ok()
+                .filter(|f| (0.0..1.0).contains(f))
+                .map(Self::Fraction),
+            "explicit" => value.parse().ok().filter(|n| *n > 0).map(Self::Explicit),
+            _ => None,
+        }
+    }
+    
+    /// 策略的文本形式（可被 parse 读回，用于配置文件）
+    fn name(&self) -> String {
+        match self {
+            Self::FixedReserve(n) => format!("fixed {}", n),
+            Self::Fraction(f) => format!("fraction {}", f),
+            Self::Explicit(n) => format!("explicit {}", n),
+        }
+    }
+    
+    /// 根据逻辑核心数计算计算线程数（至少1个）
+    /// 默认策略在 8 核及以下与旧版“保留2核”一致，16 核保留2个、32 核保留4个
+    fn worker_count(&self, logic_cores: usize) -> usize {
+        let reserved = match *self {
+            Self::FixedReserve(n) => n,
+            Self::Fraction(f) => ((logic_cores as f32 * f).floor() as usize).max(SCHED_RESERVE_MIN),
+            Self::Explicit(n) => return n.clamp(1, logic_cores.max(1)),
+        };
+        logic_cores.saturating_sub(reserved).max(1)
+    }
+}
+
+/// 调度器执行后端
+enum SchedulerBackend {
+    Pool(rayon::ThreadPool),   // Rayon线程池
+    Inline,                    // 线程池创建失败时的退路：在调用线程中直接执行
 }
 
 impl AefrScheduler {
     /// 创建调度器，根据CPU核心数智能分配线程
-    fn new() -> Self {
+    /// 受限环境（部分 Android 系统）可能禁止创建线程，此时返回错误，由调用方退回 inline()
+    fn new(lower_priority: bool, policy: ReservePolicy) -> Result<Self, rayon::ThreadPoolBuildError> {
         let logic_cores = std::thread::available_parallelism()
             .map(|n| n.get())
             .unwrap_or(4);  // 默认4核
         
-        Self { 
-            pool: rayon::ThreadPoolBuilder::new()
-                .num_threads(if logic_cores > 2 { 
-                    logic_cores - 2  // 保留2个核心
-                } else { 
-                    1  // 单核或双核系统使用1个线程
-                })
-                .build()
-                .unwrap() 
+        let worker_count = policy.worker_count(logic_cores);
+        
+        // 每个工作线程启动时自行降低优先级，结果计入计数器
+        let succeeded = Arc::new(AtomicUsize::new(0));
+        let failed = Arc::new(AtomicUsize::new(0));
+        let mut builder = rayon::ThreadPoolBuilder::new().num_threads(worker_count);
+        if lower_priority {
+            let (ok, err) = (succeeded.clone(), failed.clone());
+            builder = builder.start_handler(move |_| {
+                match lower_current_thread_priority() {
+                    Ok(()) => ok.fetch_add(1, Ordering::Relaxed),
+                    Err(_) => err.fetch_add(1, Ordering::Relaxed),
+                };
+            });
+        }
+        let pool = builder.build()?;
+        // 在所有工作线程上执行一次空任务，确保启动回调均已运行，计数完整
+        pool.broadcast(|_| ());
+        
+        Ok(Self { 
+            backend: SchedulerBackend::Pool(pool),
+            worker_count,
+            policy,
+            lower_priority,
+            priority_result: (succeeded.load(Ordering::Relaxed), failed.load(Ordering::Relaxed)),
+        })
+    }
+    
+    /// 单线程调度器：所有任务在调用线程中串行执行
+    fn inline(policy: ReservePolicy) -> Self {
+        Self { backend: SchedulerBackend::Inline, worker_count: 1, policy, lower_priority: false, priority_result: (0, 0) }
+    }
+    
+    /// 创建调度器，失败时退回单线程执行，并返回需要记录的日志
+    fn new_or_inline(lower_priority: bool, policy: ReservePolicy) -> (Self, String) {
+        match Self::new(lower_priority, policy) {
+            Ok(scheduler) => {
+                let log = format!("[系统] 调度器: {}", scheduler.describe());
+                (scheduler, log)
+            }
+            Err(e) => (
+                Self::inline(policy),
+                format!("[警告] 线程池创建失败，角色将在主线程中串行更新: {}", e),
+            ),
+        }
+    }
+    
+    /// 调度器状态描述（线程数与优先级设置结果）
+    fn describe(&self) -> String {
+        if !self.is_parallel() {
+            return "单线程 (inline)".into();
         }
+        let priority = if !self.lower_priority {
+            "优先级未调整".to_string()
+        } else if self.priority_result.1 == 0 {
+            format!("{} 个线程已降低优先级 (nice {})", self.priority_result.0, SCHED_WORKER_NICE)
+        } else {
+            format!("降低优先级失败 {}/{} 个线程", self.priority_result.1, self.worker_count)
+        };
+        format!("{} 工作线程 (策略 {}), {}", self.worker_count, self.policy.name(), priority)
+    }
+    
+    /// 是否拥有线程池（Inline 后端下调用方应使用串行迭代，避免落入 Rayon 全局线程池）
+    fn is_parallel(&self) -> bool {
+        matches!(self.backend, SchedulerBackend::Pool(_))
     }
     
     /// 在调度器线程池中执行并行任务
-    fn run_parallel<OP>(&self, op: OP) 
+    /// 返回：整个并行块的墙钟耗时
+    fn run_parallel<OP>(&self, op: OP) -> Duration
     where 
         OP: FnOnce() + Send 
     { 
-        self.pool.install(op);  // 在当前线程池中安装并执行
+        let start = Instant::now();
+        match &self.backend {
+            SchedulerBackend::Pool(pool) => pool.install(op),  // 在当前线程池中安装并执行
+            SchedulerBackend::Inline => op(),
+        }
+        start.elapsed()
+    }
+}
+
+/// 降低当前线程的调度优先级
+/// Linux/Android 上 setpriority(PRIO_PROCESS, 0, ..) 只作用于调用线程（NPTL 线程各有独立 nice 值）
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn lower_current_thread_priority() -> std::io::Result<()> {
+    extern "C" {
+        fn setpriority(which: std::os::raw::c_int, who: std::os::raw::c_uint, prio: std::os::raw::c_int) -> std::os::raw::c_int;
     }
+    const PRIO_PROCESS: std::os::raw::c_int = 0;
+    // SAFETY: setpriority 仅读取整数参数，不涉及指针
+    if unsafe { setpriority(PRIO_PROCESS, 0, SCHED_WORKER_NICE) } == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+/// 其他平台暂不支持调整线程优先级
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn lower_current_thread_priority() -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "当前平台不支持"))
 }
 
 /// 应用命令枚举：主线程与工作线程间的通信协议
 /// 所有异步操作都通过此枚举进行消息传递
 enum AppCommand {
     /// 更新对话内容
-    Dialogue { name: String, affiliation: String, content: String },
+    Dialogue { name: String, affiliation: String, content: String, plate: NamePlate, voice: Option<String> },
     /// 请求加载Spine资源
     RequestLoad { slot_idx: usize, path: String },
-    /// Spine资源加载成功
-    LoadSuccess(usize, Box<SpineObject>, egui::ColorImage, String, Vec<String>),
+    /// Spine资源加载成功 (槽位, 角色, 纹理, 动画列表)
+    LoadSuccess(usize, Box<SpineObject>, LoadedTexture, Vec<String>),
+    /// Spine资源加载失败 (槽位, 错误信息)
+    LoadFailed(usize, String),
     /// 移除角色
     RemoveCharacter(usize),
     /// 加载背景图片
     LoadBackground(String),
     /// 背景图片加载成功
     LoadBackgroundSuccess(egui::ColorImage),
+    /// 在背景之上叠加一个图层
+    AddBackgroundLayer(String),
+    /// 背景图层解码完成 (路径, 图像)
+    BackgroundLayerReady(String, egui::ColorImage),
+    /// 清除全部背景图层
+    ClearBackgroundLayers,
+    /// 绑定骨骼道具 (槽位, 骨骼名, 贴图路径)
+    AttachProp { slot_idx: usize, bone: String, path: String },
+    /// 道具贴图解码完成
+    PropReady { slot_idx: usize, bone: String, path: String, image: egui::ColorImage },
+    /// 解除骨骼道具
+    DetachProp { slot_idx: usize, bone: String },
+    /// 加载序列帧背景 (帧目录, 帧率)
+    LoadAnimatedBackground { dir: String, fps: f32 },
+    /// 序列帧背景加载成功 (帧图像, 帧率)
+    LoadAnimatedBackgroundSuccess(Vec<egui::ColorImage>, f32),
     /// 播放背景音乐
     PlayBgm(String),
     /// 播放音效
     PlaySe(String),
     /// 音频数据准备就绪
     AudioReady(Vec<u8>, bool),  // (音频数据, 是否为BGM)
+    /// 按角色位置声像播放音效（声像由角色屏幕X坐标推算）
+    PlaySeAt { path: String, slot_idx: usize },
+    /// 带声像的音效数据准备就绪
+    AudioReadyPanned(Vec<u8>, f32),  // (音频数据, 声像 -1左 ~ 1右)
+    /// 打字音效数据准备就绪
+    TypeSfxReady(Vec<u8>),
+    /// 台词语音数据准备就绪 (台词序号, 音频数据)；序号过期说明台词已切换，丢弃
+    VoiceReady(u64, Vec<u8>),
     /// 停止背景音乐
     StopBgm,
+    /// 暂停背景音乐（保留播放位置）
+    PauseBgm,
+    /// 继续播放背景音乐
+    ResumeBgm,
     /// 设置角色动画
-    SetAnimation { slot_idx: usize, anim_name: String, loop_anim: bool },
+    SetAnimation { slot_idx: usize, track: usize, anim_name: String, loop_anim: bool },
+    /// 设置动画轨道的混合参数（叠加层透明度/加法混合）
+    SetTrackMix { slot_idx: usize, track: usize, mix: TrackMix },
+    /// 清空动画轨道（清空轨道1+即恢复基础姿势）
+    ClearTrack { slot_idx: usize, track: usize },
+    /// 设置/取消轨道0交替播放
+    SetPingPong { slot_idx: usize, pair: Option<(String, String)> },
+    /// 应用配置（需要 egui 上下文以重建字体）
+    ApplyConfig(Box<AppConfig>),
+    /// 设置渲染缩放（需要 egui 上下文）
+    SetRenderScale(f32),
+    /// 隐藏/显示角色（不卸载）；pause 为 Some 时同时设置动画暂停
+    SetVisible { slot_idx: usize, visible: bool, pause: Option<bool> },
+    /// 重置到初始姿势（清除覆盖与所有轨道，restart 为 true 时重新播放默认动画）
+    ResetToSetup { slot_idx: usize, restart: bool },
+    /// 覆盖骨骼旋转角度（每帧在动画应用后重新施加）
+    SetBoneRotation { slot_idx: usize, bone_name: String, degrees: f32 },
+    /// 设置角色整体不透明度（0~1）
+    SetAlpha { slot_idx: usize, alpha: f32 },
+    /// 设置角色整体旋转角度（度）
+    SetRotation { slot_idx: usize, degrees: f32 },
+    /// 修正骨骼坐标翻转（None 表示保持不变）
+    SetCoord { slot_idx: usize, flip_x: Option<bool>, flip_y: Option<bool> },
+    /// 替换Spine插槽附件（None表示隐藏该插槽）
+    SetAttachment { slot_idx: usize, slot_name: String, attachment_name: Option<String> },
+    /// 隐藏/显示整个Spine插槽
+    SetSlotHidden { slot_idx: usize, slot_name: String, hidden: bool },
+    /// 设置两个动画之间的过渡时长
+    SetMix { slot_idx: usize, from: String, to: String, seconds: f32 },
+    /// 绑定口型同步（None表示解除）
+    SetLipSync(Option<(usize, String, String, Option<String>)>),
+    /// 在指定轨道上限次播放动画（times ≥ 1），播完后停在最后一帧或接续 then_anim
+    PlayAnimationTimes { slot_idx: usize, track: usize, anim_name: String, times: u32, then_anim: Option<String> },
+    /// 非循环动画播放完成（由UI线程从角色事件缓冲区转发）
+    AnimComplete { slot_idx: usize, anim_name: String },
+    /// 属性补间完成（含被新补间替换前已自然结束的情况，不含被取消的）
+    TweenComplete { slot_idx: usize, prop: &'static str },
     /// 日志消息
     Log(String),
+    /// 窗口控制（标题/尺寸/全屏），仅桌面端有效
+    Viewport(egui::ViewportCommand),
     /// 剧本加载完成
     ScenarioLoaded(Scenario),
 }
 
+impl AppCommand {
+    /// 命令针对的角色槽位：(槽位, 是否要求槽位中已有角色)
+    /// handle_async_events 据此统一校验，越界或空槽位时输出错误而不是静默忽略
+    /// 内部事件（加载结果、动画/补间完成）由发出方保证有效，不在此列
+    fn target_slot(&self) -> Option<(usize, bool)> {
+        match self {
+            Self::RequestLoad { slot_idx, .. }
+            | Self::AttachProp { slot_idx, .. }
+            | Self::DetachProp { slot_idx, .. }
+            | Self::PlaySeAt { slot_idx, .. } => Some((*slot_idx, false)),
+            Self::RemoveCharacter(idx) => Some((*idx, false)),
+            Self::SetAnimation { slot_idx, .. }
+            | Self::SetTrackMix { slot_idx, .. }
+            | Self::ClearTrack { slot_idx, .. }
+            | Self::SetPingPong { slot_idx, .. }
+            | Self::SetVisible { slot_idx, .. }
+            | Self::ResetToSetup { slot_idx, .. }
+            | Self::SetBoneRotation { slot_idx, .. }
+            | Self::SetAlpha { slot_idx, .. }
+            | Self::SetRotation { slot_idx, .. }
+            | Self::SetCoord { slot_idx, .. }
+            | Self::SetAttachment { slot_idx, .. }
+            | Self::SetSlotHidden { slot_idx, .. }
+            | Self::SetMix { slot_idx, .. }
+            | Self::PlayAnimationTimes { slot_idx, .. } => Some((*slot_idx, true)),
+            Self::SetLipSync(Some((slot_idx, ..))) => Some((*slot_idx, true)),
+            _ => None,
+        }
+    }
+}
+
+/// 振幅采样适配器：为口型同步提供当前播放电平
+/// rodio 不提供输出电平查询接口，因此在解码后的采样流外包一层 Source：
+/// 对每个采样取绝对值做峰值包络（瞬时起音、指数释放），
+/// 每隔一小段采样将包络以 f32 位模式写入原子变量，供 UI 线程无锁读取。
+struct AmplitudeTap<S> {
+    inner: S,
+    level: Arc<AtomicU32>,   // 共享电平（f32::to_bits）
+    envelope: f32,           // 当前包络值（0~1）
+    counter: u32,            // 写回计数器，降低原子写入频率
+}
+
+impl<S> AmplitudeTap<S> {
+    fn new(inner: S, level: Arc<AtomicU32>) -> Self {
+        Self { inner, level, envelope: 0.0, counter: 0 }
+    }
+}
+
+impl<S: Source<Item = i16>> Iterator for AmplitudeTap<S> {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        let sample = self.inner.next();
+        let amp = sample.map_or(0.0, |v| (v as f32 / i16::MAX as f32).abs());
+        self.envelope = amp.max(self.envelope * LIPSYNC_RELEASE);
+        
+        self.counter += 1;
+        if self.counter >= 256 || sample.is_none() {
+            self.counter = 0;
+            let published = if sample.is_none() { 0.0 } else { self.envelope };
+            self.level.store(published.to_bits(), Ordering::Relaxed);
+        }
+        sample
+    }
+}
+
+impl<S: Source<Item = i16>> Source for AmplitudeTap<S> {
+    fn current_frame_len(&self) -> Option<usize> { self.inner.current_frame_len() }
+    fn channels(&self) -> u16 { self.inner.channels() }
+    fn sample_rate(&self) -> u32 { self.inner.sample_rate() }
+    fn total_duration(&self) -> Option<std::time::Duration> { self.inner.total_duration() }
+}
+
 /// 音频管理器：封装rodio音频播放功能
 struct AudioManager {
     _stream: rodio::OutputStream,           // 必须持有，否则流会被丢弃
     _stream_handle: rodio::OutputStreamHandle, // 音频流句柄
     bgm_sink: rodio::Sink,                  // BGM音频槽（支持循环）
     se_sink: rodio::Sink,                   // 音效音频槽（单次播放）
+    voice_sink: rodio::Sink,                // 语音音频槽（切换台词时单独停止，不影响音效）
+    type_sink: rodio::Sink,                 // 打字音效槽（上一声未播完时跳过，永不排队）
+    bgm_level: Arc<AtomicU32>,              // BGM当前电平（口型同步用）
+    se_level: Arc<AtomicU32>,               // 音效当前电平（口型同步用）
+    voice_level: Arc<AtomicU32>,            // 语音当前电平（口型同步用）
+    device_name: Option<String>,            // 初始化时的默认输出设备名（用于检测设备变化）
 }
 
 impl AudioManager {
@@ -365,25 +1243,40 @@ impl AudioManager {
         let (_stream, stream_handle) = rodio::OutputStream::try_default()
             .map_err(|e| e.to_string())?;
         
-        // 创建两个独立的音频槽：BGM和音效
+        // 创建三个独立的音频槽：BGM、音效和语音
         let bgm_sink = rodio::Sink::try_new(&stream_handle)
             .map_err(|e| e.to_string())?;
         let se_sink = rodio::Sink::try_new(&stream_handle)
             .map_err(|e| e.to_string())?;
+        let voice_sink = rodio::Sink::try_new(&stream_handle)
+            .map_err(|e| e.to_string())?;
+        let type_sink = rodio::Sink::try_new(&stream_handle)
+            .map_err(|e| e.to_string())?;
+        type_sink.set_volume(TYPE_SFX_VOLUME);
         
         Ok(Self { 
             _stream, 
             _stream_handle: stream_handle, 
             bgm_sink, 
-            se_sink 
+            se_sink,
+            voice_sink,
+            type_sink,
+            bgm_level: Arc::new(AtomicU32::new(0)),
+            se_level: Arc::new(AtomicU32::new(0)),
+            voice_level: Arc::new(AtomicU32::new(0)),
+            device_name: default_output_device_name(),
         })
     }
     
     /// 播放背景音乐（自动循环）
+    /// 若当前处于暂停状态，旧曲目被丢弃，新曲目从头正常播放
     fn play_bgm(&self, data: Vec<u8>) {
         if let Ok(source) = rodio::Decoder::new(Cursor::new(data)) {
             self.bgm_sink.stop();  // 停止当前BGM
-            self.bgm_sink.append(source.repeat_infinite());  // 无限循环
+            self.bgm_sink.append(AmplitudeTap::new(
+                source.repeat_infinite(),  // 无限循环
+                self.bgm_level.clone()
+            ));
             self.bgm_sink.play();
         }
     }
@@ -391,39 +1284,740 @@ impl AudioManager {
     /// 播放音效（单次）
     fn play_se(&self, data: Vec<u8>) {
         if let Ok(source) = rodio::Decoder::new(Cursor::new(data)) { 
-            self.se_sink.append(source); 
+            self.se_sink.append(AmplitudeTap::new(source, self.se_level.clone())); 
             self.se_sink.play(); 
         }
     }
     
+    /// 按声像播放音效（单次），pan ∈ [-1, 1]，-1 为最左，1 为最右
+    /// 采用等功率声像：先混为单声道，再按 cos/sin 分配左右声道音量
+    fn play_positioned(&self, data: Vec<u8>, pan: f32) {
+        if let Ok(source) = rodio::Decoder::new(Cursor::new(data)) {
+            let angle = (pan.clamp(-1.0, 1.0) + 1.0) * std::f32::consts::FRAC_PI_4;
+            // ChannelVolume 会将输入各声道相加混为单声道，按声道数归一避免削波
+            let norm = 1.0 / source.channels().max(1) as f32;
+            let panned = rodio::source::ChannelVolume::new(
+                source, 
+                vec![angle.cos() * norm, angle.sin() * norm]
+            );
+            self.se_sink.append(AmplitudeTap::new(panned, self.se_level.clone()));
+            self.se_sink.play();
+        }
+    }
+    
+    /// 播放台词语音（替换正在播放的语音）
+    fn play_voice(&self, data: Vec<u8>) {
+        if let Ok(source) = rodio::Decoder::new(Cursor::new(data)) {
+            self.voice_sink.stop();
+            self.voice_sink.append(AmplitudeTap::new(source, self.voice_level.clone()));
+            self.voice_sink.play();
+        }
+    }
+    
+    /// 播放一声打字音效；上一声尚未播完时直接跳过，避免在音频槽中堆积
+    fn play_type_click(&self, data: &[u8]) {
+        if !self.type_sink.empty() {
+            return;
+        }
+        if let Ok(source) = rodio::Decoder::new(Cursor::new(data.to_vec())) {
+            self.type_sink.append(source);
+            self.type_sink.play();
+        }
+    }
+    
+    /// 冻结时暂停语音与BGM（保留播放位置）；解冻时继续语音，BGM 仅在冻结前正在播放时继续
+    fn set_frozen(&self, frozen: bool, resume_bgm: bool) {
+        if frozen {
+            self.voice_sink.pause();
+            self.bgm_sink.pause();
+        } else {
+            self.voice_sink.play();
+            if resume_bgm {
+                self.bgm_sink.play();
+            }
+        }
+    }
+    
+    /// 停止台词语音
+    fn stop_voice(&self) {
+        self.voice_sink.stop();
+        self.voice_level.store(0, Ordering::Relaxed);
+    }
+    
     /// 停止背景音乐
     fn stop_bgm(&self) { 
         self.bgm_sink.stop(); 
+        self.bgm_level.store(0, Ordering::Relaxed);
+    }
+    
+    /// 暂停背景音乐（保留播放位置，可继续）
+    fn pause_bgm(&self) {
+        self.bgm_sink.pause();
+    }
+    
+    /// 从暂停位置继续播放背景音乐
+    fn resume_bgm(&self) {
+        self.bgm_sink.play();
+    }
+    
+    /// BGM播放状态描述
+    fn bgm_state(&self) -> &'static str {
+        if self.bgm_sink.empty() {
+            "已停止"
+        } else if self.bgm_sink.is_paused() {
+            "已暂停"
+        } else {
+            "播放中"
+        }
+    }
+    
+    /// 当前输出电平（0~1）：取正在播放的BGM、音效与语音电平的最大值
+    fn output_level(&self) -> f32 {
+        let read = |sink: &rodio::Sink, level: &AtomicU32| {
+            if sink.empty() || sink.is_paused() { 
+                0.0 
+            } else { 
+                f32::from_bits(level.load(Ordering::Relaxed)) 
+            }
+        };
+        read(&self.bgm_sink, &self.bgm_level)
+            .max(read(&self.se_sink, &self.se_level))
+            .max(read(&self.voice_sink, &self.voice_level))
     }
 }
 
+/// 记录一组文件的当前修改时间（文件不存在时为None）
+fn snapshot_mtimes(files: &[PathBuf]) -> Vec<(PathBuf, Option<SystemTime>)> {
+    files.iter()
+        .map(|f| (f.clone(), std::fs::metadata(f).and_then(|m| m.modified()).ok()))
+        .collect()
+}
+
+/// 查询系统当前默认音频输出设备名称（无设备时返回None）
+fn default_output_device_name() -> Option<String> {
+    rodio::cpal::default_host()
+        .default_output_device()
+        .and_then(|d| d.name().ok())
+}
+
+/// 口型同步绑定：按音频电平切换角色嘴部插槽的附件
+struct LipSyncBinding {
+    slot_idx: usize,                 // 角色槽位
+    mouth_slot: String,              // 嘴部Spine插槽名
+    open_attachment: String,         // 张嘴附件
+    closed_attachment: Option<String>, // 闭嘴附件（None时恢复动画/初始附件）
+    is_open: bool,                   // 当前是否张嘴
+}
+
 // ============================================================================
 // Spine 2D骨骼动画对象
 // ============================================================================
-/// Spine动画对象：封装rusty_spine的C绑定，提供Rust友好接口
-pub struct SpineObject {
-    pub position: Pos2,                     // 屏幕位置
-    pub scale: f32,                         // 缩放系数
-    _texture: Option<TextureHandle>,        // 纹理句柄（保持所有权）
-    texture_id: Option<TextureId>,          // 纹理ID（用于渲染）
-    
-    // 顶点缓冲区：预分配重用，实现零分配渲染
-    world_vertices: Vec<f32>,
+/// 角色在舞台上的定位方式
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum StagePlacement {
+    #[default]
+    Auto,                                   // 按当前阵型自动排布
+    Relative(f32, Option<f32>),             // 相对屏幕的比例位置 (x, y)，y 为 None 时脚底贴合舞台底部
+    Absolute(Pos2),                         // 绝对像素位置
+}
 
-    // rusty_spine核心组件
-    skeleton: Skeleton,                     // 骨骼实例
-    state: AnimationState,                  // 动画状态机
-    _state_data: Arc<AnimationStateData>,   // 动画状态数据（引用计数）
-    _skeleton_data: Arc<rusty_spine::SkeletonData>, // 骨骼数据（引用计数）
-    _atlas: Arc<Atlas>,                     // 纹理图集（引用计数）
+/// 角色定位锚点：pos/阵型给出的舞台点对应角色的哪个位置
+/// 脚底/中心由首帧网格包围盒（骨骼坐标）换算，不受骨骼根节点在导出时放在何处的影响
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum StageAnchor {
+    #[default]
+    Root,                                   // 骨骼根节点（旧行为，BA 角色通常即脚底）
+    Feet,                                   // 包围盒底边中点
+    Center,                                 // 包围盒中心
 }
 
-// 【必要性证明 (Proof of Necessity)】
+impl StageAnchor {
+    fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "root" => Some(StageAnchor::Root),
+            "feet" => Some(StageAnchor::Feet),
+            "center" => Some(StageAnchor::Center),
+            _ => None,
+        }
+    }
+    
+    fn name(self) -> &'static str {
+        match self {
+            StageAnchor::Root => "root",
+            StageAnchor::Feet => "feet",
+            StageAnchor::Center => "center",
+        }
+    }
+}
+
+/// 脚下阴影（shadowblob 命令设置）：脚底中点处的椭圆软阴影，随角色位置、缩放与不透明度变化
+#[derive(Clone, Copy, PartialEq)]
+pub struct ShadowBlob {
+    pub size: f32,     // 宽度（角色包围盒宽度的比例）
+    pub opacity: f32,  // 中心不透明度（0~1），边缘渐隐到透明
+}
+
+impl Default for ShadowBlob {
+    fn default() -> Self {
+        Self { size: SHADOW_BLOB_SIZE, opacity: SHADOW_BLOB_OPACITY }
+    }
+}
+
+/// 自动排布阵型：对未手动定位的角色按出场顺序排列
+#[derive(Clone, Copy, PartialEq, Default)]
+enum Formation {
+    #[default]
+    Line,       // 一字排开
+    Arc,        // 弧形：两端靠前，中间靠后
+    V,          // V形：中间靠前，两端靠后
+    Staggered,  // 前后两排交错
+}
+
+impl Formation {
+    fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "line" => Some(Formation::Line),
+            "arc" => Some(Formation::Arc),
+            "v" => Some(Formation::V),
+            "staggered" => Some(Formation::Staggered),
+            _ => None,
+        }
+    }
+    
+    fn name(self) -> &'static str {
+        match self {
+            Formation::Line => "line",
+            Formation::Arc => "arc",
+            Formation::V => "v",
+            Formation::Staggered => "staggered",
+        }
+    }
+    
+    /// 第 rank 个（共 count 个）自动排布角色的脚底位置
+    /// 水平方向在安全区内等分，纵深按屏幕高度比例计算，窗口尺寸变化时随之重算
+    fn place(self, rank: usize, count: usize, safe: Rect, foot_y: f32, screen_height: f32) -> Pos2 {
+        let t = (rank as f32 + 0.5) / count.max(1) as f32;  // 0~1，每人占一等份的中点
+        let u = t * 2.0 - 1.0;                               // -1~1，0 为舞台中央
+        let depth = FORMATION_DEPTH * screen_height;
+        let lift = match self {
+            Formation::Line => 0.0,
+            Formation::Arc => depth * (1.0 - u * u),
+            Formation::V => depth * u.abs(),
+            Formation::Staggered => if rank % 2 == 1 { depth } else { 0.0 },
+        };
+        Pos2::new(safe.left() + safe.width() * t, foot_y - lift)
+    }
+}
+
+/// 跟随骨骼的道具贴图（武器、特效等）
+struct Prop {
+    slot_idx: usize,          // 所属角色槽位
+    bone: String,             // 跟随的骨骼名
+    path: String,             // 贴图路径（用于日志）
+    texture: TextureHandle,   // 道具纹理
+}
+
+/// 撤销用的槽位快照：只记录编辑操作会修改的内容
+#[derive(Clone, PartialEq)]
+struct SlotSnapshot {
+    path: String,                         // 角色来源（不同时需要重新加载）
+    placement: StagePlacement,
+    anchor: StageAnchor,
+    shadow: Option<ShadowBlob>,
+    user_offset: Vec2,
+    user_scale: f32,
+    rotation: f32,
+    alpha: f32,
+    visible: bool,
+    paused: bool,
+    animation: Option<(String, bool)>,    // 轨道0动画名与是否循环
+}
+
+/// 一步撤销：操作前后的全部槽位状态
+struct UndoEntry {
+    before: Vec<Option<SlotSnapshot>>,
+    after: Vec<Option<SlotSnapshot>>,
+}
+
+/// 可复现的随机数：以 (种子, 通道, 序号) 为输入的无状态哈希（splitmix64）
+/// 随机效果按“效果开始后的时间”取序号，而非按帧消耗随机数，
+/// 因此相同种子 + 相同命令时间线在任何帧率下都得到相同画面（录制/导出可复现）
+/// 使用者：shake（画面震动）
+fn stage_noise(seed: u64, channel: u64, index: u64) -> f32 {
+    let mut z = seed
+        .wrapping_add(channel.wrapping_mul(0xD6E8_FEB8_6659_FD93))
+        .wrapping_add(index.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    (z >> 40) as f32 / (1u64 << 23) as f32 - 1.0  // 映射到 [-1, 1)
+}
+
+/// 画面震动：随机偏移按采样频率插值，强度线性衰减到 0
+struct Shake {
+    strength: f32,   // 初始最大偏移（像素）
+    duration: f32,
+    elapsed: f32,
+}
+
+impl Shake {
+    fn offset(&self, seed: u64) -> Vec2 {
+        let decay = 1.0 - (self.elapsed / self.duration).min(1.0);
+        let pos = self.elapsed * SHAKE_SAMPLE_RATE;
+        let (k, frac) = (pos.floor() as u64, pos.fract());
+        let sample = |channel| {
+            let a = stage_noise(seed, channel, k);
+            let b = stage_noise(seed, channel, k + 1);
+            a + (b - a) * frac
+        };
+        Vec2::new(sample(0), sample(1)) * self.strength * decay
+    }
+}
+
+/// 角色层快照：用于整体交叉淡化
+/// egui 0.27 未提供离屏渲染目标，因此快照保存的是角色层的网格（顶点/索引），
+/// 而非GPU纹理：内存开销约为 顶点数 × 20 字节 + 索引数 × 4 字节，
+/// 另外会让旧角色对象及其纹理在淡化结束前保持存活（每个角色一张图集页的显存）
+struct StageSnapshot {
+    shapes: Vec<Shape>,                     // 冻结的角色层网格
+    _keep_alive: Vec<Arc<Mutex<SpineObject>>>, // 持有旧角色，保证网格引用的纹理在淡化期间有效
+    duration: f32,
+    elapsed: f32,
+}
+
+impl StageSnapshot {
+    /// 淡化进度 0~1（缓入缓出）
+    fn progress(&self) -> f32 {
+        let t = if self.duration > 0.0 { (self.elapsed / self.duration).min(1.0) } else { 1.0 };
+        Ease::InOut.apply(t)
+    }
+}
+
+/// 登场/退场过渡：在给定时长内将位置偏移与透明度从起点缓动到终点
+/// 偏移以屏幕尺寸为单位，窗口缩放时过渡轨迹随之缩放
+#[derive(Clone, Copy)]
+struct Transition {
+    from_offset: Vec2,
+    to_offset: Vec2,
+    from_alpha: f32,
+    to_alpha: f32,
+    duration: f32,
+    elapsed: f32,
+    remove_on_finish: bool,                 // 结束后卸载角色（退场）
+    exit_reported: bool,                    // 退场结束已报告（时长为 0 时开始即已结束，不能以 finished 判断）
+}
+
+impl Transition {
+    /// 当前 (偏移, 透明度)，使用三次缓出曲线
+    fn current(&self) -> (Vec2, f32) {
+        let t = if self.duration > 0.0 { (self.elapsed / self.duration).clamp(0.0, 1.0) } else { 1.0 };
+        let k = 1.0 - (1.0 - t).powi(3);
+        (
+            self.from_offset + (self.to_offset - self.from_offset) * k,
+            self.from_alpha + (self.to_alpha - self.from_alpha) * k,
+        )
+    }
+    
+    fn finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+/// 可补间的角色属性
+#[derive(Clone, Copy, PartialEq)]
+enum TweenProp {
+    X,          // 水平偏移（像素，相对舞台定位）
+    Y,          // 垂直偏移（像素，相对舞台定位）
+    Scale,      // 手动缩放倍率
+    Alpha,      // 不透明度
+    Rotation,   // 旋转角度（度）
+}
+
+impl TweenProp {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "x" => Some(TweenProp::X),
+            "y" => Some(TweenProp::Y),
+            "scale" => Some(TweenProp::Scale),
+            "alpha" => Some(TweenProp::Alpha),
+            "rotation" | "rot" => Some(TweenProp::Rotation),
+            _ => None,
+        }
+    }
+    
+    fn name(self) -> &'static str {
+        match self {
+            TweenProp::X => "x",
+            TweenProp::Y => "y",
+            TweenProp::Scale => "scale",
+            TweenProp::Alpha => "alpha",
+            TweenProp::Rotation => "rotation",
+        }
+    }
+}
+
+/// 缓动曲线
+#[derive(Clone, Copy)]
+enum Ease {
+    Linear,
+    In,
+    Out,
+    InOut,
+}
+
+impl Ease {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "linear" => Some(Ease::Linear),
+            "in" | "ease-in" => Some(Ease::In),
+            "out" | "ease-out" => Some(Ease::Out),
+            "inout" | "ease-in-out" => Some(Ease::InOut),
+            _ => None,
+        }
+    }
+    
+    /// 将线性进度 t∈[0,1] 映射为缓动进度（三次曲线）
+    fn apply(self, t: f32) -> f32 {
+        match self {
+            Ease::Linear => t,
+            Ease::In => t * t * t,
+            Ease::Out => 1.0 - (1.0 - t).powi(3),
+            Ease::InOut => if t < 0.5 { 4.0 * t * t * t } else { 1.0 - (-2.0 * t + 2.0).powi(3) * 0.5 },
+        }
+    }
+}
+
+/// 属性补间：起始值在补间真正开始时读取，排队的补间因此从前一段的终点接续
+struct Tween {
+    slot_idx: usize,
+    prop: TweenProp,
+    from: Option<f32>,                      // 起始值（开始时捕获）
+    to: f32,
+    duration: f32,
+    elapsed: f32,
+    ease: Ease,
+}
+
+/// 过渡方向名称 -> 屏外偏移（屏幕尺寸比例），fade 为原地淡入淡出
+fn transition_offset(dir: &str) -> Option<Vec2> {
+    match dir {
+        "left" => Some(Vec2::new(-0.6, 0.0)),
+        "right" => Some(Vec2::new(0.6, 0.0)),
+        "bottom" => Some(Vec2::new(0.0, 0.6)),
+        "fade" => Some(Vec2::ZERO),
+        _ => None,
+    }
+}
+
+/// 命名舞台锚点：水平比例位置
+const STAGE_ANCHORS: &[(&str, f32)] = &[
+    ("left", 0.15),
+    ("center-left", 0.325),
+    ("center", 0.5),
+    ("center-right", 0.675),
+    ("right", 0.85),
+];
+
+/// 动画轨道混合配置：用于在轨道1+上叠加眨眼、呼吸等动画
+#[derive(Clone, Copy)]
+struct TrackMix {
+    alpha: f32,                             // 叠加权重（0~1）
+    additive: bool,                         // 是否使用加法混合
+}
+
+impl Default for TrackMix {
+    fn default() -> Self {
+        Self { alpha: 1.0, additive: false }
+    }
+}
+
+impl TrackMix {
+    /// 套用到单个轨道条目
+    fn apply_to(&self, entry: &mut rusty_spine::TrackEntry) {
+        entry.set_alpha(self.alpha.clamp(0.0, 1.0));
+        entry.set_mix_blend(if self.additive { MixBlend::Add } else { MixBlend::Replace });
+    }
+}
+
+/// 动画完成事件：由Spine监听器在计算线程中写入缓冲区
+/// 注意：监听器不直接跨线程回调，统一由UI线程取出后转发到命令总线
+struct AnimCompleteEvent {
+    track: usize,                           // 所在轨道
+    anim_name: String,                      // 动画名称
+    finished: bool,                         // 播放是否真正结束（循环动画与未播完的重复轮次为false）
+}
+
+/// 读取 .atlas 文本中的 pma 页属性（Spine 4.x 导出格式：`pma: true`）
+/// 仅检查第一页的页头（与纹理加载一致）：页名之后的 `键: 值` 行，遇到第一个区域名为止。
+/// 3.8 图集页头与区域之间没有空行，且不含该字段（视为直通Alpha，需要时用 LOAD ... pma=on 手动指定）
+fn atlas_page_is_pma(text: &str) -> bool {
+    text.lines()
+        .map(str::trim)
+        .skip_while(|line| line.is_empty())
+        .skip(1)  // 页名
+        .take_while(|line| line.contains(':'))
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.trim() == "pma")
+        .map_or(false, |(_, value)| value.trim().eq_ignore_ascii_case("true"))
+}
+
+/// 图集纹理转为 egui 颜色图像
+/// egui 纹理统一以预乘Alpha存储：直通Alpha的图片在此预乘，
+/// 已预乘（pma）导出的图集则原样使用，否则会二次预乘产生黑边
+fn page_color_image(size: [usize; 2], rgba: &[u8], premultiplied_alpha: bool) -> egui::ColorImage {
+    if premultiplied_alpha {
+        egui::ColorImage::from_rgba_premultiplied(size, rgba)
+    } else {
+        egui::ColorImage::from_rgba_unmultiplied(size, rgba)
+    }
+}
+
+/// 解码背景/背景图层图片
+/// PNG 等格式为直通Alpha：只在此处预乘一次，绘制时以白色着色按预乘Alpha混合到舞台底色/下层之上，
+/// 半透明边缘不会因二次预乘而变暗
+fn decode_background_image(path: &str) -> Result<egui::ColorImage, String> {
+    let img = image::open(path).map_err(|e| format!("无法读取背景 {}: {}", path, e))?;
+    Ok(egui::ColorImage::from_rgba_unmultiplied(
+        [img.width() as _, img.height() as _],
+        img.to_rgba8().as_raw(),
+    ))
+}
+
+/// 加载路径是否为 zip 资源包
+fn is_bundle_path(path: &str) -> bool {
+    path.to_lowercase().ends_with(".zip")
+}
+
+/// 加载路径是否为远程 URL
+fn is_url_path(path: &str) -> bool {
+    let lower = path.to_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://")
+}
+
+/// 网络错误前缀：与资源解析错误区分，日志中单独标注
+const NETWORK_ERROR_PREFIX: &str = "Network Error: ";
+/// 单个资源下载超时（秒）
+const DOWNLOAD_TIMEOUT_SECS: u64 = 30;
+
+/// 下载缓存目录：系统临时目录下按 URL 所在目录分组
+fn download_cache_dir(base_url: &str) -> PathBuf {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    base_url.hash(&mut hasher);
+    std::env::temp_dir().join("aefr_cache").join(format!("{:016x}", hasher.finish()))
+}
+
+/// 拆分 URL：(路径部分, 查询串（含前导 '?'，无则为空）)，片段（#...）丢弃
+fn split_url_query(url: &str) -> (&str, &str) {
+    let url = url.split('#').next().unwrap_or(url);
+    url.split_at(url.find('?').unwrap_or(url.len()))
+}
+
+/// 与 url 同目录的文件地址：替换路径最后一段，沿用原查询串（版本号、访问令牌等）
+fn sibling_url(url: &str, name: &str) -> String {
+    let (path, query) = split_url_query(url);
+    format!("{}{}{}", &path[..path.rfind('/').map_or(0, |i| i + 1)], name, query)
+}
+
+/// URL 对应的缓存文件名：去掉查询串与片段，取路径最后一段并做百分号解码
+/// 解码出的路径分隔符替换为 '_'，文件名不会越出缓存目录
+fn url_file_name(url: &str) -> String {
+    let (path, _) = split_url_query(url);
+    let raw = path.rsplit('/').next().unwrap_or_default();
+    let bytes = raw.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| raw.get(i + 1..i + 3))
+            .flatten()
+            .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(b) => { decoded.push(b); i += 3; }
+            None => { decoded.push(bytes[i]); i += 1; }
+        }
+    }
+    let name = String::from_utf8_lossy(&decoded).replace(['/', '\\'], "_");
+    if name.is_empty() || name == "." || name == ".." { "asset".into() } else { name }
+}
+
+/// 先写入同目录下的临时文件再改名：下载或写入中断时不会留下残缺的缓存文件
+fn write_atomic(path: &std::path::Path, bytes: &[u8]) -> std::io::Result<()> {
+    static PART_SEQ: AtomicUsize = AtomicUsize::new(0);  // 同一文件并发下载时各用各的临时文件
+    let mut part = path.as_os_str().to_owned();
+    part.push(format!(".{}.{}.part", std::process::id(), PART_SEQ.fetch_add(1, Ordering::Relaxed)));
+    std::fs::write(&part, bytes)?;
+    std::fs::rename(&part, path).map_err(|e| {
+        let _ = std::fs::remove_file(&part);
+        e
+    })
+}
+
+/// 下载单个文件（带本地缓存）；返回 Ok(None) 表示服务器上不存在（404）
+/// 已有缓存时携带上次的 ETag / Last-Modified 向服务器确认：304 直接使用缓存，否则下载新内容；
+/// 网络不可用时退回缓存，离线也能加载下载过的资源
+fn fetch_cached(url: &str, cache_dir: &std::path::Path) -> Result<Option<Vec<u8>>, String> {
+    let cache_path = cache_dir.join(url_file_name(url));
+    let mut meta_path = cache_path.as_os_str().to_owned();
+    meta_path.push(".meta");
+    let meta_path = PathBuf::from(meta_path);
+    let cached = std::fs::read(&cache_path).ok();
+    
+    // 1. 条件请求：验证信息按 "键: 值" 逐行保存在缓存文件旁的 .meta 中
+    let mut request = ureq::get(url).timeout(Duration::from_secs(DOWNLOAD_TIMEOUT_SECS));
+    if cached.is_some() {
+        for line in std::fs::read_to_string(&meta_path).unwrap_or_default().lines() {
+            match line.split_once(": ") {
+                Some(("etag", value)) => request = request.set("If-None-Match", value),
+                Some(("last-modified", value)) => request = request.set("If-Modified-Since", value),
+                _ => {}
+            }
+        }
+    }
+    let response = match request.call() {
+        Ok(response) if response.status() == 304 && cached.is_some() => return Ok(cached),
+        Ok(response) => response,
+        Err(ureq::Error::Status(404, _)) => return Ok(None),
+        Err(ureq::Error::Status(code, _)) => return Err(format!("{}HTTP {} ({})", NETWORK_ERROR_PREFIX, code, url)),
+        Err(ureq::Error::Transport(_)) if cached.is_some() => return Ok(cached),
+        Err(e) => return Err(format!("{}{} ({})", NETWORK_ERROR_PREFIX, e, url)),
+    };
+    let validators: String = [("etag", "ETag"), ("last-modified", "Last-Modified")]
+        .iter()
+        .filter_map(|(key, header)| response.header(header).map(|value| format!("{}: {}\n", key, value)))
+        .collect();
+    let mut bytes = Vec::new();
+    response.into_reader().read_to_end(&mut bytes)
+        .map_err(|e| format!("{}{} ({})", NETWORK_ERROR_PREFIX, e, url))?;
+    
+    // 2. 更新缓存（写入失败不影响本次加载）
+    // 先删除旧的验证信息：新内容写入失败时，不能让旧缓存被 304 当作最新版本
+    if std::fs::create_dir_all(cache_dir).is_ok() {
+        let _ = std::fs::remove_file(&meta_path);
+        if write_atomic(&cache_path, &bytes).is_ok() && !validators.is_empty() {
+            let _ = write_atomic(&meta_path, validators.as_bytes());
+        }
+    }
+    Ok(Some(bytes))
+}
+
+/// 骨骼坐标约定：标准导出为Y轴向上，需要翻转到屏幕坐标（Y轴向下）
+/// 部分社区导出已是Y轴向下或左右镜像，可逐角色修正
+#[derive(Clone, Copy, PartialEq)]
+pub struct CoordFlags {
+    pub flip_x: bool,
+    pub flip_y: bool,
+}
+
+impl Default for CoordFlags {
+    fn default() -> Self {
+        Self { flip_x: false, flip_y: true }
+    }
+}
+
+/// 资源缓存项：同一路径再次加载时共享图集、骨骼数据与已上传的纹理，只新建骨骼实例与动画状态
+/// 适用于同一角色多次登场（克隆、路人），以及一个图集内用不同皮肤打包的多个角色
+struct SharedAsset {
+    atlas: Arc<Atlas>,
+    skeleton_data: Arc<rusty_spine::SkeletonData>,
+    texture: TextureHandle,
+    texture_bytes: usize,
+    premultiplied_alpha: bool,
+    source_files: Vec<PathBuf>,
+    mtimes: Vec<(PathBuf, Option<SystemTime>)>,  // 登记时源文件的修改时间，不一致时缓存失效
+}
+
+impl SharedAsset {
+    /// 源文件自登记以来是否被修改（导出工具覆盖写入后不能再复用旧数据）
+    fn is_stale(&self) -> bool {
+        snapshot_mtimes(&self.source_files) != self.mtimes
+    }
+}
+
+/// 加载结果携带的纹理
+enum LoadedTexture {
+    Decoded(egui::ColorImage, String),  // 新解码的纹理页（像素, 页面名），由主线程上传
+    Shared,                             // 来自资源缓存，角色对象已持有共享纹理
+}
+
+/// Spine动画对象：封装rusty_spine的C绑定，提供Rust友好接口
+pub struct SpineObject {
+    pub position: Pos2,                     // 屏幕位置
+    pub scale: f32,                         // 缩放系数
+    pub user_offset: Vec2,                  // 手动拖动产生的位置偏移（叠加在自动布局之上）
+    pub user_scale: f32,                    // 手动缩放倍率（双指缩放/触控板捏合）
+    pub placement: StagePlacement,          // 舞台定位方式（pos 命令设置）
+    pub anchor: StageAnchor,                // 定位锚点（anchor 命令设置）
+    pub shadow: Option<ShadowBlob>,         // 脚下阴影（None 为不绘制）
+    pub fit_height: Option<f32>,            // 角色高度占屏幕高度的比例（None 使用默认缩放）
+    natural_height: Option<f32>,            // 首帧测得的骨骼高度（骨骼坐标单位）
+    natural_bounds: Option<Rect>,           // 首帧测得的网格包围盒（骨骼坐标单位，相对根节点），供锚点换算
+    pub alpha: f32,                         // 角色整体不透明度（0~1）
+    pub visible: bool,                      // 是否绘制（隐藏时保持加载并继续播放动画）
+    pub paused: bool,                       // 暂停动画时间（与 visible 独立）
+    pub rotation: f32,                      // 整体旋转角度（度，屏幕坐标下顺时针为正），绕 position 旋转
+    pub coord: CoordFlags,                  // 骨骼坐标到屏幕坐标的翻转设置
+    transition: Option<Transition>,         // 进行中的登场/退场过渡
+    transition_alpha: f32,                  // 过渡透明度（在 push_to_mesh 中乘入顶点颜色）
+    premultiplied_alpha: bool,              // 图集是否以预乘Alpha导出（atlas 页属性 pma）
+    source_path: String,                    // 加载来源（.atlas路径）
+    source_files: Vec<PathBuf>,             // 全部源文件（atlas/骨骼/纹理），供热重载监视
+    _texture: Option<TextureHandle>,        // 纹理句柄（保持所有权）
+    texture_id: Option<TextureId>,          // 纹理ID（用于渲染）
+    texture_bytes: usize,                   // 已上传纹理占用的显存（RGBA8 字节数）
+    texture_evicted: bool,                  // 纹理已因显存预算被回收，等待按需重新加载
+    last_painted: Option<Instant>,          // 最近一次生成非空网格的时刻（纹理回收按此排序）
+    mesh_bounds: Rect,                      // 上一次生成网格的包围盒（相对 position），供视口剔除
+    culled: bool,                           // 本帧完全位于舞台之外，未生成网格
+    
+    // 顶点缓冲区：预分配重用，实现零分配渲染
+    // 只增不减；仅在 build_mesh 中使用（计算线程紧接 update_parallel 调用，或快照/无窗口渲染时调用），
+    // 整个对象处于 Mutex 之内，因此不会被同时访问
+    world_vertices: Vec<f32>,
+    // 计算线程在更新后预先生成的网格，UI线程绘制时取走，只剩合批与提交
+    prebuilt_shapes: Vec<Shape>,
+    // 上一次预生成网格的耗时（仅在开启逐角色计时时更新）
+    last_build_time: Duration,
+    // 上一帧各 Mesh（按混合模式切分的顺序）的顶点/索引数，用于逐个预留容量，避免逐帧扩容
+    last_mesh_sizes: Vec<(usize, usize)>,
+    // 上一次 update_parallel 的耗时（仅在开启逐角色计时时更新）
+    last_update_time: Duration,
+
+    // rusty_spine核心组件
+    skeleton: Skeleton,                     // 骨骼实例
+    state: AnimationState,                  // 动画状态机
+    _state_data: Arc<AnimationStateData>,   // 动画状态数据（引用计数）
+    _skeleton_data: Arc<rusty_spine::SkeletonData>, // 骨骼数据（引用计数）
+    _atlas: Arc<Atlas>,                     // 纹理图集（引用计数）
+
+    // 动画完成事件缓冲区（监听器写入，UI线程取出）
+    completed_events: Arc<Mutex<Vec<AnimCompleteEvent>>>,
+
+    // 骨骼旋转覆盖表（骨骼名 -> 角度），动画每帧会重算骨骼，因此需每帧重新施加
+    bone_overrides: HashMap<String, f32>,
+    
+    // 附件覆盖表（插槽名 -> 附件名，None为隐藏），同样需每帧重新施加
+    attachment_overrides: HashMap<String, Option<String>>,
+    
+    // 隐藏插槽集合（插槽名），build_mesh 遍历绘制顺序时跳过，不影响动画对附件的切换
+    hidden_slots: HashSet<String>,
+    
+    // 动画对过渡时长（从, 到, 秒），已写入动画状态数据；保留一份供列表显示与保存
+    mix_rules: Vec<(String, String, f32)>,
+    
+    // 轨道0播放计数：loop_target 为 0 表示不限次数
+    loop_count: u32,
+    loop_target: u32,
+    
+    // 各轨道的混合配置（新动画条目设置到该轨道时自动套用）
+    track_mixes: HashMap<usize, TrackMix>,
+    
+    // 轨道0的交替播放 (动画A, 动画B)：两者以非循环条目轮流排队
+    pingpong: Option<(String, String)>,
+}
+
+// 【必要性证明 (Proof of Necessity)】
 // 原因：rusty_spine 底层封装了 C 指针，默认不支持跨线程运算。
 // 不可替代性：AEFR 需要使用 Rayon 在多个 CPU 核心上并行计算 5 人的 Spine 骨骼变形，以维持 144Hz 渲染。
 // 安全边界：我们已经在 AEFR 状态中强制使用 `Arc<Mutex<SpineObject>>` 对对象进行包裹封装。
@@ -433,87 +2027,435 @@ unsafe impl Send for SpineObject {}
 impl SpineObject {
     /// 异步加载Spine资源（不在GPU线程中加载纹理）
     /// 返回：(SpineObject实例, 纹理颜色数据, 页面名称, 动画列表)
-    fn load_async_no_gpu(path_str: &str) -> Result<(Self, egui::ColorImage, String, Vec<String>), String> {
-        // 1. 加载Atlas纹理图集
-        let atlas = Arc::new(
-            Atlas::new_from_file(std::path::Path::new(path_str))
-                .map_err(|e| format!("Atlas Error: {}", e))?
-        );
+    /// pma：手动指定纹理是否为预乘Alpha，None 时按图集页属性自动识别
+    fn load_async_no_gpu(path_str: &str, pma: Option<bool>) -> Result<(Self, egui::ColorImage, String, Vec<String>), String> {
+        // 0. 远程 URL 与 zip 资源包：整体读入内存后加载
+        if is_url_path(path_str) {
+            return Self::load_url(path_str, pma);
+        }
+        if is_bundle_path(path_str) {
+            return Self::load_bundle(path_str, pma);
+        }
+        
+        // 1. 读取图集文本
+        let atlas_path = std::path::Path::new(path_str);
+        let dir = atlas_path.parent().ok_or("Invalid path")?;
+        let atlas_bytes = std::fs::read(atlas_path).map_err(|e| format!("Atlas Error: {}", e))?;
+        
+        // 2. 查找并读取骨骼数据（.skel 二进制优先，其次 .json）
+        let skel_path = atlas_path.with_extension("skel");
+        let json_path = atlas_path.with_extension("json");
+        let skeleton_path = if skel_path.exists() {
+            skel_path
+        } else if json_path.exists() {
+            json_path
+        } else {
+            return Err("Missing .skel or .json".into());
+        };
+        let skeleton_bytes = std::fs::read(&skeleton_path).map_err(|e| format!("Skeleton read failed: {}", e))?;
+        
+        // 3. 纹理页相对图集所在目录读取，其余解析与内存加载完全相同
+        Self::load_from_memory(
+            path_str,
+            &atlas_bytes,
+            dir,
+            |page| std::fs::read(dir.join(page)).map_err(|e| format!("Image Load Error: {}: {}", page, e)),
+            &skeleton_bytes,
+            spine_companion_files(atlas_path),
+            pma,
+        )
+    }
+    
+    /// 从 URL 加载：下载 atlas、同目录下同名 .skel（或 .json）及图集引用的纹理
+    /// 下载内容缓存到临时目录，同一 URL 再次加载时先向服务器确认是否更新，未更新或离线时读取缓存
+    fn load_url(url: &str, pma: Option<bool>) -> Result<(Self, egui::ColorImage, String, Vec<String>), String> {
+        let (path, _) = split_url_query(url);
+        let base = &path[..path.rfind('/').map_or(0, |i| i + 1)];
+        let stem = path.strip_suffix(".atlas").unwrap_or(path);
+        let stem_name = &stem[base.len()..];
+        let cache_dir = download_cache_dir(base);
+        
+        let atlas_bytes = fetch_cached(url, &cache_dir)?
+            .ok_or_else(|| format!("{}HTTP 404 ({})", NETWORK_ERROR_PREFIX, url))?;
+        let skeleton_bytes = match fetch_cached(&sibling_url(url, &format!("{}.skel", stem_name)), &cache_dir)? {
+            Some(bytes) => bytes,
+            None => fetch_cached(&sibling_url(url, &format!("{}.json", stem_name)), &cache_dir)?
+                .ok_or_else(|| format!("{}服务器上缺少 .skel 或 .json ({})", NETWORK_ERROR_PREFIX, stem))?,
+        };
+        
+        let page_image = |page: &str| {
+            let page_url = sibling_url(url, &page.replace(' ', "%20"));
+            fetch_cached(&page_url, &cache_dir)?
+                .ok_or_else(|| format!("{}HTTP 404 ({})", NETWORK_ERROR_PREFIX, page_url))
+        };
+        
+        // 远程资源不参与热重载监视
+        Self::load_from_memory(url, &atlas_bytes, "", page_image, &skeleton_bytes, Vec::new(), pma)
+    }
+    
+    /// 从 zip 资源包加载：包内需含 .atlas、同名（或唯一的）.skel/.json 及图集引用的纹理
+    /// 资源全部在内存中解析，不向文件系统解压
+    fn load_bundle(zip_path: &str, pma: Option<bool>) -> Result<(Self, egui::ColorImage, String, Vec<String>), String> {
+        let file = std::fs::File::open(zip_path).map_err(|e| format!("Bundle Error: {}", e))?;
+        let mut archive = zip::ZipArchive::new(file).map_err(|e| format!("Bundle Error: {}", e))?;
+        let mut entries: HashMap<String, Vec<u8>> = HashMap::new();
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i).map_err(|e| format!("Bundle Error: {}", e))?;
+            if entry.is_dir() {
+                continue;
+            }
+            let mut data = Vec::with_capacity(entry.size() as usize);
+            std::io::Read::read_to_end(&mut entry, &mut data).map_err(|e| format!("Bundle Error: {}", e))?;
+            entries.insert(entry.name().replace('\\', "/"), data);
+        }
+        
+        // 1. 定位图集与骨骼文件
+        let atlas_name = entries.keys().filter(|n| n.to_lowercase().ends_with(".atlas")).min()
+            .cloned()
+            .ok_or("Bundle Error: 包内没有 .atlas 文件")?;
+        let stem = atlas_name.strip_suffix(".atlas").unwrap_or(&atlas_name).to_string();
+        let find_skeleton = |ext: &str| -> Option<String> {
+            let same = format!("{}.{}", stem, ext);
+            if entries.contains_key(&same) {
+                return Some(same);
+            }
+            let mut candidates: Vec<&String> = entries.keys().filter(|n| n.to_lowercase().ends_with(&format!(".{}", ext))).collect();
+            candidates.sort();
+            (candidates.len() == 1).then(|| candidates[0].clone())
+        };
+        let skeleton_name = find_skeleton("skel").or_else(|| find_skeleton("json"))
+            .ok_or("Bundle Error: 包内缺少 .skel 或 .json")?;
         
-        // 2. 获取第一页纹理信息
-        let page = atlas.pages().next().ok_or("Atlas has no pages")?;
-        let page_name = page.name().to_string();
+        // 2. 纹理页按图集所在目录的相对路径查找，找不到时退回按文件名匹配
+        let atlas_dir = atlas_name.rsplit_once('/').map(|(dir, _)| format!("{}/", dir)).unwrap_or_default();
+        let page_image = |page: &str| -> Result<Vec<u8>, String> {
+            let direct = format!("{}{}", atlas_dir, page);
+            entries.get(&direct)
+                .or_else(|| entries.iter().find(|(n, _)| n.rsplit('/').next() == Some(page)).map(|(_, d)| d))
+                .cloned()
+                .ok_or_else(|| format!("Bundle Error: 包内缺少纹理 {}", page))
+        };
         
-        // 3. 加载纹理图片
-        let img_path = std::path::Path::new(path_str)
-            .parent()
-            .ok_or("Invalid path")?
-            .join(&page_name);
+        Self::load_from_memory(
+            zip_path,
+            &entries[&atlas_name],
+            "",
+            page_image,
+            &entries[&skeleton_name],
+            vec![PathBuf::from(zip_path)],
+            pma,
+        )
+    }
+    
+    /// 从内存数据加载并上传纹理，不依赖文件系统（嵌入资源、网络下载等）
+    /// image_provider 按图集页名返回纹理文件（PNG/JPEG/WebP）字节；骨骼数据自动识别二进制或JSON
+    pub fn load_from_bytes(
+        ctx: &egui::Context,
+        atlas_bytes: &[u8],
+        image_provider: impl FnMut(&str) -> Result<Vec<u8>, String>,
+        skeleton_bytes: &[u8],
+    ) -> Result<Self, String> {
+        let (mut obj, color_image, page_name, _) =
+            Self::load_from_memory("<memory>", atlas_bytes, "", image_provider, skeleton_bytes, Vec::new(), None)?;
+        obj.texture_bytes = color_image.pixels.len() * 4;
+        let handle = ctx.load_texture(page_name, color_image, egui::TextureOptions::LINEAR);
+        obj.texture_id = Some(handle.id());
+        obj._texture = Some(handle);
+        Ok(obj)
+    }
+    
+    /// 从内存数据解析角色（不上传纹理）：文件、zip 包与 load_from_bytes 的共同实现
+    /// atlas_dir 仅作为 spine-c 记录的纹理目录，纹理字节一律由 image_provider 提供
+    fn load_from_memory(
+        source_path: &str,
+        atlas_bytes: &[u8],
+        atlas_dir: impl AsRef<std::path::Path>,
+        mut image_provider: impl FnMut(&str) -> Result<Vec<u8>, String>,
+        skeleton_bytes: &[u8],
+        source_files: Vec<PathBuf>,
+        pma: Option<bool>,
+    ) -> Result<(Self, egui::ColorImage, String, Vec<String>), String> {
+        // 1. 解析图集，取第一页纹理信息
+        let atlas = Arc::new(
+            Atlas::new(atlas_bytes, atlas_dir).map_err(|e| format!("Atlas Error: {}", e))?
+        );
+        let page_name = atlas.pages().next().ok_or("Atlas has no pages")?.name().to_string();
         
-        let img = image::open(&img_path)
+        // 2. 解码纹理
+        let img = image::load_from_memory(&image_provider(&page_name)?)
             .map_err(|e| format!("Image Load Error: {}", e))?;
         let rgba = img.to_rgba8();
-        let (width, height) = (rgba.width() as _, rgba.height() as _);
-        let raw_pixels = rgba.into_raw();
-        
-        // 4. 转换为egui颜色图像（不进行预乘Alpha，在渲染时处理）
-        let color_image = egui::ColorImage::from_rgba_unmultiplied([width, height], &raw_pixels);
-
-        // 5. 查找并加载骨骼数据（支持.skel二进制和.json格式）
-        let skel_path = std::path::Path::new(path_str).with_extension("skel");
-        let json_path = std::path::Path::new(path_str).with_extension("json");
-        
-        let skeleton_data = if skel_path.exists() {
-            // 二进制格式
-            let skeleton_bin = SkeletonBinary::new(atlas.clone());
-            Arc::new(
-                skeleton_bin.read_skeleton_data_file(&skel_path)
-                    .map_err(|e| format!("Binary load failed: {}", e))?
-            )
-        } else if json_path.exists() {
-            // JSON格式
-            let skeleton_json = SkeletonJson::new(atlas.clone());
-            Arc::new(
-                skeleton_json.read_skeleton_data_file(&json_path)
-                    .map_err(|e| format!("JSON load failed: {}", e))?
-            )
-        } else { 
-            return Err("Missing .skel or .json".into()); 
-        };
-
-        // 6. 创建动画状态机
+        let size = [rgba.width() as usize, rgba.height() as usize];
+        let premultiplied_alpha = pma.unwrap_or_else(|| atlas_page_is_pma(&String::from_utf8_lossy(atlas_bytes)));
+        let color_image = page_color_image(size, rgba.as_raw(), premultiplied_alpha);
+        
+        // 3. 解析骨骼数据：JSON 以 '{' 开头，否则按二进制 .skel 处理
+        let is_json = skeleton_bytes.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'{');
+        let skeleton_data = Arc::new(if !is_json {
+            SkeletonBinary::new(atlas.clone()).read_skeleton_data(skeleton_bytes)
+                .map_err(|e| format!("Binary load failed: {}", e))?
+        } else {
+            SkeletonJson::new(atlas.clone()).read_skeleton_data(skeleton_bytes)
+                .map_err(|e| format!("JSON load failed: {}", e))?
+        });
+        
+        let (obj, anim_names) = Self::assemble(atlas, skeleton_data, premultiplied_alpha, source_path, source_files)?;
+        Ok((obj, color_image, page_name, anim_names))
+    }
+    
+    /// 由资源缓存项创建新实例：共享骨骼数据与纹理，骨骼、动画状态与混合设置各自独立
+    fn from_shared(asset: &SharedAsset, path_str: &str) -> Result<(Self, Vec<String>), String> {
+        let (mut obj, anim_names) = Self::assemble(
+            asset.atlas.clone(),
+            asset.skeleton_data.clone(),
+            asset.premultiplied_alpha,
+            path_str,
+            asset.source_files.clone(),
+        )?;
+        obj.texture_bytes = asset.texture_bytes;
+        obj.texture_id = Some(asset.texture.id());
+        obj._texture = Some(asset.texture.clone());
+        Ok((obj, anim_names))
+    }
+    
+    /// 本对象的资源缓存项（纹理尚未上传或已被回收时为 None）
+    fn shared_asset(&self) -> Option<SharedAsset> {
+        Some(SharedAsset {
+            atlas: self._atlas.clone(),
+            skeleton_data: self._skeleton_data.clone(),
+            texture: self._texture.clone()?,
+            texture_bytes: self.texture_bytes,
+            premultiplied_alpha: self.premultiplied_alpha,
+            source_files: self.source_files.clone(),
+            mtimes: snapshot_mtimes(&self.source_files),
+        })
+    }
+    
+    /// 由已解析的图集与骨骼数据组装角色对象（文件加载、内存加载与资源缓存共用）
+    /// 返回：(SpineObject实例, 动画列表)
+    fn assemble(
+        atlas: Arc<Atlas>,
+        skeleton_data: Arc<rusty_spine::SkeletonData>,
+        premultiplied_alpha: bool,
+        path_str: &str,
+        source_files: Vec<PathBuf>,
+    ) -> Result<(Self, Vec<String>), String> {
+        // 1. 创建动画状态机
         let state_data = Arc::new(AnimationStateData::new(skeleton_data.clone()));
         let mut state = AnimationState::new(state_data.clone());
         
-        // 7. 提取所有动画名称
+        // 1.1 注册完成监听器：仅缓冲事件，不在计算线程中直接回调
+        let completed_events: Arc<Mutex<Vec<AnimCompleteEvent>>> = Arc::new(Mutex::new(Vec::new()));
+        let events_sink = completed_events.clone();
+        state.set_listener(move |_, event| {
+            if let AnimationEvent::Complete { track_entry, .. } = event {
+                if let Ok(mut events) = events_sink.lock() {
+                    events.push(AnimCompleteEvent {
+                        track: track_entry.track_index(),
+                        anim_name: track_entry.animation().name().to_string(),
+                        finished: !track_entry.looping(),
+                    });
+                }
+            }
+        });
+        
+        // 2. 提取所有动画名称
         let anim_names: Vec<String> = skeleton_data.animations()
             .map(|a| a.name().to_string())
             .collect();
         
-        // 8. 设置默认动画（第一个动画）
+        // 3. 设置默认动画（第一个动画）；没有动画时保持初始姿势（静态立绘）
         if let Some(anim) = skeleton_data.animations().next() { 
             let _ = state.set_animation(0, &anim, true);  // 循环播放
         }
 
-        // 9. 创建骨骼实例
+        // 4. 创建骨骼实例
         let skeleton = Skeleton::new(skeleton_data.clone());
 
-        // 10. 返回构建的SpineObject
+        // 5. 返回构建的SpineObject
         Ok((Self { 
             position: Pos2::ZERO, 
             scale: CHAR_BASE_SCALE, 
+            user_offset: Vec2::ZERO,
+            user_scale: 1.0,
+            placement: StagePlacement::Auto,
+            anchor: StageAnchor::Root,
+            shadow: None,
+            fit_height: None,
+            natural_height: None,
+            natural_bounds: None,
+            alpha: 1.0,
+            visible: true,
+            paused: false,
+            rotation: 0.0,
+            coord: CoordFlags::default(),
+            transition: None,
+            transition_alpha: 1.0,
+            premultiplied_alpha,
+            source_path: path_str.to_string(),
+            source_files,
             _texture: None, 
             texture_id: None, 
+            texture_bytes: 0,
+            texture_evicted: false,
+            last_painted: None,
+            mesh_bounds: Rect::NOTHING,
+            culled: false,
             // 🌟 性能优化：预分配8192个顶点的缓冲区，避免运行时分配
             world_vertices: Vec::with_capacity(8192),
+            last_mesh_sizes: Vec::new(),
+            last_update_time: Duration::ZERO,
+            prebuilt_shapes: Vec::new(),
+            last_build_time: Duration::ZERO,
             skeleton, 
             state, 
             _state_data: state_data,
             _skeleton_data: skeleton_data,
             _atlas: atlas,
-        }, color_image, page_name, anim_names))
+            completed_events,
+            bone_overrides: HashMap::new(),
+            attachment_overrides: HashMap::new(),
+            hidden_slots: HashSet::new(),
+            mix_rules: Vec::new(),
+            loop_count: 0,
+            loop_target: 0,
+            track_mixes: HashMap::new(),
+            pingpong: None,
+        }, anim_names))
+    }
+
+    /// 读取可补间属性的当前值
+    fn tween_value(&self, prop: TweenProp) -> f32 {
+        match prop {
+            TweenProp::X => self.user_offset.x,
+            TweenProp::Y => self.user_offset.y,
+            TweenProp::Scale => self.user_scale,
+            TweenProp::Alpha => self.alpha,
+            TweenProp::Rotation => self.rotation,
+        }
+    }
+    
+    /// 写入可补间属性
+    fn set_tween_value(&mut self, prop: TweenProp, value: f32) {
+        match prop {
+            TweenProp::X => self.user_offset.x = value,
+            TweenProp::Y => self.user_offset.y = value,
+            TweenProp::Scale => self.user_scale = value.max(0.01),
+            TweenProp::Alpha => self.alpha = value.clamp(0.0, 1.0),
+            TweenProp::Rotation => self.rotation = value,
+        }
+    }
+
+    /// 开始过渡；若已有过渡在进行，则从其当前状态出发，中途改向不会跳变
+    fn start_transition(&mut self, from: (Vec2, f32), to: (Vec2, f32), duration: f32, remove_on_finish: bool) {
+        let (from_offset, from_alpha) = match &self.transition {
+            Some(t) => t.current(),
+            None => from,
+        };
+        self.transition = Some(Transition {
+            from_offset,
+            to_offset: to.0,
+            from_alpha,
+            to_alpha: to.1,
+            duration: duration.max(0.0),
+            elapsed: 0.0,
+            remove_on_finish,
+            exit_reported: false,
+        });
+        self.transition_alpha = from_alpha;
+    }
+    
+    /// 推进过渡，返回 (当前偏移, 是否为刚结束的退场)
+    fn advance_transition(&mut self, dt: f32) -> (Vec2, bool) {
+        let Some(t) = &mut self.transition else { return (Vec2::ZERO, false) };
+        t.elapsed += dt;
+        let (offset, alpha) = t.current();
+        self.transition_alpha = alpha;
+        if !t.finished() {
+            return (offset, false);
+        }
+        if t.remove_on_finish {
+            // 退场保持最终状态直到被卸载，仅在结束当帧报告一次
+            let just_finished = !t.exit_reported;
+            t.exit_reported = true;
+            return (offset, just_finished);
+        }
+        self.transition = None;  // 登场结束后回到常态
+        (offset, false)
+    }
+
+    /// 取出自上次调用以来缓冲的动画完成事件，并累计轨道0的播放次数
+    fn take_completed_events(&mut self) -> Vec<AnimCompleteEvent> {
+        let mut events = match self.completed_events.lock() {
+            Ok(mut events) => std::mem::take(&mut *events),
+            Err(_) => Vec::new(),
+        };
+        
+        for ev in events.iter_mut().filter(|ev| ev.track == 0) {
+            // 交替播放：刚播完的动画排到队尾（另一个已在队列中接续），保持 A→B→A… 且过渡混合照常生效
+            if let Some((a, b)) = &self.pingpong {
+                if ev.anim_name == *a || ev.anim_name == *b {
+                    let _ = self.state.add_animation_by_name(0, &ev.anim_name, false, 0.0);
+                    ev.finished = false;
+                    continue;
+                }
+            }
+            if self.loop_target == 0 {
+                self.loop_count += 1;
+            } else if self.loop_count < self.loop_target {
+                // 限次播放：只有最后一轮才算真正结束
+                self.loop_count += 1;
+                ev.finished = self.loop_count >= self.loop_target;
+            }
+        }
+        events
     }
 
+    /// 获取所有动画的名称与时长（秒），按名称排序
+    fn anim_durations(&self) -> Vec<(String, f32)> {
+        let mut list: Vec<(String, f32)> = self._skeleton_data.animations()
+            .map(|a| (a.name().to_string(), a.duration()))
+            .collect();
+        list.sort_by(|a, b| a.0.cmp(&b.0));
+        list
+    }
+    
+    /// 骨骼是否包含动画（静态立绘导出可能一个都没有）
+    fn has_animations(&self) -> bool {
+        self._skeleton_data.animations().next().is_some()
+    }
+    
+    /// 设置两个动画之间的过渡时长（覆盖默认的立即切换），同一对重复设置时以最后一次为准
+    pub fn set_mix(&mut self, from: &str, to: &str, seconds: f32) -> Result<(), String> {
+        for name in [from, to] {
+            if !self._skeleton_data.animations().any(|anim| anim.name() == name) {
+                return Err(self.missing_animation(name));
+            }
+        }
+        self.state.data_mut().set_mix_by_name(from, to, seconds);
+        self.mix_rules.retain(|(f, t, _)| f != from || t != to);
+        self.mix_rules.push((from.to_string(), to.to_string(), seconds));
+        Ok(())
+    }
+    
+    /// 找不到动画时的错误说明：没有任何动画的角色单独提示，避免误以为是拼写错误
+    fn missing_animation(&self, name: &str) -> String {
+        if self.has_animations() {
+            format!("动画不存在: {}", name)
+        } else {
+            format!("角色没有任何动画（静态立绘），无法播放: {}", name)
+        }
+    }
+    
+    /// 状态/列表显示用的当前动画名
+    fn animation_label(&self) -> String {
+        match self.current_animation_name() {
+            Some(name) => name,
+            None if self.has_animations() => "(无)".into(),
+            None => "(无动画，静态立绘)".into(),
+        }
+    }
+    
     /// 获取所有可用动画名称
     fn get_anim_names(&self) -> Vec<String> { 
         self._skeleton_data.animations()
@@ -521,28 +2463,396 @@ impl SpineObject {
             .collect() 
     }
     
-    /// 按名称设置当前动画
-    /// 返回：是否成功找到并设置动画
-    fn set_animation_by_name(&mut self, anim_name: &str, loop_anim: bool) -> bool {
-        if let Some(anim) = self._skeleton_data.animations()
-            .find(|a| a.name() == anim_name) 
-        {
-            let _ = self.state.set_animation(0, &anim, loop_anim); 
-            true
-        } else { 
-            false  // 动画名称不存在
-        }
+    /// 获取轨道0当前播放的动画名称
+    fn current_animation_name(&self) -> Option<String> {
+        self.state.track_at_index(0)
+            .map(|entry| entry.animation().name().to_string())
     }
     
-    /// 并行更新：计算骨骼动画状态
-    /// 在Rayon线程池中调用，需保证线程安全
+    /// 获取轨道0当前动画的播放时间（秒），无动画时返回0
+    fn current_track_time(&self) -> f32 {
+        self.state.track_at_index(0)
+            .map_or(0.0, |entry| entry.track_time())
+    }
+    
+    /// 获取轨道0当前动画是否循环
+    fn current_looping(&self) -> bool {
+        self.state.track_at_index(0)
+            .map_or(false, |entry| entry.looping())
+    }
+    
+    /// 获取当前皮肤名称
+    fn current_skin_name(&self) -> Option<String> {
+        self.skeleton.skin().map(|skin| skin.name().to_string())
+    }
+    
+    /// 获取所有骨骼名称
+    fn bone_names(&self) -> Vec<String> {
+        self.skeleton.bones()
+            .map(|b| b.data().name().to_string())
+            .collect()
+    }
+    
+    /// 获取骨骼的本地变换
+    /// 返回：(x, y, 旋转角度, 缩放)
+    fn get_bone_transform(&self, name: &str) -> Option<(f32, f32, f32, f32)> {
+        self.skeleton.find_bone(name)
+            .map(|b| (b.x(), b.y(), b.rotation(), b.scale_x()))
+    }
+    
+    /// 导出插槽/附件/图集页信息（只读，用于排查角色不可见等问题）
+    fn dump_lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for page in self._atlas.pages() {
+            lines.push(format!("  图集页 {} ({}x{})", page.name(), page.width(), page.height()));
+        }
+        for slot in self.skeleton.slots() {
+            let alpha = slot.color().a;
+            let attachment = match slot.attachment() {
+                Some(att) => {
+                    let detail = if att.as_region().is_some() {
+                        "区域 4 顶点".to_string()
+                    } else if let Some(mesh) = att.as_mesh() {
+                        format!(
+                            "{}网格 {} 顶点 {} 三角形{}",
+                            if mesh.bones_count() > 0 { "加权" } else { "" },
+                            mesh.world_vertices_length() / 2,
+                            mesh.triangles_count() / 3,
+                            if mesh_indices_in_range(&mesh) { "" } else { " ⚠ 索引越界，已跳过绘制" },
+                        )
+                    } else {
+                        format!("{:?}（不渲染）", att.attachment_type())
+                    };
+                    format!("{} [{}]", att.name(), detail)
+                }
+                None => "(无附件)".to_string(),
+            };
+            lines.push(format!(
+                "  插槽 {} -> {}{}",
+                slot.data().name(),
+                attachment,
+                if alpha <= 0.0 { " ⚠ 插槽Alpha为0" } else { "" },
+            ));
+        }
+        lines
+    }
+    
+    /// 骨骼世界坐标在屏幕上的位置（与 push_to_mesh 使用相同的缩放/翻转/旋转/平移）
+    /// 返回：骨骼不存在时为 None
+    fn world_point_of_bone(&self, name: &str) -> Option<Pos2> {
+        let bone = self.skeleton.find_bone(name)?;
+        Some(self.skeleton_to_screen(bone.world_x(), bone.world_y()))
+    }
+    
+    /// 骨骼坐标 -> 屏幕坐标
+    fn skeleton_to_screen(&self, x: f32, y: f32) -> Pos2 {
+        let sx = if self.coord.flip_x { -self.scale } else { self.scale };
+        let sy = if self.coord.flip_y { -self.scale } else { self.scale };
+        let local = Vec2::new(x * sx, y * sy);
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        self.position + Vec2::new(
+            local.x * cos - local.y * sin,
+            local.x * sin + local.y * cos,
+        )
+    }
+    
+    /// 锚点换算：position = 舞台定位点 + anchor_offset()，使锚点（而非根节点）落在定位点上
+    /// 尚未生成过网格时无从得知包围盒，暂按根节点定位
+    fn anchor_offset(&self) -> Vec2 {
+        let Some(bounds) = self.natural_bounds else { return Vec2::ZERO };
+        match self.anchor {
+            StageAnchor::Root => Vec2::ZERO,
+            StageAnchor::Feet => -bounds.center_bottom().to_vec2() * self.scale,
+            StageAnchor::Center => -bounds.center().to_vec2() * self.scale,
+        }
+    }
+    
+    /// 脚下阴影网格：椭圆中心在包围盒底边中点（随整体旋转），宽度随缩放，不透明度随角色淡入淡出
+    /// 返回：未开启、尚未测得包围盒、不可见或已被裁剪时为 None
+    fn shadow_mesh(&self) -> Option<Mesh> {
+        let shadow = self.shadow?;
+        let bounds = self.natural_bounds?;
+        if !self.is_visible() || self.culled {
+            return None;
+        }
+        let feet = bounds.center_bottom().to_vec2() * self.scale;
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        let center = self.position + Vec2::new(feet.x * cos - feet.y * sin, feet.x * sin + feet.y * cos);
+        let half_width = bounds.width() * self.scale * shadow.size * 0.5;
+        let alpha = shadow.opacity * self.alpha * self.transition_alpha;
+        Some(build_shadow_blob_mesh(center, Vec2::new(half_width, half_width * SHADOW_BLOB_FLATTEN), alpha))
+    }
+    
+    /// 撤销快照
+    fn edit_snapshot(&self) -> SlotSnapshot {
+        SlotSnapshot {
+            path: self.source_path.clone(),
+            placement: self.placement,
+            anchor: self.anchor,
+            shadow: self.shadow,
+            user_offset: self.user_offset,
+            user_scale: self.user_scale,
+            rotation: self.rotation,
+            alpha: self.alpha,
+            visible: self.visible,
+            paused: self.paused,
+            animation: self.current_animation_name().map(|name| (name, self.current_looping())),
+        }
+    }
+    
+    /// 恢复撤销快照（来源相同的前提下）；动画只在与当前不同时重新设置，避免从头播放
+    fn apply_edit_snapshot(&mut self, snapshot: &SlotSnapshot) {
+        self.placement = snapshot.placement;
+        self.anchor = snapshot.anchor;
+        self.shadow = snapshot.shadow;
+        self.user_offset = snapshot.user_offset;
+        self.user_scale = snapshot.user_scale;
+        self.rotation = snapshot.rotation;
+        self.alpha = snapshot.alpha;
+        self.visible = snapshot.visible;
+        self.paused = snapshot.paused;
+        if let Some((name, looping)) = &snapshot.animation {
+            if self.current_animation_name().as_deref() != Some(name.as_str()) || self.current_looping() != *looping {
+                self.set_animation_by_name(0, name, *looping);
+            }
+        }
+    }
+    
+    /// 上一次 build_mesh 生成的网格规模：(顶点数, 三角形数)
+    /// 隐藏或被剔除期间保留最后一次生成的数值，用于找出渲染开销大的角色
+    pub fn mesh_stats(&self) -> (usize, usize) {
+        let (vertices, indices) = self.last_mesh_sizes.iter()
+            .fold((0, 0), |acc, &(v, i)| (acc.0 + v, acc.1 + i));
+        (vertices, indices / 3)
+    }
+    
+    /// 角色头顶的屏幕位置：上一次网格包围盒的顶边中点（随 position 平移）
+    /// 尚未生成网格时退回到 position
+    fn head_point(&self) -> Pos2 {
+        if self.mesh_bounds.is_positive() {
+            self.mesh_bounds.translate(self.position.to_vec2()).center_top()
+        } else {
+            self.position
+        }
+    }
+    
+    /// 用于视口剔除的粗略屏幕包围盒：全部骨骼位置 ∪ 上一次网格包围盒（随 position 平移）
+    /// 只包含骨骼的角色（如单骨骼立绘）依靠网格包围盒，动画中的大幅位移依靠骨骼包围盒
+    fn cull_bounds(&self) -> Rect {
+        if !self.mesh_bounds.is_positive() {
+            return Rect::EVERYTHING;  // 尚未生成过网格，无从估算，不剔除
+        }
+        let mut bounds = self.mesh_bounds.translate(self.position.to_vec2());
+        for bone in self.skeleton.bones() {
+            bounds.extend_with(self.skeleton_to_screen(bone.world_x(), bone.world_y()));
+        }
+        bounds.expand(CULL_MARGIN)
+    }
+    
+    /// 覆盖骨骼旋转角度（度）
+    /// 返回：骨骼是否存在
+    fn set_bone_rotation(&mut self, name: &str, degrees: f32) -> bool {
+        if self.skeleton.find_bone(name).is_none() { 
+            return false; 
+        }
+        self.bone_overrides.insert(name.to_string(), degrees);
+        true
+    }
+    
+    /// 替换插槽附件（用于表情/五官切换）
+    /// attachment_name 为 None 时隐藏该插槽
+    fn set_attachment(&mut self, slot_name: &str, attachment_name: Option<&str>) -> Result<(), String> {
+        if self.skeleton.find_slot(slot_name).is_none() {
+            return Err(format!("插槽不存在: {}", slot_name));
+        }
+        if let Some(att) = attachment_name {
+            // 在当前皮肤（及默认皮肤）中校验附件
+            if self.skeleton.get_attachment_for_slot_name(slot_name, att).is_none() {
+                return Err(format!("插槽 {} 在当前皮肤中不存在附件: {}", slot_name, att));
+            }
+        }
+        self.attachment_overrides.insert(
+            slot_name.to_string(), 
+            attachment_name.map(|a| a.to_string())
+        );
+        Ok(())
+    }
+    
+    /// 清除插槽附件覆盖，恢复由动画/初始姿势决定的附件
+    fn clear_attachment_override(&mut self, slot_name: &str) {
+        self.attachment_overrides.remove(slot_name);
+    }
+    
+    /// 隐藏/显示整个插槽（如红晕、汗滴叠加层）；与 set_attachment 不同，动画切换附件后仍保持隐藏
+    fn set_slot_hidden(&mut self, slot_name: &str, hidden: bool) -> Result<(), String> {
+        if self.skeleton.find_slot(slot_name).is_none() {
+            return Err(format!("插槽不存在: {}", slot_name));
+        }
+        if hidden {
+            self.hidden_slots.insert(slot_name.to_string());
+        } else {
+            self.hidden_slots.remove(slot_name);
+        }
+        Ok(())
+    }
+    
+    /// 已隐藏的插槽名（排序后，用于保存与列表显示）
+    fn hidden_slot_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.hidden_slots.iter().cloned().collect();
+        names.sort();
+        names
+    }
+    
+    /// 按名称在指定轨道上设置动画
+    /// 轨道0为基础动画，轨道1+为叠加层
+    /// 返回：是否成功找到并设置动画
+    fn set_animation_by_name(&mut self, track: usize, anim_name: &str, loop_anim: bool) -> bool {
+        if let Some(anim) = self._skeleton_data.animations()
+            .find(|a| a.name() == anim_name) 
+        {
+            let _ = self.state.set_animation(track, &anim, loop_anim); 
+            self.apply_track_mix(track);
+            if track == 0 {
+                self.loop_count = 0;
+                self.loop_target = 0;
+                self.pingpong = None;
+            }
+            true
+        } else { 
+            false  // 动画名称不存在
+        }
+    }
+    
+    /// 设置轨道混合参数，并立即作用于该轨道当前的动画条目
+    fn set_track_mix(&mut self, track: usize, mix: TrackMix) {
+        self.track_mixes.insert(track, mix);
+        self.apply_track_mix(track);
+    }
+    
+    /// 将已保存的轨道混合参数套用到该轨道当前条目
+    fn apply_track_mix(&mut self, track: usize) {
+        let mix = match self.track_mixes.get(&track) { 
+            Some(m) => *m, 
+            None => return 
+        };
+        if let Some(mut entry) = self.state.track_at_index_mut(track) {
+            mix.apply_to(&mut entry);
+        }
+    }
+    
+    /// 清空指定轨道
+    /// 由于每帧都会先重置到初始姿势，清空叠加轨道后基础姿势自动恢复
+    fn clear_track(&mut self, track: usize) {
+        self.state.clear_track(track);
+        if track == 0 {
+            self.loop_count = 0;
+            self.loop_target = 0;
+            self.pingpong = None;
+        }
+    }
+    
+    /// 设置轨道0交替播放两个动画；None 时取消，并让当前动画恢复普通循环
+    fn set_pingpong(&mut self, pair: Option<(String, String)>) -> Result<(), String> {
+        match pair {
+            Some((a, b)) => {
+                for name in [&a, &b] {
+                    if !self._skeleton_data.animations().any(|anim| anim.name() == name.as_str()) {
+                        return Err(self.missing_animation(name));
+                    }
+                }
+                self.set_animation_by_name(0, &a, false);
+                let _ = self.state.add_animation_by_name(0, &b, false, 0.0);
+                self.pingpong = Some((a, b));
+            }
+            None => {
+                if self.pingpong.take().is_some() {
+                    if let Some(current) = self.current_animation_name() {
+                        self.set_animation_by_name(0, &current, true);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+    
+    /// 重置到初始姿势：清除骨骼/附件覆盖与全部轨道
+    /// restart 为 true 时重新循环播放默认动画（第一个动画），否则停在初始姿势
+    fn reset_to_setup(&mut self, restart: bool) {
+        self.bone_overrides.clear();
+        self.attachment_overrides.clear();
+        self.state.clear_tracks();
+        self.loop_count = 0;
+        self.loop_target = 0;
+        self.pingpong = None;
+        self.skeleton.set_to_setup_pose();
+        if restart {
+            if let Some(anim) = self._skeleton_data.animations().next() {
+                let _ = self.state.set_animation(0, &anim, true);
+                self.apply_track_mix(0);
+            }
+        }
+    }
+    
+    /// 在指定轨道上将动画精确播放指定次数后停在最后一帧（或接续 then_anim 循环播放）
+    /// times 须 ≥ 1；无限循环请使用 set_animation_by_name。播放次数统计（loop_target）只针对轨道0
+    fn play_animation_times(&mut self, track: usize, anim_name: &str, times: u32, then_anim: Option<&str>) -> Result<(), String> {
+        let anim = self._skeleton_data.animations()
+            .find(|a| a.name() == anim_name)
+            .ok_or_else(|| self.missing_animation(anim_name))?;
+        if let Some(next) = then_anim {
+            if !self._skeleton_data.animations().any(|a| a.name() == next) {
+                return Err(self.missing_animation(next));
+            }
+        }
+        
+        // 以非循环条目排队 N 次，由Spine队列保证精确次数；排队条目同样套用该轨道的混合参数
+        let mix = self.track_mixes.get(&track).copied();
+        let _ = self.state.set_animation(track, &anim, false);
+        self.apply_track_mix(track);
+        for _ in 1..times {
+            if let (Ok(mut entry), Some(mix)) = (self.state.add_animation_by_name(track, anim_name, false, 0.0), mix) {
+                mix.apply_to(&mut entry);
+            }
+        }
+        if let Some(next) = then_anim {
+            if let (Ok(mut entry), Some(mix)) = (self.state.add_animation_by_name(track, next, true, 0.0), mix) {
+                mix.apply_to(&mut entry);
+            }
+        }
+        
+        if track == 0 {
+            self.loop_count = 0;
+            self.loop_target = times;
+            self.pingpong = None;
+        }
+        Ok(())
+    }
+    
+    /// 并行更新：计算骨骼动画状态
+    /// 在Rayon线程池中调用，需保证线程安全
     fn update_parallel(&mut self, dt: f32) {
-        let dt = dt.min(MAX_DT);  // 限制最大时间步，防止卡顿导致的动画跳跃
+        // 限制最大时间步，防止卡顿导致的动画跳跃；暂停时只重新施加姿势
+        let dt = if self.paused { 0.0 } else { dt.min(MAX_DT) };
         
         // Spine动画更新流水线
         self.state.update(dt);                     // 更新动画状态机
         self.skeleton.set_to_setup_pose();         // 重置到初始姿势
-        let _ = self.state.apply(&mut self.skeleton); // 应用当前动画
+        let _ = self.state.apply(&mut self.skeleton); // 按轨道顺序应用所有轨道的动画
+        
+        // 重新施加骨骼覆盖（必须在世界变换之前）
+        for (name, degrees) in &self.bone_overrides {
+            if let Some(mut bone) = self.skeleton.find_bone_mut(name) {
+                bone.set_rotation(*degrees);
+            }
+        }
+        
+        // 重新施加附件覆盖（setup pose 与动画关键帧都会重置附件）
+        for (slot_name, att) in &self.attachment_overrides {
+            unsafe {
+                self.skeleton.set_attachment(slot_name, att.as_deref());
+            }
+        }
+        
         self.skeleton.update_world_transform();    // 更新世界变换
         self.skeleton.update_cache();              // 更新渲染缓存
     }
@@ -551,21 +2861,65 @@ impl SpineObject {
     /// 在UI线程中调用，将动画转换为egui Mesh
     fn paint(&mut self, ui: &mut egui::Ui) {
         // 检查纹理是否已加载
-        let tex_id = match self.texture_id { 
-            Some(id) => id, 
-            None => return  // 纹理未就绪，跳过渲染
-        };
+        if self.texture_id.is_none() { 
+            return;  // 纹理未就绪，跳过渲染
+        }
+        
+        // 生成网格并添加到UI绘制器
+        ui.painter().extend(self.build_mesh());
+    }
+    
+    /// 是否会产生可见网格（综合隐藏开关、不透明度、过渡透明度与骨架颜色）
+    fn is_visible(&self) -> bool {
+        self.visible && self.alpha * self.transition_alpha * self.skeleton.color().a > 0.0
+    }
+    
+    /// 释放已上传的纹理（显存预算回收），返回释放的字节数
+    /// 对象本身保留，之后绘制时按需重新加载
+    fn evict_texture(&mut self) -> usize {
+        self._texture = None;
+        self.texture_id = None;
+        self.prebuilt_shapes.clear();
+        self.texture_evicted = true;
+        std::mem::take(&mut self.texture_bytes)
+    }
+    
+    /// 根据当前骨骼姿势生成网格（不提交绘制）
+    /// 与绘制分离，便于截图、无窗口测试与批处理
+    /// 返回：按绘制顺序排列的一个或多个Mesh，混合模式变化处会切分为新的Mesh
+    fn build_mesh(&mut self) -> Vec<Shape> {
+        let tex_id = self.texture_id.unwrap_or_default();  // 无窗口模式下使用默认纹理ID
+        let mut shapes = Vec::new();
+        
+        // 隐藏或完全透明时不生成网格（含骨架颜色被动画淡出的情况）
+        if !self.is_visible() {
+            return shapes;
+        }
         
-        // 创建纹理Mesh
-        let mut mesh = Mesh::with_texture(tex_id);
+        // 创建纹理Mesh，每段按上一帧同序号 Mesh 的规模一次性预留容量（帧间姿势变化很小）
+        let last_sizes = std::mem::take(&mut self.last_mesh_sizes);
+        let mut mesh = reserved_mesh(tex_id, &last_sizes, 0);
+        let mut current_blend = None;
         
         // 遍历所有绘制槽位
         for slot in self.skeleton.draw_order() {
+            // 被 slot 命令隐藏的插槽不生成几何
+            if !self.hidden_slots.is_empty() && self.hidden_slots.contains(slot.data().name()) {
+                continue;
+            }
             let attachment = match slot.attachment() { 
                 Some(a) => a, 
                 None => continue  // 槽位无附件，跳过
             };
             
+            // 混合模式切换时结束当前批次
+            let blend = std::mem::discriminant(&slot.data().blend_mode());
+            if current_blend.map_or(false, |b| b != blend) && !mesh.is_empty() {
+                let next = reserved_mesh(tex_id, &last_sizes, shapes.len() + 1);
+                shapes.push(Shape::mesh(std::mem::replace(&mut mesh, next)));
+            }
+            current_blend = Some(blend);
+            
             // 处理区域附件（简单四边形）
             if let Some(region) = attachment.as_region() {
                 unsafe {
@@ -589,7 +2943,14 @@ impl SpineObject {
                 }
             } 
             // 处理网格附件（复杂网格）
+            // 加权网格（bones_count > 0）的骨骼权重数组与顶点数无关：
+            // world_vertices_length 始终为 顶点数 × 2，start/count 以浮点数计、stride 为 2，
+            // 变形（deform）由 spine-c 从插槽读取，加权与非加权网格调用方式一致
             else if let Some(mesh_att) = attachment.as_mesh() {
+                // 索引越界的网格（导出数据损坏）整体跳过，避免拉出贯穿画面的三角形；DUMP 中会标出
+                if !mesh_indices_in_range(&mesh_att) {
+                    continue;
+                }
                 unsafe {
                     let len = mesh_att.world_vertices_length() as usize;
                     
@@ -601,7 +2962,7 @@ impl SpineObject {
                     // 计算世界坐标顶点
                     mesh_att.compute_world_vertices(&*slot, 0, len as i32, &mut self.world_vertices, 0, 2);
                     
-                    // 从C指针获取UV和三角形数据
+                    // 从C指针获取UV和三角形数据（UV 与世界坐标同为 顶点数 × 2 个浮点数）
                     let uvs = std::slice::from_raw_parts(mesh_att.uvs(), len);
                     let tris = std::slice::from_raw_parts(
                         mesh_att.triangles(), 
@@ -621,8 +2982,35 @@ impl SpineObject {
             }
         }
         
-        // 将Mesh添加到UI绘制器
-        ui.painter().add(Shape::mesh(mesh));
+        if !mesh.is_empty() {
+            shapes.push(Shape::mesh(mesh));
+        }
+        
+        // 记录本帧各 Mesh 的规模供下一帧预留
+        self.last_mesh_sizes = shapes.iter()
+            .filter_map(|shape| match shape {
+                Shape::Mesh(m) => Some((m.vertices.len(), m.indices.len())),
+                _ => None,
+            })
+            .collect();
+        
+        let bounds = shapes.iter().fold(Rect::NOTHING, |acc, shape| match shape {
+            Shape::Mesh(m) => acc.union(m.calc_bounds()),
+            _ => acc,
+        });
+        if bounds.is_positive() {
+            self.mesh_bounds = bounds.translate(-self.position.to_vec2());
+            
+            // 首次生成网格时测量骨骼高度（骨骼坐标单位），供 fitheight 换算缩放
+            if self.natural_height.is_none() && self.scale > 0.0 {
+                self.natural_height = Some(bounds.height() / self.scale);
+                self.natural_bounds = Some(Rect::from_min_max(
+                    (self.mesh_bounds.min.to_vec2() / self.scale).to_pos2(),
+                    (self.mesh_bounds.max.to_vec2() / self.scale).to_pos2(),
+                ));
+            }
+        }
+        shapes
     }
     
     /// 将顶点数据推送到egui Mesh
@@ -636,12 +3024,15 @@ impl SpineObject {
         slot: &Slot,      // Spine槽位
         att_c: rusty_spine::Color  // 附件颜色
     ) {
-        // 1. 颜色计算：槽位颜色 × 附件颜色
+        // 1. 颜色计算：骨架颜色 × 槽位颜色 × 附件颜色 × 角色不透明度 × 过渡透明度
+        // 骨架颜色可被动画驱动（Spine 数据中的整体淡入淡出），中性骨架为白色不透明，结果不变
+        // 预乘格式下 RGB 与 A 同比缩放，因此加色混合（A=0）的部分也会随之淡出
+        let k_c = self.skeleton.color(); // 骨架颜色
         let s_c = slot.color();      // 槽位颜色
-        let a = s_c.a * att_c.a;     // 最终Alpha（预乘）
-        let r = s_c.r * att_c.r * a; // 预乘红色
-        let g = s_c.g * att_c.g * a; // 预乘绿色
-        let b = s_c.b * att_c.b * a; // 预乘蓝色
+        let a = k_c.a * s_c.a * att_c.a * self.alpha * self.transition_alpha;  // 最终Alpha（预乘）
+        let r = k_c.r * s_c.r * att_c.r * a; // 预乘红色
+        let g = k_c.g * s_c.g * att_c.g * a; // 预乘绿色
+        let b = k_c.b * s_c.b * att_c.b * a; // 预乘蓝色
         
         // 2. 特殊混合模式处理：Additive模式需要Alpha为0
         let final_a = match slot.data().blend_mode() {
@@ -660,13 +3051,17 @@ impl SpineObject {
         // 4. 计算顶点数量
         let count = usize::min(uvs.len() / 2, w_v.len() / 2);
         let idx_offset = mesh.vertices.len() as u32;  // 当前Mesh的顶点偏移
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        let sx = if self.coord.flip_x { -self.scale } else { self.scale };
+        let sy = if self.coord.flip_y { -self.scale } else { self.scale };
         
         // 5. 添加顶点
         for i in 0..count {
-            // 应用缩放和位置变换
-            let pos = Pos2::new(
-                w_v[i*2] * self.scale + self.position.x,      // X坐标
-                -w_v[i*2+1] * self.scale + self.position.y    // Y坐标（翻转Y轴）
+            // 应用缩放（含坐标翻转）→ 旋转 → 平移；仅变换位置，UV保持不变
+            let local = Vec2::new(w_v[i*2] * sx, w_v[i*2+1] * sy);
+            let pos = self.position + Vec2::new(
+                local.x * cos - local.y * sin,
+                local.x * sin + local.y * cos,
             );
             
             // 添加顶点到Mesh
@@ -684,197 +3079,3440 @@ impl SpineObject {
     }
 }
 
+/// 新建纹理Mesh，按上一帧第 index 个 Mesh 的顶点/索引数预留容量（没有对应记录时不预留）
+fn reserved_mesh(tex_id: TextureId, last_sizes: &[(usize, usize)], index: usize) -> Mesh {
+    let mut mesh = Mesh::with_texture(tex_id);
+    if let Some(&(vertices, indices)) = last_sizes.get(index) {
+        mesh.vertices.reserve(vertices);
+        mesh.indices.reserve(indices);
+    }
+    mesh
+}
+
+/// 网格附件的三角形索引是否都落在顶点范围内（顶点数 = world_vertices_length / 2）
+fn mesh_indices_in_range(mesh_att: &rusty_spine::MeshAttachment) -> bool {
+    let vertex_count = (mesh_att.world_vertices_length().max(0) / 2) as usize;
+    let count = mesh_att.triangles_count().max(0) as usize;
+    if count == 0 {
+        return true;
+    }
+    // 三角形数据由 spine-c 持有，长度为 triangles_count
+    let tris = unsafe { std::slice::from_raw_parts(mesh_att.triangles(), count) };
+    tris.iter().all(|&i| (i as usize) < vertex_count)
+}
+
 // ============================================================================
-// 主应用程序逻辑
+// 无窗口渲染（快照/回归测试用）
 // ============================================================================
-/// 主应用程序状态
-struct AefrApp {
-    // 系统组件
-    scheduler: AefrScheduler,      // 并行调度器
-    audio_manager: Option<AudioManager>, // 音频管理器（可选，可能初始化失败）
-    
-    // 剧本状态
-    scenario: Scenario,            // 当前剧本
-    current_scene_idx: usize,      // 当前场景索引
+/// 无需 eframe 窗口即可驱动 SpineObject：
+/// 以固定时间步序列推进动画，并直接返回生成的网格顶点/索引，
+/// 便于测试对已知动画在已知时刻的顶点数量与坐标做断言。
+impl SpineObject {
+    /// 从 .atlas 路径加载角色（不上传纹理）
+    pub fn load_headless(path: &str) -> Result<Self, String> {
+        Self::load_async_no_gpu(path, None).map(|(obj, _, _, _)| obj)
+    }
     
-    // 对话状态
-    target_chars: Vec<char>,       // 目标文本字符数组
-    visible_count: usize,          // 当前可见字符数
-    type_timer: f32,               // 打字机计时器
+    /// 切换轨道0动画，返回是否成功
+    pub fn set_animation_headless(&mut self, anim_name: &str, loop_anim: bool) -> bool {
+        self.set_animation_by_name(0, anim_name, loop_anim)
+    }
     
-    // UI状态
-    is_auto_enabled: bool,         // 自动播放模式
-    show_dialogue: bool,           // 显示对话框
-    console_open: bool,            // 控制台窗口状态
-    selected_slot: usize,          // 当前选中的角色槽位
-    console_input: String,         // 控制台输入
-    console_logs: Vec<String>,     // 控制台日志
+    /// 依次以给定的时间步推进动画，返回最终姿势的网格（均为 Shape::Mesh）
+    /// 注意：单步时间仍受 MAX_DT 限制，需要更大跨度时请拆分为多步
+    pub fn render_headless(&mut self, dts: &[f32]) -> Vec<Shape> {
+        for &dt in dts {
+            self.update_parallel(dt);
+        }
+        self.build_mesh()
+    }
+}
+
+// ============================================================================
+// 动画录制（软件光栅化为PNG序列）
+// ============================================================================
+/// 录制参数
+struct RecordJob {
+    atlas_path: String,     // 角色来源（录制使用独立加载的副本，不影响舞台上的角色）
+    animation: Option<String>,
+    looping: bool,
+    coord: CoordFlags,
+    pma: Option<bool>,      // 与舞台上的角色使用相同的 pma 指定
+    dir: PathBuf,
+    fps: f32,
+    duration: f32,
+}
+
+/// 以固定时间步推进动画并逐帧光栅化为带透明通道的PNG序列
+/// 与墙钟时间完全解耦：输出帧数恒为 round(fps × duration)
+/// 返回：写出的帧数与画布尺寸
+fn record_animation(job: RecordJob, tx: &Sender<AppCommand>) -> Result<(usize, [usize; 2]), String> {
+    // 录制用的独立副本：画布尺寸需要先走完整段动画才能确定，两遍各加载一份，保证逐帧姿势完全相同
+    let load = || -> Result<(SpineObject, egui::ColorImage), String> {
+        let (mut obj, texture, _, _) = SpineObject::load_async_no_gpu(&job.atlas_path, job.pma)?;
+        obj.coord = job.coord;
+        obj.scale = 1.0;  // 以骨骼坐标单位（1 单位 = 1 像素）录制
+        if let Some(anim) = &job.animation {
+            if !obj.set_animation_headless(anim, job.looping) {
+                return Err(obj.missing_animation(anim));
+            }
+        }
+        Ok((obj, texture))
+    };
     
-    // 动画预览
-    show_anim_preview: bool,       // 显示动画预览窗口
-    preview_anim_idx: usize,       // 预览动画索引
+    // 按固定时间步推进到第 i 帧并生成网格（单步超过 MAX_DT 时拆分）
+    let frame_count = (job.fps * job.duration).round().max(1.0) as usize;
+    let frame_dt = 1.0 / job.fps;
+    let substeps = (frame_dt / MAX_DT).ceil().max(1.0) as usize;
+    let dts = vec![frame_dt / substeps as f32; substeps];
+    let step = |obj: &mut SpineObject, i: usize| if i == 0 { obj.render_headless(&[0.0]) } else { obj.render_headless(&dts) };
     
-    // 游戏对象
-    // 🌟 关键：使用Arc<Mutex>包装SpineObject，实现线程安全共享
-    characters: Vec<Option<Arc<Mutex<SpineObject>>>>, // 5个角色槽位
-    background: Option<TextureHandle>, // 背景纹理
+    // 1. 第一遍只累计所有帧的包围盒确定画布（不保留网格，内存占用与录制时长无关），保证整段动画不被裁切
+    let (mut obj, texture) = load()?;
+    let mut bounds = Rect::NOTHING;
+    for i in 0..frame_count {
+        for shape in step(&mut obj, i) {
+            if let Shape::Mesh(mesh) = shape {
+                for v in &mesh.vertices {
+                    bounds.extend_with(v.pos);
+                }
+            }
+        }
+    }
+    if !bounds.is_positive() {
+        return Err("动画没有可见网格".into());
+    }
+    let bounds = bounds.expand(RECORD_PADDING);
+    let fit = (RECORD_MAX_SIZE / bounds.width().max(bounds.height())).min(1.0);  // 超大角色整体缩小
+    let size = [(bounds.width() * fit).ceil() as usize, (bounds.height() * fit).ceil() as usize];
     
-    // 线程通信
-    tx: Sender<AppCommand>,        // 命令发送端
-    rx: Receiver<AppCommand>,      // 命令接收端
+    // 2. 第二遍从头以相同时间步推进，每帧生成后立即光栅化并写出
+    std::fs::create_dir_all(&job.dir).map_err(|e| format!("无法创建目录 {}: {}", job.dir.display(), e))?;
+    let (mut obj, _) = load()?;
+    let mut canvas = vec![[0.0f32; 4]; size[0] * size[1]];
+    for i in 0..frame_count {
+        canvas.fill([0.0; 4]);
+        for shape in &step(&mut obj, i) {
+            if let Shape::Mesh(mesh) = shape {
+                rasterize_mesh(mesh, &texture, bounds.min, fit, &mut canvas, size);
+            }
+        }
+        
+        // 画布为预乘Alpha，PNG 需要直通Alpha
+        let mut pixels = Vec::with_capacity(size[0] * size[1] * 4);
+        for px in &canvas {
+            let c = Color32::from_rgba_premultiplied(
+                px[0].clamp(0.0, 255.0) as u8,
+                px[1].clamp(0.0, 255.0) as u8,
+                px[2].clamp(0.0, 255.0) as u8,
+                px[3].clamp(0.0, 255.0) as u8,
+            );
+            pixels.extend_from_slice(&c.to_srgba_unmultiplied());
+        }
+        let path = job.dir.join(format!("frame_{:04}.png", i));
+        image::save_buffer(&path, &pixels, size[0] as u32, size[1] as u32, image::ExtendedColorType::Rgba8)
+            .map_err(|e| format!("写入 {} 失败: {}", path.display(), e))?;
+        
+        // 每完成四分之一输出一次进度
+        let quarter = (frame_count / 4).max(1);
+        if (i + 1) % quarter == 0 && i + 1 < frame_count {
+            let _ = tx.send(AppCommand::Log(format!("[系统] 录制进度 {}/{}", i + 1, frame_count)));
+        }
+    }
+    Ok((frame_count, size))
 }
 
-impl AefrApp {
-    /// 创建应用程序实例
-    fn new(cc: &eframe::CreationContext) -> Self {
-        // 1. 设置嵌入式字体
-        setup_embedded_font(&cc.egui_ctx);
-        
-        // 2. 安装图片加载器
-        egui_extras::install_image_loaders(&cc.egui_ctx);
+/// 将一个纹理网格光栅化到预乘Alpha画布（与 egui 相同的 “源 + 目标 × (1 - 源Alpha)” 混合）
+/// 顶点颜色与纹理均为预乘格式；加色混合的顶点 Alpha 为 0，因此自然得到叠加效果
+fn rasterize_mesh(mesh: &Mesh, texture: &egui::ColorImage, origin: Pos2, fit: f32, canvas: &mut [[f32; 4]], size: [usize; 2]) {
+    let to_canvas = |p: Pos2| (p - origin) * fit;
+    let [tw, th] = texture.size;
+    
+    // 双线性采样，返回 0~1 的预乘RGBA
+    let sample = |u: f32, v: f32| -> [f32; 4] {
+        let x = (u * tw as f32 - 0.5).clamp(0.0, (tw - 1) as f32);
+        let y = (v * th as f32 - 0.5).clamp(0.0, (th - 1) as f32);
+        let (x0, y0) = (x as usize, y as usize);
+        let (x1, y1) = ((x0 + 1).min(tw - 1), (y0 + 1).min(th - 1));
+        let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+        let px = |x: usize, y: usize| texture.pixels[y * tw + x].to_array();
+        let (a, b, c, d) = (px(x0, y0), px(x1, y0), px(x0, y1), px(x1, y1));
+        let mut out = [0.0; 4];
+        for k in 0..4 {
+            let top = a[k] as f32 + (b[k] as f32 - a[k] as f32) * fx;
+            let bottom = c[k] as f32 + (d[k] as f32 - c[k] as f32) * fx;
+            out[k] = (top + (bottom - top) * fy) / 255.0;
+        }
+        out
+    };
+    
+    for tri in mesh.indices.chunks_exact(3) {
+        let v = [&mesh.vertices[tri[0] as usize], &mesh.vertices[tri[1] as usize], &mesh.vertices[tri[2] as usize]];
+        let p = [to_canvas(v[0].pos), to_canvas(v[1].pos), to_canvas(v[2].pos)];
+        let area = (p[1] - p[0]).x * (p[2] - p[0]).y - (p[1] - p[0]).y * (p[2] - p[0]).x;
+        if area.abs() < f32::EPSILON {
+            continue;  // 退化三角形
+        }
         
-        // 3. 创建线程通信通道
-        let (tx, rx) = channel();
+        let min_x = p.iter().map(|q| q.x).fold(f32::MAX, f32::min).floor().max(0.0) as usize;
+        let max_x = (p.iter().map(|q| q.x).fold(f32::MIN, f32::max).ceil() as usize).min(size[0]);
+        let min_y = p.iter().map(|q| q.y).fold(f32::MAX, f32::min).floor().max(0.0) as usize;
+        let max_y = (p.iter().map(|q| q.y).fold(f32::MIN, f32::max).ceil() as usize).min(size[1]);
         
-        // 4. 初始化音频系统（允许失败）
-        let audio_manager = match AudioManager::new() {
-            Ok(mgr) => Some(mgr),
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                // 像素中心的重心坐标
+                let c = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let edge = |a: Vec2, b: Vec2| (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x);
+                let w0 = edge(p[1], p[2]) / area;
+                let w1 = edge(p[2], p[0]) / area;
+                let w2 = 1.0 - w0 - w1;
+                if w0 < 0.0 || w1 < 0.0 || w2 < 0.0 {
+                    continue;
+                }
+                
+                let uv = v[0].uv.to_vec2() * w0 + v[1].uv.to_vec2() * w1 + v[2].uv.to_vec2() * w2;
+                let tex = sample(uv.x, uv.y);
+                let dst = &mut canvas[y * size[0] + x];
+                let mut src = [0.0; 4];
+                for k in 0..4 {
+                    let tint = (v[0].color[k] as f32 * w0 + v[1].color[k] as f32 * w1 + v[2].color[k] as f32 * w2) / 255.0;
+                    src[k] = tex[k] * tint * 255.0;
+                }
+                let keep = 1.0 - src[3] / 255.0;
+                for k in 0..4 {
+                    dst[k] = src[k] + dst[k] * keep;
+                }
+            }
+        }
+    }
+}
+
+// ============================================================================
+// 场景导出（剧本 + 资源清单）
+// ============================================================================
+/// 资源清单中的一项
+#[derive(Serialize)]
+struct ManifestEntry {
+    source: String,                         // 原始路径
+    path: String,                           // 导出后的相对路径（未复制时与原始路径相同）
+    copied: bool,                           // 是否已复制进导出目录
+}
+
+/// 导出清单：与 scenario.json 一同写入导出目录
+#[derive(Serialize)]
+struct ExportManifest {
+    scenario: String,                       // 剧本文件名
+    assets: Vec<ManifestEntry>,             // 引用到的全部资源
+    missing: Vec<String>,                   // 复制失败的资源及原因
+}
+
+/// Spine 角色依赖的全部文件：atlas、同名骨骼文件（.skel 优先）及 atlas 中列出的纹理页
+fn spine_companion_files(atlas_path: &std::path::Path) -> Vec<PathBuf> {
+    let mut files = vec![atlas_path.to_path_buf()];
+    if is_bundle_path(&atlas_path.to_string_lossy()) || is_url_path(&atlas_path.to_string_lossy()) {
+        return files;  // zip 资源包自包含；远程资源无本地配套文件
+    }
+    let skel = atlas_path.with_extension("skel");
+    files.push(if skel.exists() { skel } else { atlas_path.with_extension("json") });
+    
+    if let (Ok(text), Some(dir)) = (std::fs::read_to_string(atlas_path), atlas_path.parent()) {
+        // 纹理页名称行：不含冒号且以图片扩展名结尾
+        let pages = text.lines().map(str::trim).filter(|line| {
+            !line.contains(':') && [".png", ".jpg", ".jpeg", ".webp"].iter().any(|ext| line.to_lowercase().ends_with(ext))
+        });
+        files.extend(pages.map(|page| dir.join(page)));
+    }
+    files
+}
+
+/// 导出剧本到目录：写入 scenario.json 与 manifest.json
+/// copy_assets 为 true 时将资源复制进 assets/ 子目录，并把剧本中的路径改写为相对路径
+/// 返回 (资源数量, 复制失败列表)
+fn export_scenario(mut scenario: Scenario, dir: PathBuf, copy_assets: bool) -> Result<(usize, Vec<String>), String> {
+    std::fs::create_dir_all(&dir).map_err(|e| format!("无法创建目录 {}: {}", dir.display(), e))?;
+    
+    let mut assets: Vec<ManifestEntry> = Vec::new();
+    let mut missing: Vec<String> = Vec::new();
+    let mut copied: HashMap<PathBuf, String> = HashMap::new();  // 源文件 -> 导出相对路径（去重）
+    
+    // 复制单个文件到 assets/<group>/ 下，返回相对路径；失败时记录原因
+    let mut copy_file = |src: &std::path::Path, group: &str, assets: &mut Vec<ManifestEntry>, missing: &mut Vec<String>| -> Option<String> {
+        if let Some(rel) = copied.get(src) {
+            return Some(rel.clone());
+        }
+        let file_name = src.file_name()?.to_string_lossy().to_string();
+        // 不同来源的同名文件加序号区分，避免相互覆盖
+        let mut rel = format!("assets/{}/{}", group, file_name);
+        let mut n = 1;
+        while copied.values().any(|used| *used == rel) {
+            rel = format!("assets/{}/{}_{}", group, n, file_name);
+            n += 1;
+        }
+        let dest = dir.join(&rel);
+        let result = dest.parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::copy(src, &dest));
+        match result {
+            Ok(_) => {
+                assets.push(ManifestEntry { source: src.display().to_string(), path: rel.clone(), copied: true });
+                copied.insert(src.to_path_buf(), rel.clone());
+                Some(rel)
+            }
             Err(e) => {
-                // 记录错误但不中断程序
-                let _ = tx.send(AppCommand::Log(
-                    format!("[警告] 音频系统初始化失败 (无声卡/独占): {}", e)
-                ));
+                missing.push(format!("{}: {}", src.display(), e));
                 None
             }
+        }
+    };
+    
+    for scene in scenario.scenes.iter_mut() {
+        // 背景：静态图片或序列帧目录
+        if let Some(bg) = scene.bg_path.clone() {
+            let src = PathBuf::from(&bg);
+            if !copy_assets {
+                assets.push(ManifestEntry { source: bg.clone(), path: bg, copied: false });
+            } else if scene.bg_anim_fps.is_some() {
+                let group = format!("bg/{}", src.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default());
+                match collect_frame_paths(&bg) {
+                    Ok(frames) => {
+                        for frame in &frames {
+                            copy_file(frame, &group, &mut assets, &mut missing);
+                        }
+                        scene.bg_path = Some(format!("assets/{}", group));
+                    }
+                    Err(e) => missing.push(e),
+                }
+            } else if let Some(rel) = copy_file(&src, "bg", &mut assets, &mut missing) {
+                scene.bg_path = Some(rel);
+            }
+        }
+        
+        // 背景音乐
+        if let Some(bgm) = scene.bgm_path.clone() {
+            if !copy_assets {
+                assets.push(ManifestEntry { source: bgm.clone(), path: bgm, copied: false });
+            } else if let Some(rel) = copy_file(std::path::Path::new(&bgm), "audio", &mut assets, &mut missing) {
+                scene.bgm_path = Some(rel);
+            }
+        }
+        
+        // 台词语音
+        if let Some(voice) = scene.voice_path.clone() {
+            if !copy_assets {
+                assets.push(ManifestEntry { source: voice.clone(), path: voice, copied: false });
+            } else if let Some(rel) = copy_file(std::path::Path::new(&voice), "voice", &mut assets, &mut missing) {
+                scene.voice_path = Some(rel);
+            }
+        }
+        
+        // 角色：atlas 与其依赖文件放在同一子目录，保持相对引用关系
+        for char_path in scene.char_paths.iter_mut().flatten() {
+            let atlas = PathBuf::from(char_path.as_str());
+            let files = spine_companion_files(&atlas);
+            if !copy_assets {
+                for f in files {
+                    let p = f.display().to_string();
+                    assets.push(ManifestEntry { source: p.clone(), path: p, copied: false });
+                }
+                continue;
+            }
+            let group = format!("spine/{}", atlas.file_stem().map(|n| n.to_string_lossy().to_string()).unwrap_or_default());
+            let mut atlas_rel = None;
+            for (i, f) in files.iter().enumerate() {
+                let rel = copy_file(f, &group, &mut assets, &mut missing);
+                if i == 0 { atlas_rel = rel; }
+            }
+            if let Some(rel) = atlas_rel {
+                *char_path = rel;
+            }
+        }
+    }
+    
+    let json = serde_json::to_string_pretty(&scenario).map_err(|e| e.to_string())?;
+    std::fs::write(dir.join("scenario.json"), json).map_err(|e| format!("无法写入剧本: {}", e))?;
+    
+    let asset_count = assets.len();
+    let manifest = ExportManifest { scenario: "scenario.json".into(), assets, missing: missing.clone() };
+    let json = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
+    std::fs::write(dir.join("manifest.json"), json).map_err(|e| format!("无法写入清单: {}", e))?;
+    
+    Ok((asset_count, missing))
+}
+
+// ============================================================================
+// 控制台命令表
+// ============================================================================
+/// 控制台命令用法表：(命令名, 参数格式, 说明)
+/// 解析错误提示与 help 命令共用同一份用法字符串
+const COMMAND_USAGES: &[(&str, &str, &str)] = &[
+    ("load", "<槽位> <路径> [pma=on|off|auto]", "加载Spine角色（.atlas、.zip 资源包或 http(s) URL）；pma 手动指定纹理是否为预乘Alpha（按资源记住）"),
+    ("loadall", "<清单.json>", "按清单一次加载多个槽位，并套用位置/缩放/动画"),
+    ("anim", "<槽位> <动画名> [true|false] [times=N] [then=动画名] [track=N] [alpha=F] [blend=add|replace]", "设置动画"),
+    ("cleartrack", "<槽位> <轨道>", "清空动画轨道"),
+    ("cls", "", "清空控制台日志"),
+    ("clear", "log", "清空控制台日志"),
+    ("loglevel", "<verbose|info|warn|error>", "只显示不低于该级别的日志"),
+    ("logsave", "<路径>", "将带时间戳的日志保存为文本文件"),
+    ("loglimit", "<条数>", "设置控制台日志最大保留条数"),
+    ("sched", "[nice on|off] | auto <on|off> | cores <N|auto> | reserve <fixed N|fraction F|explicit N>", "查看调度器状态 / 计算线程降优先级 / 自适应并发 / 手动并发数 / 核心保留策略"),
+    ("texbudget", "[MB|off]", "查看/设置角色纹理显存预算，超出时回收最久未显示角色的纹理"),
+    ("retry", "[次数]", "查看/设置角色加载失败后的重试次数（间隔逐次翻倍，0 为不重试）"),
+    ("undo", "", "撤销上一步舞台编辑（移动/缩放/旋转/动画/加载/移除，Ctrl+Z）"),
+    ("redo", "", "重做（Ctrl+Y / Ctrl+Shift+Z）"),
+    ("grid", "<间距> | off", "拖动角色时吸附到网格（对齐其他角色时显示参考线，按住 Alt 临时关闭吸附）"),
+    ("gizmo", "on|off", "显示选中角色的包围盒与变换手柄（四角缩放、顶部旋转）"),
+    ("console", "dock|float", "控制台停靠在屏幕底部（下拉式，可用 bind console 改键，默认 `）或作为浮动窗口"),
+    ("demo", "on|off", "演示模式：数字键播放选中角色的第 N 个动画，并显示当前角色信息"),
+    ("focus", "<槽位> | off", "突出焦点角色：其余角色降低饱和度、压暗并软化边缘（景深近似）"),
+    ("tag", "<槽位> <文字> | <槽位> off | on|off", "设置角色头顶名牌（随角色移动）/ 开关全部名牌"),
+    ("skip", "[on|off]", "快进：立即显示整句并连续推进后续各幕（也可按住快进键）"),
+    ("freeze", "", "冻结整个模拟（界面与输入照常）"),
+    ("unfreeze", "", "解除冻结"),
+    ("pingpong", "<槽位> <动画A> <动画B> | <槽位> off", "两个动画交替循环播放"),
+    ("mix", "<槽位> [<从动画> <到动画> <秒>]", "列出 / 设置两个动画之间的过渡时长（随 SAVE 保存）"),
+    ("pacing", "[cjk=倍率] [latin=倍率] [short=倍数] [long=倍数] | reset", "查看/设置打字机按字符类别的节奏（全角/其他字符间隔倍率，标点后停顿）"),
+    ("config", "save|load [路径]", "保存/重新加载配置（TOML，默认 aefr.toml）：打字速度、舞台底色、字体、日志、调度、对话框主题"),
+    ("hide", "<槽位> [pause]", "隐藏角色但保持加载（pause 同时暂停动画）"),
+    ("show", "<槽位>", "显示被隐藏的角色并恢复动画"),
+    ("reset", "<槽位> [stop]", "清除骨骼/附件覆盖并重置到初始姿势"),
+    ("bgm", "<路径>", "播放背景音乐"),
+    ("se", "<路径> [slot=N]", "播放音效（指定槽位时按角色位置声像）"),
+    ("stop", "", "停止背景音乐"),
+    ("pausebgm", "", "暂停背景音乐"),
+    ("resumebgm", "", "继续背景音乐"),
+    ("talk", "名称|所属|内容[|color=#RRGGBB][|pos=left|center|right][|voice=<路径>]", "发送对话"),
+    ("bg", "<路径> | anim <帧目录> [fps=12] | layer <路径|clear>", "设置背景（静态图片或序列帧）/ 叠加或清除透明图层"),
+    ("bone", "<槽位> [骨骼名] | <槽位> <骨骼名> rot <角度>", "查看骨骼 / 覆盖骨骼旋转"),
+    ("face", "<槽位> <插槽名> [附件名|none]", "替换表情附件"),
+    ("slot", "<槽位> [插槽名 hide|show]", "列出已隐藏插槽 / 隐藏或显示整个插槽（随 SAVE 保存）"),
+    ("lipsync", "<槽位> <嘴部插槽> <张嘴附件> [闭嘴附件] | off", "口型同步"),
+    ("watch", "<槽位> <on|off>", "源文件变化时自动热重载角色"),
+    ("record", "<槽位> <目录> [fps=30] [duration=3]", "以固定帧率把当前动画录制为PNG序列"),
+    ("export", "<目录> [copy]", "导出剧本与资源清单"),
+    ("typesfx", "<路径|off>", "设置打字音效"),
+    ("crossfade", "<秒>", "冻结当前角色层并交叉淡化到新阵容"),
+    ("seed", "[非负整数]", "查看 / 固定随机效果种子（shake 可复现）"),
+    ("shake", "<强度像素> <秒> | off", "画面震动"),
+    ("letterbox", "<on [高度比例] [above]|off>", "电影黑边"),
+    ("title", "<文本>", "设置窗口标题"),
+    ("resize", "<宽> <高>", "调整窗口内部尺寸（逻辑像素）"),
+    ("fullscreen", "<on|off>", "切换全屏"),
+    ("unfocused", "[帧率]", "查看/设置窗口失去焦点时的帧率上限（0 为不限制；最小化时总是暂停更新）"),
+    ("renderscale", "[倍数]", "查看/设置渲染缩放（系统缩放的倍数，放大 UI 与文字，角色占比不变）"),
+    ("ruler", "<on [间距像素]|off>", "坐标标尺与指针/选中角色坐标"),
+    ("formation", "[line|arc|v|staggered]", "自动排布阵型（仅作用于未手动定位的角色）"),
+    ("stage", "<#RRGGBB|transparent|reset>", "设置舞台底色 / 透明舞台"),
+    ("vignette", "<强度 0~1>", "设置暗角强度（0 关闭）"),
+    ("grade", "<#RRGGBBAA|off>", "设置全屏调色叠加"),
+    ("bgfit", "<stretch|cover|contain|tile>", "设置背景适配模式"),
+    ("filter", "<nearest|linear>", "切换纹理采样方式"),
+    ("safearea", "<上> <下> <左> <右> | off", "设置安全区内边距"),
+    ("theme", "<boxheight|bg|alpha|plate|affcolor|affscale|namesize|textsize|padding|radius> <值> | load <路径> | save <路径> | reset", "设置对话框主题"),
+    ("stats", "", "切换性能面板"),
+    ("profile", "[on|off]", "输出更新耗时报告 / 开关逐角色计时"),
+    ("audio", "reinit", "重新初始化音频设备"),
+    ("list", "", "列出所有槽位与已加载资源"),
+    ("drawcalls", "", "查看上一帧角色绘制调用数"),
+    ("status", "<槽位>", "查看角色当前状态"),
+    ("anims", "<槽位>", "列出角色全部动画名与时长（按名称排序）"),
+    ("dump", "<槽位>", "列出插槽、当前附件与图集页（排查显示问题）"),
+    ("pos", "<槽位> <left|center-left|center|center-right|right|auto> | <槽位> <x%> <y%> | <槽位> <x> <y>", "设置角色舞台位置"),
+    ("shadowblob", "<槽位> [on [宽度] [不透明度] | off]", "查看/设置脚下椭圆软阴影（宽度为包围盒宽度的比例，默认 0.7 / 0.35）"),
+    ("anchor", "<槽位> [root|feet|center]", "查看/设置定位锚点：骨骼根节点 / 包围盒底边中点 / 包围盒中心"),
+    ("bind", "[<advance|console|close|slots|freeze|skip|cycle|quickanims> <按键...>]", "查看/设置快捷键"),
+    ("tween", "<槽位> <x|y|scale|alpha|rotation> <目标值> <秒> [linear|in|out|inout] [queue] | <槽位> cancel [属性]", "属性补间动画"),
+    ("rot", "<槽位> <角度>", "设置角色旋转角度"),
+    ("attach", "<槽位> <骨骼名> <图片路径|off>", "绑定跟随骨骼的道具贴图 / 解除"),
+    ("coord", "<槽位> [flipy=on|off] [flipx=on|off]", "修正非标准导出的坐标翻转（按资源记住）"),
+    ("alpha", "<槽位> <0~1>", "设置角色不透明度"),
+    ("enter", "<槽位> [from=left|right|bottom|fade] [秒]", "角色登场过渡"),
+    ("exit", "<槽位> [to=left|right|bottom|fade] [秒]", "角色退场过渡并卸载"),
+    ("fitheight", "<槽位> <比例|off>", "按屏幕高度比例缩放角色"),
+    ("set", "[<名称> = <值>]", "设置变量（命令中以 ${名称} 引用）"),
+    ("unset", "<名称>", "删除变量"),
+    ("macro", "[<名称> [= 命令1 ; 命令2 ; ...]]", "定义/查看/删除宏"),
+    ("timeline", "[on|off|clear] | save <路径>", "录制控制台命令时间线 / 导出为可回放脚本"),
+    ("play", "<脚本路径> | stop", "逐行执行命令脚本（# 开头为注释）/ 停止回放"),
+    ("wait", "<秒>", "脚本中：等待若干秒再执行后续命令"),
+    ("alias", "[<名称> [= <命令> [预设参数...]]]", "定义/查看/删除命令别名（与内置命令同名时只能指向该命令本身）"),
+    ("help", "[命令]", "查看命令用法"),
+];
+
+/// 引号感知的命令切分：按空白分词，双引号内的空白保留，并去掉引号本身
+/// 不处理反斜杠转义，以便直接书写 Windows 路径（如 "C:\My Chars\hoshino.atlas"）
+fn tokenize_command(input: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut has_token = false;  // 区分空引号 "" 与无内容
+    
+    for c in input.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                has_token = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if has_token {
+                    tokens.push(std::mem::take(&mut current));
+                    has_token = false;
+                }
+            }
+            c => {
+                current.push(c);
+                has_token = true;
+            }
+        }
+    }
+    if has_token {
+        tokens.push(current);
+    }
+    tokens
+}
+
+/// 引号感知的分隔：按 sep 切分，引号内的 sep 保留，并去掉引号本身
+fn split_unquoted(input: &str, sep: char) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut in_quotes = false;
+    for c in input.chars() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            c if c == sep && !in_quotes => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+/// 将 ${名称} 替换为变量值；返回替换结果及未定义的变量名（未定义者替换为空）
+/// 缺少右括号的 ${ 原样保留
+fn interpolate_vars(input: &str, vars: &HashMap<String, String>) -> (String, Vec<String>) {
+    let mut out = String::with_capacity(input.len());
+    let mut undefined = Vec::new();
+    let mut remaining = input;
+    while let Some(start) = remaining.find("${") {
+        out.push_str(&remaining[..start]);
+        let after = &remaining[start + 2..];
+        match after.find('}') {
+            Some(end) => {
+                let name = after[..end].trim();
+                match vars.get(name) {
+                    Some(value) => out.push_str(value),
+                    None => undefined.push(name.to_string()),
+                }
+                remaining = &after[end + 1..];
+            }
+            None => {
+                out.push_str(&remaining[start..]);
+                remaining = "";
+            }
+        }
+    }
+    out.push_str(remaining);
+    (out, undefined)
+}
+
+/// 校验别名定义（名称已转小写）：与内置命令同名的别名只能指向该命令本身（如 alias bg = BG anim），
+/// 其他别名不能指向自身
+fn validate_alias(name: &str, target: &str) -> Result<(), String> {
+    let target_head = tokenize_command(target).into_iter().next().unwrap_or_default().to_lowercase();
+    let builtin = COMMAND_USAGES.iter().any(|(cmd, _, _)| *cmd == name);
+    if builtin && target_head != name {
+        return Err(format!("ALIAS: 不能覆盖内置命令 {}（同名别名只能指向 {} 本身）", name, name));
+    }
+    if !builtin && target_head == name {
+        return Err(format!("ALIAS: 别名 {} 不能指向自身", name));
+    }
+    Ok(())
+}
+
+/// 展开别名：目标命令（可带预设参数）之后接上调用时的参数
+fn expand_alias(target: &str, rest: &str) -> String {
+    if rest.is_empty() {
+        target.to_string()
+    } else {
+        format!("{} {}", target, rest)
+    }
+}
+
+/// 按分号拆分一行中的多条命令（引号内的分号保留，引号本身也保留供后续解析）
+fn split_commands(input: &str) -> Vec<String> {
+    let mut cmds = vec![String::new()];
+    let mut in_quotes = false;
+    for c in input.chars() {
+        match c {
+            ';' if !in_quotes => cmds.push(String::new()),
+            c => {
+                if c == '"' { in_quotes = !in_quotes; }
+                cmds.last_mut().unwrap().push(c);
+            }
+        }
+    }
+    cmds.into_iter().map(|c| c.trim().to_string()).collect()
+}
+
+/// 控制台日志级别：由日志前缀决定，用于 loglevel 过滤显示
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+enum LogLevel {
+    Verbose,  // 逐帧/高频日志：[事件] [性能]
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    fn of(line: &str) -> Self {
+        if line.starts_with("[错误]") {
+            LogLevel::Error
+        } else if line.starts_with("[警告]") {
+            LogLevel::Warn
+        } else if line.starts_with("[事件]") || line.starts_with("[性能]") {
+            LogLevel::Verbose
+        } else {
+            LogLevel::Info
+        }
+    }
+    
+    fn name(self) -> &'static str {
+        match self {
+            LogLevel::Verbose => "verbose",
+            LogLevel::Info => "info",
+            LogLevel::Warn => "warn",
+            LogLevel::Error => "error",
+        }
+    }
+    
+    fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "verbose" | "all" => Some(LogLevel::Verbose),
+            "info" => Some(LogLevel::Info),
+            "warn" => Some(LogLevel::Warn),
+            "error" => Some(LogLevel::Error),
+            _ => None,
+        }
+    }
+}
+
+/// 生成某命令的用法错误提示
+fn usage_error(cmd: &str) -> String {
+    match COMMAND_USAGES.iter().find(|(name, _, _)| *name == cmd) {
+        Some((name, usage, _)) => format!("{}: 用法 {} {}", name.to_uppercase(), name, usage),
+        None => format!("未知命令: {}", cmd),
+    }
+}
+
+// ============================================================================
+// 主应用程序逻辑
+// ============================================================================
+/// 主应用程序状态
+struct AefrApp {
+    // 系统组件
+    scheduler: AefrScheduler,      // 并行调度器
+    audio_manager: Option<AudioManager>, // 音频管理器（可选，可能初始化失败）
+    audio_check_timer: f32,        // 音频设备检查计时器（热插拔/自动重连）
+    
+    // 热重载
+    watched_slots: HashMap<usize, Vec<(PathBuf, Option<SystemTime>)>>, // 槽位 -> 源文件及上次修改时间
+    pending_reloads: HashSet<usize>, // 正在热重载的槽位（加载成功后沿用原有状态）
+    watch_timer: f32,              // 修改时间轮询计时器
+    
+    // 剧本状态
+    scenario: Scenario,            // 当前剧本
+    current_scene_idx: usize,      // 当前场景索引
+    
+    // 对话状态
+    target_chars: Vec<char>,       // 目标文本字符数组
+    visible_count: usize,          // 当前可见字符数
+    type_timer: f32,               // 打字机计时器
+    type_interval: f32,            // 打字机每个字符的基准显示间隔（秒）
+    type_pacing: TypePacing,       // 打字机按字符类别的节奏倍率
+    font_path: String,             // 当前外部字体（空为内置字体）
+    type_sfx: Option<Vec<u8>>,     // 打字音效数据（None 为关闭）
+    type_sfx_timer: f32,           // 距上次打字音效的时间
+    voice_generation: u64,         // 台词序号：每次切换台词递增，用于丢弃过期的语音读取结果
+    indicator_timer: f32,          // "继续"指示器闪烁计时（本句显示完毕后开始计时）
+    
+    // UI状态
+    dialogue_theme: DialogueTheme, // 对话框主题
+    safe_insets: egui::Margin,     // 安全区内边距（刘海/圆角屏），交互UI据此内缩
+    texture_options: egui::TextureOptions, // 角色/背景纹理采样方式（filter 命令切换）
+    bg_fit: BgFit,                 // 背景适配模式
+    vignette_strength: f32,        // 暗角强度（0 为关闭）
+    grade_tint: Option<Color32>,   // 全屏调色叠加色（None 为关闭）
+    letterbox: (f32, f32, f32),    // 电影黑边动画 (起始高度, 目标高度, 已用时间)，高度为屏幕比例
+    rng_seed: u64,                 // 随机效果种子（seed 命令固定后可复现）
+    shake: Option<Shake>,          // 进行中的画面震动
+    shake_offset: Vec2,            // 本帧震动偏移
+    letterbox_above: bool,         // 黑边绘制在对话框之上
+    coord_presets: HashMap<String, CoordFlags>, // 按资源路径记住的坐标修正，重新加载同一资源时自动应用
+    pma_overrides: HashMap<String, bool>,   // 按资源路径记住的 pma 手动指定（LOAD ... pma=on|off），优先于图集自动识别
+    props: Vec<Prop>,              // 跟随骨骼的道具
+    name_tags: HashMap<usize, String>, // 角色头顶名牌：槽位 -> 文字
+    focus_slot: Option<usize>,     // 焦点角色（其余角色做景深近似），None 为关闭
+    show_gizmo: bool,              // 选中角色的包围盒与变换手柄
+    demo_mode: bool,               // 演示模式：quick_anims 按键播放动画（取代 slots 按键选槽位）并显示 HUD
+    grid_size: Option<f32>,        // 拖动吸附网格间距（像素），None 为关闭
+    drag_raw_offset: Option<Vec2>, // 拖动中未经吸附的累积偏移，None 表示未在拖动
+    snap_guides: (Option<f32>, Option<f32>), // 当前显示的对齐参考线 (竖线x, 横线y)
+    show_name_tags: bool,          // 名牌总开关
+    stage_color: Option<Color32>,  // 舞台底色（None 为透明，用于带Alpha的截图/导出）
+    transparent_window: bool,      // 窗口创建时是否启用了透明（运行中不可更改）
+    formation: Formation,          // 自动排布阵型
+    stage_snapshot: Option<StageSnapshot>, // 交叉淡化中的旧角色层
+    is_auto_enabled: bool,         // 自动播放模式
+    show_dialogue: bool,           // 显示对话框
+    console_open: bool,            // 控制台窗口状态
+    frozen: bool,                  // 全局冻结：模拟时间停止，输入/拖动/控制台照常响应
+    skip_toggle: bool,             // 快进开关（skip 命令）
+    skip_held: bool,               // 快进键是否按住（松开即恢复）
+    skip_timer: f32,               // 快进模式下当前幕已停留的时间
+    bgm_playing_before_freeze: bool, // 冻结前BGM是否在播放（解冻时据此决定是否继续）
+    selected_slot: usize,          // 当前选中的角色槽位
+    console_input: String,         // 控制台输入
+    console_logs: Vec<String>,     // 控制台日志（每帧裁剪到 log_limit 条）
+    log_times: Vec<f64>,           // 与 console_logs 一一对应的时间戳（启动后秒数，写入后下一帧补记）
+    log_limit: usize,              // 控制台日志最大保留条数
+    log_level: LogLevel,           // 控制台显示的最低日志级别
+    pending_enters: HashMap<usize, (Vec2, f32)>, // 对空槽位发出的登场过渡（屏外偏移, 时长），加载完成后开始
+    loadall_pending: HashMap<usize, LoadAllEntry>, // LOADALL 中尚未完成的槽位，加载完成后套用其属性
+    loadall_result: (usize, usize),    // 当前 LOADALL 批次的 (成功, 失败) 数
+    tweens: Vec<Tween>,            // 进行中的属性补间（同一槽位同一属性至多一个）
+    undo_stack: Vec<UndoEntry>,    // 撤销栈（最新在末尾）
+    redo_stack: Vec<UndoEntry>,    // 重做栈
+    undo_base: Vec<Option<SlotSnapshot>>, // 上一个检查点的舞台状态
+    undo_dirty: bool,              // 自上个检查点以来发生过编辑操作，待比较
+    pending_restores: HashMap<usize, SlotSnapshot>, // 撤销/重做中需要重新加载的槽位，加载完成后恢复属性
+    tween_queue: Vec<Tween>,       // 排队的补间：所属槽位没有进行中的补间时依次启动
+    key_bindings: KeyBindings,     // 快捷键绑定
+    variables: HashMap<String, String>, // 控制台变量（set/unset），命令中以 ${名称} 引用
+    
+    // 命令时间线与脚本回放
+    timeline_start: Option<Instant>,   // 正在录制时为录制开始时刻（timeline on）
+    timeline: Vec<(f32, String)>,      // 已录制的命令：(相对开始的秒数, 原始命令文本)
+    script_queue: VecDeque<String>,    // play 读入、尚未执行的脚本命令
+    script_wait: f32,                  // 脚本剩余等待时间（wait 命令累加，逐帧扣减）
+    
+    // 动画预览
+    show_anim_preview: bool,       // 显示动画预览窗口
+    preview_anim_idx: usize,       // 预览动画索引
+    
+    // 游戏对象
+    // 🌟 关键：使用Arc<Mutex>包装SpineObject，实现线程安全共享
+    characters: Vec<Option<Arc<Mutex<SpineObject>>>>, // 5个角色槽位
+    background: Option<Background>,    // 背景（静态或序列帧）
+    bg_layers: Vec<(String, Option<TextureHandle>)>, // 背景图层（自下而上），解码完成前纹理为 None
+    lip_sync: Option<LipSyncBinding>,  // 口型同步绑定
+    draw_call_stats: (usize, usize),   // 上一帧角色绘制调用数（合并前, 合并后）
+    
+    // 性能面板
+    show_stats: bool,                  // 显示性能面板
+    ruler_step: Option<f32>,           // 坐标标尺网格间距（像素），None 为关闭
+    frame_time_avg: f32,               // 平滑后的帧时间（秒）
+    mesh_totals: (usize, usize),       // 上一帧生成的顶点/索引总数
+    parallel_time: Duration,           // 上一帧并行更新块的墙钟耗时
+    parallel_used: bool,               // 上一帧是否经由线程池更新（单角色时串行）
+    sched_auto: bool,                  // 自适应并发开关（sched auto on|off）
+    sched_manual: Option<usize>,       // 手动指定的并发数（sched cores），设置后优先于自适应
+    sched_limit: usize,                // 自适应模式下当前的并发上限
+    sched_adapt_timer: f32,            // 距上次自适应调整的时间
+    serial_time_sum: Duration,         // 上一帧各角色更新耗时之和（需开启逐角色计时）
+    profile_chars: bool,               // 逐角色计时开关（有额外开销，默认仅调试构建开启）
+    texture_budget: Option<usize>,     // 角色纹理显存预算（字节），None 为不限制
+    asset_cache: HashMap<String, SharedAsset>, // 角色资源缓存（路径 -> 共享资源），只保留仍有角色在使用的条目
+    load_retries: u32,                 // 角色加载失败后的重试次数（retry 命令设置）
+    render_scale: f32,                 // 渲染缩放：pixels_per_point = 系统缩放 × render_scale
+    console_docked: bool,              // 控制台停靠在底部（下拉式）还是浮动窗口
+    unfocused_fps: u32,                // 失去焦点时的帧率上限（0 为不限制）
+    suspended: bool,                   // 窗口已最小化：跳过更新与绘制
+    
+    // 线程通信
+    tx: Sender<AppCommand>,        // 命令发送端
+    rx: Receiver<AppCommand>,      // 命令接收端
+}
+
+impl AefrApp {
+    /// 创建应用程序实例
+    fn new(cc: &eframe::CreationContext) -> Self {
+        // 1. 设置嵌入式字体
+        setup_embedded_font(&cc.egui_ctx);
+        
+        // 2. 安装图片加载器
+        egui_extras::install_image_loaders(&cc.egui_ctx);
+        
+        // 3. 创建线程通信通道
+        let (tx, rx) = channel();
+        
+        // 4. 初始化音频系统（允许失败）
+        let audio_manager = match AudioManager::new() {
+            Ok(mgr) => {
+                let _ = tx.send(AppCommand::Log(format!(
+                    "[音频] 已连接输出设备: {}", 
+                    mgr.device_name.as_deref().unwrap_or("(未知)")
+                )));
+                Some(mgr)
+            }
+            Err(e) => {
+                // 记录错误但不中断程序
+                let _ = tx.send(AppCommand::Log(
+                    format!("[警告] 音频系统初始化失败 (无声卡/独占): {}", e)
+                ));
+                None
+            }
+        };
+        
+        // 4.1 可配置项的初始值统一取自 AppConfig::default()，与配置文件的缺省值保持一致
+        let defaults = AppConfig::default();
+        
+        // 4.2 创建并行调度器（失败时退回单线程执行）
+        let (scheduler, sched_log) = AefrScheduler::new_or_inline(defaults.sched_nice, ReservePolicy::default());
+        let _ = tx.send(AppCommand::Log(sched_log));
+        
+        // 4.3 用户配置（首帧在 UI 线程应用，届时可重建字体）
+        // 透明窗口与 main 中创建窗口时读取的是同一份配置（安卓窗口始终不透明）
+        let mut transparent_window = defaults.transparent_window;
+        if std::path::Path::new(AppConfig::FILE).exists() {
+            match AppConfig::load(AppConfig::FILE) {
+                Ok((config, unknown)) => {
+                    for key in unknown {
+                        let _ = tx.send(AppCommand::Log(format!("[警告] {}: 未知配置项 {}，已忽略", AppConfig::FILE, key)));
+                    }
+                    transparent_window = cfg!(not(target_os = "android")) && config.transparent_window;
+                    let _ = tx.send(AppCommand::ApplyConfig(Box::new(config)));
+                }
+                Err(e) => { let _ = tx.send(AppCommand::Log(format!("[错误] {}，使用默认配置", e))); }
+            }
+        }
+        
+        // 5. 创建初始场景
+        let startup_text = "GNU:AEFR 已启动！\n正在等待指令......";
+        let mut first_scene = Scene::default();
+        first_scene.speaker_name = "OxidizedSchale".into();
+        first_scene.speaker_aff = "The Executive Committee of GNU:AEFR".into();
+        first_scene.dialogue_content = startup_text.into();
+
+        // 6. 返回应用实例
+        Self {
+            scheduler,
+            dialogue_theme: defaults.theme,
+            safe_insets: egui::Margin::ZERO,
+            texture_options: egui::TextureOptions::LINEAR,
+            bg_fit: BgFit::default(),
+            vignette_strength: 0.0,
+            grade_tint: None,
+            letterbox: (0.0, 0.0, LETTERBOX_SLIDE_TIME),
+            rng_seed: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or(0),
+            shake: None,
+            shake_offset: Vec2::ZERO,
+            letterbox_above: false,
+            coord_presets: HashMap::new(),
+            pma_overrides: HashMap::new(),
+            props: Vec::new(),
+            name_tags: HashMap::new(),
+            focus_slot: None,
+            show_gizmo: false,
+            demo_mode: false,
+            grid_size: None,
+            drag_raw_offset: None,
+            snap_guides: (None, None),
+            show_name_tags: true,
+            stage_color: parse_hex_color(&defaults.stage_color),
+            transparent_window,
+            formation: Formation::default(),
+            stage_snapshot: None,
+            is_auto_enabled: true, 
+            show_dialogue: true,
+            scenario: Scenario { scenes: vec![first_scene], ..Default::default() },
+            current_scene_idx: 0,
+            target_chars: startup_text.chars().collect(), 
+            visible_count: 0, 
+            type_timer: 0.0,
+            type_interval: defaults.type_interval,
+            type_pacing: defaults.pacing,
+            font_path: defaults.font_path,
+            type_sfx: None,
+            type_sfx_timer: 0.0,
+            voice_generation: 0,
+            indicator_timer: 0.0,
+            frozen: false,
+            skip_toggle: false,
+            skip_held: false,
+            skip_timer: 0.0,
+            bgm_playing_before_freeze: false,
+            console_open: false,
+            key_bindings: KeyBindings::load().unwrap_or_else(|e| {
+                let _ = tx.send(AppCommand::Log(format!("[错误] {}，使用默认快捷键", e)));
+                KeyBindings::default()
+            }),
+            variables: HashMap::new(),
+            timeline_start: None,
+            timeline: Vec::new(),
+            script_queue: VecDeque::new(),
+            script_wait: 0.0,
+            pending_enters: HashMap::new(),
+            loadall_pending: HashMap::new(),
+            loadall_result: (0, 0),
+            tweens: Vec::new(),
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            undo_base: vec![None; SLOT_COUNT],
+            undo_dirty: false,
+            pending_restores: HashMap::new(),
+            tween_queue: Vec::new(),
+            selected_slot: 0,
+            console_input: String::new(),
+            console_logs: vec!["[系统] 编辑器就绪。".into()],
+            log_times: vec![0.0],
+            log_limit: defaults.log_limit,
+            log_level: LogLevel::parse(&defaults.log_level).unwrap_or(LogLevel::Verbose),
+            show_anim_preview: false,
+            preview_anim_idx: 0,
+            // 初始化5个空角色槽位
+            characters: (0..SLOT_COUNT).map(|_| None).collect(),
+            background: None,
+            bg_layers: Vec::new(),
+            lip_sync: None,
+            audio_check_timer: 0.0,
+            watched_slots: HashMap::new(),
+            pending_reloads: HashSet::new(),
+            watch_timer: 0.0,
+            draw_call_stats: (0, 0),
+            show_stats: false,
+            ruler_step: None,
+            frame_time_avg: 0.0,
+            mesh_totals: (0, 0),
+            parallel_time: Duration::ZERO,
+            parallel_used: false,
+            sched_auto: defaults.sched_auto,
+            sched_manual: None,
+            sched_limit: usize::MAX,
+            sched_adapt_timer: 0.0,
+            texture_budget: (defaults.texture_budget_mb > 0).then_some(defaults.texture_budget_mb * 1024 * 1024),
+            load_retries: defaults.load_retries,
+            render_scale: defaults.render_scale,
+            console_docked: defaults.console_docked,
+            unfocused_fps: defaults.unfocused_fps,
+            suspended: false,
+            asset_cache: HashMap::new(),
+            serial_time_sum: Duration::ZERO,
+            profile_chars: cfg!(debug_assertions),
+            audio_manager,
+            tx, rx,
+        }
+    }
+
+    /// 同步当前场景到UI状态
+    /// 在场景切换时调用，重置对话状态
+    fn sync_scene_to_ui(&mut self) {
+        if let Some(scene) = self.scenario.scenes.get(self.current_scene_idx) {
+            self.target_chars = scene.dialogue_content.chars().collect();
+            // 🌟 修复打字机残影：切幕时必须归零
+            self.visible_count = 0;
+            self.type_timer = 0.0;
+            
+            // 切换台词：停止上一句语音，异步读取本句语音
+            self.voice_generation += 1;
+            if let Some(mgr) = &self.audio_manager {
+                mgr.stop_voice();
+            }
+            if let Some(path) = scene.voice_path.clone() {
+                let tx_cb = self.tx.clone();
+                let generation = self.voice_generation;
+                thread::spawn(move || {
+                    match std::fs::read(&path) {
+                        Ok(d) => { let _ = tx_cb.send(AppCommand::VoiceReady(generation, d)); }
+                        Err(e) => { let _ = tx_cb.send(AppCommand::Log(format!("[错误] 语音读取失败 {}: {}", path, e))); }
+                    }
+                });
+            }
+        }
+    }
+
+    /// 以当前纹理采样方式重新上传所有纹理
+    /// 上传后不保留像素数据，因此借用热重载流程重新加载角色（沿用位置、缩放与动画）
+    fn reload_textures(&mut self) {
+        self.asset_cache.clear();  // 缓存的纹理使用旧的采样方式
+        let mut count = 0;
+        for (idx, slot) in self.characters.iter().enumerate() {
+            let Some(Ok(char)) = slot.as_ref().map(|c| c.lock()) else { continue };
+            if self.pending_reloads.insert(idx) {
+                let _ = self.tx.send(AppCommand::RequestLoad { slot_idx: idx, path: char.source_path.clone() });
+                count += 1;
+            }
+        }
+        let scene = &self.scenario.scenes[self.current_scene_idx];
+        if let (Some(bg), true) = (scene.bg_path.clone(), self.background.is_some()) {
+            let _ = self.tx.send(match scene.bg_anim_fps {
+                Some(fps) => AppCommand::LoadAnimatedBackground { dir: bg, fps },
+                None => AppCommand::LoadBackground(bg),
+            });
+        }
+        for (_, tex) in &mut self.bg_layers {
+            *tex = None;
+        }
+        for path in self.bg_layers.iter().map(|(p, _)| p.clone()).collect::<Vec<_>>() {
+            self.spawn_bg_layer_decode(path);
+        }
+        self.console_logs.push(format!("[系统] 纹理采样方式已切换，正在重新加载 {} 个角色", count));
+    }
+    
+    /// 在后台解码背景图层，完成后发送 BackgroundLayerReady
+    fn spawn_bg_layer_decode(&self, path: String) {
+        let tx = self.tx.clone();
+        thread::spawn(move || match decode_background_image(&path) {
+            Ok(image) => { let _ = tx.send(AppCommand::BackgroundLayerReady(path, image)); }
+            Err(e) => { let _ = tx.send(AppCommand::Log(format!("[错误] {}", e))); }
+        });
+    }
+
+    /// 当前电影黑边高度（屏幕比例），按缓入缓出曲线在起止高度间插值
+    fn letterbox_height(&self) -> f32 {
+        let (from, to, elapsed) = self.letterbox;
+        let t = (elapsed / LETTERBOX_SLIDE_TIME).min(1.0);
+        from + (to - from) * Ease::InOut.apply(t)
+    }
+
+    /// 冻结/解冻整个模拟（角色动画、打字机、补间、过渡、语音与BGM）
+    /// 与逐角色的暂停不同：冻结只是把分发给模拟的 dt 置 0，UI 仍每帧重绘并处理输入
+    fn set_frozen(&mut self, frozen: bool) {
+        if self.frozen == frozen {
+            return;
+        }
+        if let Some(mgr) = &self.audio_manager {
+            if frozen {
+                self.bgm_playing_before_freeze = mgr.bgm_state() == "播放中";
+            }
+            mgr.set_frozen(frozen, self.bgm_playing_before_freeze);
+        }
+        self.frozen = frozen;
+        self.console_logs.push(format!("[系统] 模拟已{}", if frozen { "冻结" } else { "解冻" }));
+    }
+
+    /// 处理全局快捷键
+    fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        if any_key_pressed(ctx, &self.key_bindings.advance) && self.show_dialogue {
+            self.visible_count = self.target_chars.len();  // 与点击对话框相同：立即显示全文
+        }
+        if any_key_pressed(ctx, &self.key_bindings.console) {
+            self.console_open = !self.console_open;
+        }
+        if any_key_pressed(ctx, &self.key_bindings.freeze) {
+            self.set_frozen(!self.frozen);
+        }
+        // 撤销/重做：Ctrl+Z / Ctrl+Y（或 Ctrl+Shift+Z），macOS 上为 Cmd
+        let (undo, redo) = ctx.input_mut(|i| {
+            let redo = i.consume_key(egui::Modifiers::COMMAND | egui::Modifiers::SHIFT, egui::Key::Z)
+                || i.consume_key(egui::Modifiers::COMMAND, egui::Key::Y);
+            (i.consume_key(egui::Modifiers::COMMAND, egui::Key::Z), redo)
+        });
+        if undo || redo {
+            self.undo_dirty = true;
+            self.undo_checkpoint(false);  // 先提交尚未记录的修改，撤销才会回到它之前
+            self.undo_redo(undo);
+        }
+        self.skip_held = any_key_down(ctx, &self.key_bindings.skip);
+        if any_key_pressed(ctx, &self.key_bindings.close) {
+            self.console_open = false;
+            self.show_anim_preview = false;
+        }
+        if any_key_pressed(ctx, &self.key_bindings.cycle) {
+            let backward = ctx.input(|i| i.modifiers.shift);
+            self.cycle_selected_slot(backward);
+        }
+        if self.demo_mode {
+            // 演示模式：数字键播放选中角色的第 N 个动画（按名称排序，与 anims 列表一致）
+            let pressed = self.key_bindings.quick_anims.iter()
+                .position(|key| any_key_pressed(ctx, std::slice::from_ref(key)));
+            if let Some(n) = pressed {
+                let name = match self.characters.get(self.selected_slot) {
+                    Some(Some(char_arc)) => char_arc.lock().ok()
+                        .and_then(|c| c.anim_durations().into_iter().nth(n).map(|(name, _)| name)),
+                    _ => None,
+                };
+                if let Some(anim_name) = name {
+                    let _ = self.tx.send(AppCommand::SetAnimation { slot_idx: self.selected_slot, track: 0, anim_name, loop_anim: true });
+                    self.undo_dirty = true;
+                }
+            }
+        } else {
+            for (idx, key) in self.key_bindings.slots.iter().enumerate().take(self.characters.len()) {
+                if any_key_pressed(ctx, std::slice::from_ref(key)) {
+                    self.selected_slot = idx;
+                }
+            }
+        }
+    }
+    
+    /// 选中下一个（backward 时为上一个）已加载角色的槽位，没有角色时不变
+    fn cycle_selected_slot(&mut self, backward: bool) {
+        let count = self.characters.len();
+        let next = (1..=count)
+            .map(|step| if backward {
+                (self.selected_slot + count - step) % count
+            } else {
+                (self.selected_slot + step) % count
+            })
+            .find(|&idx| matches!(self.characters.get(idx), Some(Some(_))));
+        if let Some(idx) = next {
+            self.selected_slot = idx;
+        }
+    }
+
+    /// 推进属性补间：启动排队项、插值写入角色属性、结束时发出完成事件
+    /// 角色被卸载的槽位上的补间直接丢弃
+    fn update_tweens(&mut self, dt: f32) {
+        // 1. 没有进行中补间的槽位，启动其下一个排队补间
+        let mut i = 0;
+        while i < self.tween_queue.len() {
+            let slot = self.tween_queue[i].slot_idx;
+            let busy = self.tweens.iter().any(|t| t.slot_idx == slot);
+            let first_for_slot = !self.tween_queue[..i].iter().any(|t| t.slot_idx == slot);
+            if !busy && first_for_slot {
+                let tween = self.tween_queue.remove(i);
+                self.tweens.push(tween);
+            } else {
+                i += 1;
+            }
+        }
+        
+        // 2. 推进并写入
+        let characters = &self.characters;
+        let tx = &self.tx;
+        self.tweens.retain_mut(|tween| {
+            let Some(Some(char_arc)) = characters.get(tween.slot_idx) else { return false };
+            let Ok(mut char) = char_arc.lock() else { return false };
+            
+            let from = *tween.from.get_or_insert_with(|| char.tween_value(tween.prop));
+            tween.elapsed += dt;
+            let t = if tween.duration > 0.0 { (tween.elapsed / tween.duration).min(1.0) } else { 1.0 };
+            char.set_tween_value(tween.prop, from + (tween.to - from) * tween.ease.apply(t));
+            
+            if t >= 1.0 {
+                let _ = tx.send(AppCommand::TweenComplete { slot_idx: tween.slot_idx, prop: tween.prop.name() });
+                return false;
+            }
+            true
+        });
+    }
+
+    /// 当前全部槽位的撤销快照
+    fn stage_edit_snapshot(&self) -> Vec<Option<SlotSnapshot>> {
+        self.characters.iter()
+            .map(|slot| slot.as_ref().and_then(|c| c.lock().ok()).map(|c| c.edit_snapshot()))
+            .collect()
+    }
+    
+    /// 撤销检查点：自上次检查点以来有编辑操作、且舞台状态确有变化时记录一步
+    /// 拖动/捏合进行中、补间进行中或撤销恢复尚未完成时推迟，连续的拖动因此合并为一步
+    /// gesture_active：指针/触摸仍按下（手势可能尚未结束）
+    fn undo_checkpoint(&mut self, gesture_active: bool) {
+        if !self.undo_dirty || gesture_active || !self.pending_restores.is_empty() || !self.tweens.is_empty() || !self.tween_queue.is_empty() {
+            return;
+        }
+        self.undo_dirty = false;
+        let current = self.stage_edit_snapshot();
+        if current == self.undo_base {
+            return;
+        }
+        let before = std::mem::replace(&mut self.undo_base, current.clone());
+        self.undo_stack.push(UndoEntry { before, after: current });
+        if self.undo_stack.len() > UNDO_LIMIT {
+            self.undo_stack.remove(0);
+        }
+        self.redo_stack.clear();
+    }
+    
+    /// 撤销（undo 为 false 时重做）一步
+    fn undo_redo(&mut self, undo: bool) {
+        if !self.pending_restores.is_empty() {
+            self.console_logs.push("[警告] 上一次撤销/重做仍在加载角色，请稍候".into());
+            return;
+        }
+        let (from, to) = if undo { (&mut self.undo_stack, &mut self.redo_stack) } else { (&mut self.redo_stack, &mut self.undo_stack) };
+        let Some(entry) = from.pop() else {
+            self.console_logs.push(format!("[系统] 没有可{}的操作", if undo { "撤销" } else { "重做" }));
+            return;
+        };
+        let target = if undo { entry.before.clone() } else { entry.after.clone() };
+        to.push(entry);
+        self.restore_stage(&target);
+        self.console_logs.push(format!(
+            "[系统] 已{}（剩余 撤销 {} / 重做 {}）",
+            if undo { "撤销" } else { "重做" }, self.undo_stack.len(), self.redo_stack.len(),
+        ));
+    }
+    
+    /// 把舞台恢复到快照：来源相同的槽位直接写回属性，来源不同的重新加载后再写回，多出的角色移除
+    fn restore_stage(&mut self, target: &[Option<SlotSnapshot>]) {
+        for (idx, wanted) in target.iter().enumerate() {
+            let current = match self.characters.get(idx) {
+                Some(Some(char_arc)) => Some(char_arc.clone()),
+                _ => None,
+            };
+            match (wanted, current) {
+                (None, Some(_)) => { let _ = self.tx.send(AppCommand::RemoveCharacter(idx)); }
+                (Some(snapshot), Some(char_arc)) if char_arc.lock().is_ok_and(|c| c.source_path == snapshot.path) => {
+                    if let Ok(mut char) = char_arc.lock() {
+                        char.apply_edit_snapshot(snapshot);
+                    }
+                }
+                (Some(snapshot), _) => {
+                    self.pending_restores.insert(idx, snapshot.clone());
+                    let _ = self.tx.send(AppCommand::RequestLoad { slot_idx: idx, path: snapshot.path.clone() });
+                }
+                (None, None) => {}
+            }
+        }
+        self.undo_base = target.to_vec();
+        self.undo_dirty = false;
+    }
+
+    /// 舞台手势：单指/鼠标拖动移动选中角色，双指捏合（或触控板缩放）调整其缩放
+    /// 仅当手势起始于舞台空白处（未被对话框、按钮、控制台占用）时生效
+    /// 鼠标拖动时按网格与其他角色的锚点吸附，按住 Alt 临时关闭吸附
+    fn handle_stage_gestures(&mut self, ctx: &egui::Context, stage: &egui::Response) {
+        if !stage.dragged() {
+            self.drag_raw_offset = None;
+            self.snap_guides = (None, None);
+        }
+        let Some(Some(char_arc)) = self.characters.get(self.selected_slot) else { return };
+        let Ok(mut char) = char_arc.lock() else { return };
+        
+        if stage.dragged() {
+            self.undo_dirty = true;
+            match ctx.multi_touch() {
+                Some(touch) => {
+                    char.user_scale = (char.user_scale * touch.zoom_delta).clamp(0.1, 10.0);
+                    char.user_offset += touch.translation_delta;
+                }
+                None => {
+                    // 累积未吸附的偏移，吸附只作用于显示结果，松开吸附范围后能平滑离开
+                    let raw = self.drag_raw_offset.unwrap_or(char.user_offset) + stage.drag_delta();
+                    self.drag_raw_offset = Some(raw);
+                    let wanted = char.position - char.user_offset + raw;
+                    let (snapped, guides) = if ctx.input(|i| i.modifiers.alt) {
+                        (wanted, (None, None))
+                    } else {
+                        snap_position(wanted, &self.characters, self.selected_slot, self.grid_size)
+                    };
+                    self.snap_guides = guides;
+                    char.user_offset = raw + (snapped - wanted);
+                }
+            }
+        } else if stage.hovered() {
+            // 桌面端：触控板捏合 / Ctrl+滚轮
+            let zoom = ctx.input(|i| i.zoom_delta());
+            if zoom != 1.0 {
+                char.user_scale = (char.user_scale * zoom).clamp(0.1, 10.0);
+                self.undo_dirty = true;
+            }
+        }
+    }
+
+    /// 演示模式 HUD：选中角色的槽位、名称与当前动画，以及数字键对应的动画序号
+    fn draw_demo_hud(&self, ui: &egui::Ui, screen: Rect) {
+        let text = match self.characters.get(self.selected_slot) {
+            Some(Some(char_arc)) => match char_arc.lock() {
+                Ok(char) => {
+                    let name = self.name_tags.get(&self.selected_slot).cloned().unwrap_or_else(|| {
+                        std::path::Path::new(&char.source_path).file_stem()
+                            .map_or_else(|| char.source_path.clone(), |s| s.to_string_lossy().to_string())
+                    });
+                    let anims = char.anim_durations();
+                    let current = char.current_animation_name();
+                    let index = anims.iter().position(|(n, _)| Some(n) == current.as_ref());
+                    format!(
+                        "[{}] {} · {}{}",
+                        self.selected_slot, name, char.animation_label(),
+                        index.map_or(String::new(), |i| format!(" ({}/{})", i + 1, anims.len())),
+                    )
+                }
+                Err(_) => return,
+            },
+            _ => format!("[{}] (空) · Tab 切换角色", self.selected_slot),
+        };
+        let galley = ui.painter().layout_no_wrap(text, egui::FontId::proportional(16.0), Color32::WHITE);
+        let pos = Pos2::new(screen.center().x - galley.size().x / 2.0, screen.top() + 68.0);
+        ui.painter().rect_filled(Rect::from_min_size(pos, galley.size()).expand(6.0), 4.0, Color32::from_black_alpha(160));
+        ui.painter().galley(pos, galley, Color32::WHITE);
+    }
+    
+    /// 拖动中的网格与对齐参考线（仅在拖动时绘制）
+    fn draw_snap_overlay(&self, ui: &egui::Ui, screen: Rect) {
+        if self.drag_raw_offset.is_none() {
+            return;
+        }
+        let painter = ui.painter();
+        if let Some(step) = self.grid_size {
+            let stroke = egui::Stroke::new(1.0, Color32::from_white_alpha(18));
+            let mut x = screen.left() - screen.left().rem_euclid(step);
+            while x <= screen.right() {
+                painter.vline(x, screen.y_range(), stroke);
+                x += step;
+            }
+            let mut y = screen.top() - screen.top().rem_euclid(step);
+            while y <= screen.bottom() {
+                painter.hline(screen.x_range(), y, stroke);
+                y += step;
+            }
+        }
+        let guide = egui::Stroke::new(1.0, Color32::from_rgb(255, 90, 200));
+        if let Some(x) = self.snap_guides.0 {
+            painter.vline(x, screen.y_range(), guide);
+        }
+        if let Some(y) = self.snap_guides.1 {
+            painter.hline(screen.x_range(), y, guide);
+        }
+    }
+
+    /// 选中角色的变换手柄：包围盒轮廓、四角缩放手柄、顶部旋转手柄
+    /// 缩放与旋转都以角色锚点（position）为中心；拖动角色本体沿用舞台手势平移
+    /// 手柄与舞台位于同一图层且后注册，对话框、按钮与控制台在其之上，不会被手柄抢走输入
+    fn draw_gizmo(&mut self, ui: &mut egui::Ui) {
+        let Some(Some(char_arc)) = self.characters.get(self.selected_slot) else { return };
+        let Ok(mut char) = char_arc.lock() else { return };
+        if !char.mesh_bounds.is_positive() {
+            return;  // 尚未生成网格
+        }
+        let bounds = char.mesh_bounds.translate(char.position.to_vec2());
+        let pivot = char.position;
+        let stroke = egui::Stroke::new(1.5, Color32::from_rgb(120, 200, 255));
+        let painter = ui.painter().clone();
+        painter.rect_stroke(bounds, 0.0, stroke);
+        painter.circle_stroke(pivot, 4.0, stroke);
+        
+        let gizmo_id = ui.id().with("gizmo");
+        let handle = |center: Pos2| Rect::from_center_size(center, Vec2::splat(GIZMO_HANDLE_SIZE));
+        
+        // 四角缩放：按指针到锚点距离的变化比例缩放
+        let corners = [bounds.left_top(), bounds.right_top(), bounds.left_bottom(), bounds.right_bottom()];
+        for (i, corner) in corners.into_iter().enumerate() {
+            let resp = ui.interact(handle(corner), gizmo_id.with(i), egui::Sense::drag());
+            painter.rect_filled(resp.rect, 2.0, if resp.hovered() || resp.dragged() { Color32::WHITE } else { stroke.color });
+            if resp.dragged() {
+                self.undo_dirty = true;
+                if let Some(pointer) = resp.interact_pointer_pos() {
+                    let before = (pointer - resp.drag_delta()).distance(pivot);
+                    if before > 1.0 {
+                        char.user_scale = (char.user_scale * pointer.distance(pivot) / before).clamp(0.1, 10.0);
+                    }
+                }
+            }
+        }
+        
+        // 顶部旋转：按指针绕锚点转过的角度旋转（屏幕坐标下顺时针为正，与 rotation 一致）
+        let knob = bounds.center_top() - Vec2::new(0.0, GIZMO_ROTATE_DISTANCE);
+        painter.line_segment([bounds.center_top(), knob], stroke);
+        let resp = ui.interact(handle(knob), gizmo_id.with("rotate"), egui::Sense::drag());
+        painter.circle_filled(knob, GIZMO_HANDLE_SIZE / 2.0, if resp.hovered() || resp.dragged() { Color32::WHITE } else { stroke.color });
+        if resp.dragged() {
+            self.undo_dirty = true;
+            if let Some(pointer) = resp.interact_pointer_pos() {
+                let before = (pointer - resp.drag_delta()) - pivot;
+                let after = pointer - pivot;
+                let delta = after.y.atan2(after.x) - before.y.atan2(before.x);
+                // 跨越 ±180° 时取最短方向
+                let delta = (delta + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU) - std::f32::consts::PI;
+                char.rotation = (char.rotation + delta.to_degrees()).rem_euclid(360.0);
+            }
+        }
+    }
+
+    /// 重新初始化音频系统（替换现有的音频管理器）
+    /// manual 为 false 时（自动重试）仅在成功时记录日志，避免刷屏
+    fn reinit_audio(&mut self, manual: bool) {
+        self.audio_manager = None;  // 先释放旧的输出流
+        match AudioManager::new() {
+            Ok(mgr) => {
+                self.console_logs.push(format!(
+                    "[音频] 已连接输出设备: {}", 
+                    mgr.device_name.as_deref().unwrap_or("(未知)")
+                ));
+                self.audio_manager = Some(mgr);
+                
+                // 恢复当前幕的BGM
+                if let Some(path) = self.scenario.scenes[self.current_scene_idx].bgm_path.clone() {
+                    let _ = self.tx.send(AppCommand::PlayBgm(path));
+                }
+            }
+            Err(e) => {
+                if manual {
+                    self.console_logs.push(format!("[警告] 音频系统初始化失败 (设备: {}): {}", 
+                        default_output_device_name().as_deref().unwrap_or("无"), e));
+                }
+            }
+        }
+    }
+    
+    /// 周期性检查音频设备：默认设备消失/切换时丢弃旧管理器，有设备可用时自动重连
+    fn check_audio_device(&mut self) {
+        let current = default_output_device_name();
+        if let Some(mgr) = &self.audio_manager {
+            if mgr.device_name == current {
+                return;  // 设备未变化
+            }
+            self.console_logs.push(format!(
+                "[音频] 默认输出设备变化: {} -> {}",
+                mgr.device_name.as_deref().unwrap_or("(未知)"),
+                current.as_deref().unwrap_or("无")
+            ));
+            self.audio_manager = None;
+        }
+        if current.is_some() {
+            self.reinit_audio(false);
+        }
+    }
+    
+    /// 开关槽位的源文件监视
+    fn set_watch(&mut self, idx: usize, enabled: bool) -> Result<(), String> {
+        if !enabled {
+            self.watched_slots.remove(&idx);
+            self.console_logs.push(format!("[热重载] 槽位 {} 已停止监视", idx));
+            return Ok(());
+        }
+        let files = match self.characters.get(idx) {
+            Some(Some(char_arc)) => char_arc.lock()
+                .map_err(|_| format!("槽位 {} 状态锁定失败", idx))?
+                .source_files.clone(),
+            _ => return Err(format!("WATCH: 槽位 {} 为空", idx)),
+        };
+        self.console_logs.push(format!("[热重载] 槽位 {} 开始监视 {} 个文件", idx, files.len()));
+        self.watched_slots.insert(idx, snapshot_mtimes(&files));
+        Ok(())
+    }
+    
+    /// 轮询被监视槽位的源文件，修改时间变化时发起热重载
+    fn poll_watched_files(&mut self) {
+        let mut to_reload = Vec::new();
+        for (&idx, files) in self.watched_slots.iter_mut() {
+            let current = snapshot_mtimes(&files.iter().map(|(f, _)| f.clone()).collect::<Vec<_>>());
+            if current != *files {
+                *files = current;
+                to_reload.push(idx);
+            }
+        }
+        
+        for idx in to_reload {
+            if self.pending_reloads.contains(&idx) {
+                continue;  // 上一次重载尚未完成
+            }
+            let path = match self.characters.get(idx) {
+                Some(Some(char_arc)) => match char_arc.lock() {
+                    Ok(char) => char.source_path.clone(),
+                    Err(_) => continue,
+                },
+                _ => continue,
+            };
+            self.console_logs.push(format!("[热重载] 检测到槽位 {} 源文件变化", idx));
+            self.asset_cache.remove(&path);  // 源文件已变化，缓存的解析结果作废
+            self.pending_reloads.insert(idx);
+            let _ = self.tx.send(AppCommand::RequestLoad { slot_idx: idx, path });
+        }
+    }
+    
+    /// 已上传的角色纹理总字节数（不计 except 槽位，其纹理即将被替换）；多个槽位共享的纹理只计一次
+    fn texture_usage(&self, except: Option<usize>) -> usize {
+        let mut seen = HashSet::new();
+        self.characters.iter().enumerate()
+            .filter(|(idx, _)| Some(*idx) != except)
+            .filter_map(|(_, slot)| slot.as_ref()?.lock().ok().map(|c| (c.texture_id, c.texture_bytes)))
+            .filter(|(id, _)| seen.insert(*id))
+            .map(|(_, bytes)| bytes)
+            .sum()
+    }
+    
+    /// 丢弃已没有角色使用的资源缓存项（角色被移除、替换或纹理被回收后）
+    fn prune_asset_cache(&mut self) {
+        let in_use: HashSet<TextureId> = self.characters.iter()
+            .filter_map(|slot| slot.as_ref()?.lock().ok()?.texture_id)
+            .collect();
+        self.asset_cache.retain(|_, asset| in_use.contains(&asset.texture.id()));
+    }
+    
+    /// 为即将上传到 target 槽位的纹理腾出显存：按最久未绘制的顺序回收其他角色的纹理
+    /// 最近绘制过的角色不回收；仍然不够时照常加载并给出警告
+    fn make_texture_room(&mut self, incoming: usize, target: usize) {
+        let Some(budget) = self.texture_budget else { return };
+        let mut usage = self.texture_usage(Some(target));
+        if usage + incoming <= budget {
+            return;
+        }
+        
+        // 候选：持有纹理且空闲超过阈值的槽位，最久未绘制的优先（从未绘制的最先）
+        let mut candidates: Vec<(usize, Option<Instant>)> = self.characters.iter().enumerate()
+            .filter(|(idx, _)| *idx != target)
+            .filter_map(|(idx, slot)| {
+                let char = slot.as_ref()?.lock().ok()?;
+                let idle = char.last_painted.map_or(true, |t| t.elapsed().as_secs_f32() >= TEXTURE_EVICT_IDLE);
+                (char.texture_bytes > 0 && idle).then_some((idx, char.last_painted))
+            })
+            .collect();
+        candidates.sort_by_key(|(_, painted)| *painted);
+        let idle: HashSet<usize> = candidates.iter().map(|(idx, _)| *idx).collect();
+        let texture_of = |characters: &[Option<Arc<Mutex<SpineObject>>>], idx: usize| {
+            characters.get(idx)?.as_ref()?.lock().ok()?.texture_id
+        };
+        
+        for (idx, _) in candidates {
+            if usage + incoming <= budget {
+                break;
+            }
+            // 共享纹理：所有使用者都空闲时才一起回收（连同缓存项），只回收其中一个并不会释放显存
+            let Some(texture_id) = texture_of(&self.characters, idx) else { continue };  // 已随同组回收
+            let sharers: Vec<usize> = (0..self.characters.len())
+                .filter(|i| texture_of(&self.characters, *i) == Some(texture_id))
+                .collect();
+            if sharers.iter().any(|i| !idle.contains(i)) {
+                continue;
+            }
+            let mut freed = 0;
+            for &i in &sharers {
+                if let Some(Some(char_arc)) = self.characters.get(i) {
+                    if let Ok(mut char) = char_arc.lock() {
+                        freed = freed.max(char.evict_texture());
+                    }
+                }
+            }
+            self.asset_cache.retain(|_, asset| asset.texture.id() != texture_id);
+            usage -= freed;
+            let slots = sharers.iter().map(|i| i.to_string()).collect::<Vec<_>>().join("/");
+            self.console_logs.push(format!(
+                "[性能] 显存预算: 回收槽位 {} 的纹理 ({:.1} MB)，再次显示时重新加载",
+                slots, freed as f64 / 1048576.0,
+            ));
+        }
+        if usage + incoming > budget {
+            self.console_logs.push(format!(
+                "[警告] 显存预算不足: 加载后将占用 {:.1} / {:.1} MB（其余角色均在显示中）",
+                (usage + incoming) as f64 / 1048576.0, budget as f64 / 1048576.0,
+            ));
+        }
+    }
+    
+    /// 纹理被回收、但又需要显示的角色：沿用热重载流程重新加载（保留位置与动画）
+    /// 只尝试一次；重新加载失败时该角色保持不绘制，错误见日志
+    fn reload_evicted_textures(&mut self) {
+        let wanted: Vec<(usize, String)> = self.characters.iter().enumerate()
+            .filter(|(idx, _)| !self.pending_reloads.contains(idx))
+            .filter_map(|(idx, slot)| {
+                let mut char = slot.as_ref()?.lock().ok()?;
+                if !(char.texture_evicted && char.is_visible() && !char.culled) {
+                    return None;
+                }
+                char.texture_evicted = false;
+                Some((idx, char.source_path.clone()))
+            })
+            .collect();
+        for (idx, path) in wanted {
+            self.console_logs.push(format!("[性能] 槽位 {} 的纹理已被回收，重新加载", idx));
+            self.pending_reloads.insert(idx);
+            let _ = self.tx.send(AppCommand::RequestLoad { slot_idx: idx, path });
+        }
+    }
+    
+    /// 根据当前音频电平驱动口型同步（阈值式张嘴/闭嘴切换）
+    fn update_lip_sync(&mut self) {
+        let level = self.audio_manager.as_ref().map_or(0.0, |mgr| mgr.output_level());
+        let binding = match &mut self.lip_sync { 
+            Some(b) => b, 
+            None => return 
+        };
+        
+        let should_open = level > LIPSYNC_THRESHOLD;
+        if should_open == binding.is_open { 
+            return;  // 状态未变化，避免每帧重复写入
+        }
+        binding.is_open = should_open;
+        
+        if let Some(Some(char_arc)) = self.characters.get(binding.slot_idx) {
+            if let Ok(mut char) = char_arc.lock() {
+                let _ = match (should_open, &binding.closed_attachment) {
+                    (true, _) => char.set_attachment(&binding.mouth_slot, Some(&binding.open_attachment)),
+                    (false, Some(closed)) => char.set_attachment(&binding.mouth_slot, Some(closed)),
+                    (false, None) => Ok(char.clear_attachment_override(&binding.mouth_slot)),
+                };
+            }
+        }
+    }
+    
+    /// 解除口型同步并恢复嘴部插槽
+    fn clear_lip_sync(&mut self) {
+        if let Some(binding) = self.lip_sync.take() {
+            if let Some(Some(char_arc)) = self.characters.get(binding.slot_idx) {
+                if let Ok(mut char) = char_arc.lock() {
+                    char.clear_attachment_override(&binding.mouth_slot);
+                }
+            }
+        }
+    }
+
+    /// 解析并执行控制台命令
+    /// 支持的命令及格式见 COMMAND_USAGES（输入 help 可在控制台查看）
+    /// 解析失败时输出具体错误与用法提示，而不是静默忽略
+    fn parse_and_send_command(&mut self, input: &str) {
+        self.run_command(input, 0);
+    }
+    
+    /// 执行单条命令；depth 为宏/别名展开层数（用户直接输入为 0）
+    fn run_command(&mut self, input: &str, depth: usize) {
+        let input_trimmed = input.trim();
+        if input_trimmed.is_empty() { return; }
+        
+        // 时间线录制：只记录顶层命令的原始文本（宏在回放时重新展开），录制/回放控制命令本身不记录
+        if let (0, Some(start)) = (depth, self.timeline_start) {
+            let head = input_trimmed.split_whitespace().next().unwrap_or("").to_lowercase();
+            if !matches!(head.as_str(), "timeline" | "play" | "wait") {
+                self.timeline.push((start.elapsed().as_secs_f32(), input_trimmed.to_string()));
+            }
+        }
+        
+        // 记录命令到日志（宏展开出的命令以 >> 标记）
+        self.console_logs.push(format!("{} {}", if depth == 0 { ">" } else { ">>" }, input_trimmed));
+        
+        // 变量插值：宏定义保留 ${...} 原文，待宏执行时再展开
+        let is_macro_def = input_trimmed.split_whitespace().next()
+            .map_or(false, |h| h.eq_ignore_ascii_case("macro"));
+        let interpolated;
+        let input_trimmed = if is_macro_def || !input_trimmed.contains("${") {
+            input_trimmed
+        } else {
+            let (text, undefined) = interpolate_vars(input_trimmed, &self.variables);
+            for name in undefined {
+                self.console_logs.push(format!("[警告] 未定义的变量 ${{{}}}，已替换为空", name));
+            }
+            interpolated = text;
+            interpolated.as_str()
+        };
+        
+        let tokens = tokenize_command(input_trimmed);
+        let args: Vec<&str> = tokens.iter().map(String::as_str).collect();
+        if args.is_empty() { return; }
+        let head = args[0].to_lowercase();
+        // 命令名之后的原始参数串（保留引号，供对话等自行解析）
+        let rest = input_trimmed
+            .split_once(char::is_whitespace)
+            .map_or("", |(_, r)| r.trim());
+        
+        // 别名与宏：展开后逐条执行
+        let alias = self.scenario.aliases.get(&head).cloned();
+        let macro_cmds = self.scenario.macros.get(&head).cloned();
+        if alias.is_some() || macro_cmds.is_some() {
+            if depth >= MAX_MACRO_DEPTH {
+                self.console_logs.push(format!("[错误] {}: 展开层数超过 {}，可能存在循环引用", head, MAX_MACRO_DEPTH));
+                return;
+            }
+            if let Some(target) = alias {
+                let expanded = expand_alias(&target, rest);
+                // 与内置命令同名的别名直接交给该内置命令执行，不再按别名展开
+                if COMMAND_USAGES.iter().any(|(cmd, _, _)| *cmd == head) {
+                    self.console_logs.push(format!(">> {}", expanded));
+                    let tokens = tokenize_command(&expanded);
+                    let args: Vec<&str> = tokens.iter().map(String::as_str).collect();
+                    let rest = expanded.split_once(char::is_whitespace).map_or("", |(_, r)| r.trim());
+                    self.run_builtin(&head, &args, rest);
+                } else {
+                    self.run_command(&expanded, depth + 1);
+                }
+            } else {
+                for cmd in macro_cmds.unwrap_or_default() {
+                    self.run_command(&cmd, depth + 1);
+                }
+            }
+            return;
+        }
+        
+        self.run_builtin(&head, &args, rest);
+    }
+    
+    /// 执行内置命令并记录错误
+    fn run_builtin(&mut self, head: &str, args: &[&str], rest: &str) {
+        if let Err(e) = self.dispatch_command(head, args, rest) {
+            self.console_logs.push(format!("[错误] {}", e));
+        }
+        // 控制台与脚本命令都可撤销：下一个检查点比较前后状态（撤销/重做本身除外）
+        if head != "undo" && head != "redo" {
+            self.undo_dirty = true;
+        }
+    }
+    
+    /// 解析槽位参数并校验范围
+    fn parse_slot(&self, cmd: &str, s: &str) -> Result<usize, String> {
+        let idx = s.parse::<usize>()
+            .map_err(|_| format!("{}: 槽位必须是数字，收到 \"{}\"", cmd.to_uppercase(), s))?;
+        self.check_slot(idx, false).map_err(|e| format!("{}: {}", cmd.to_uppercase(), e))?;
+        Ok(idx)
+    }
+    
+    /// 校验槽位索引（命令解析与异步命令处理共用）；require_character 为 true 时还要求槽位中已有角色
+    fn check_slot(&self, idx: usize, require_character: bool) -> Result<(), String> {
+        match self.characters.get(idx) {
+            None => Err(format!("槽位 {} 超出范围 (0-{})", idx, self.characters.len() - 1)),
+            Some(None) if require_character => Err(format!("槽位 {} 没有角色", idx)),
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// 命令分发
+    /// args 为按引号规则切分后的参数（args[0] 为命令名），rest 为命令名之后的原始参数串
+    /// 未加引号的含空格路径会按单个空格重新拼接，以兼容旧写法
+    fn dispatch_command(&mut self, head: &str, args: &[&str], rest: &str) -> Result<(), String> {
+        let tx = self.tx.clone();
+        
+        match head {
+            "load" => {
+                // 格式: load 0 "path/to/file.atlas" [pma=on|off|auto]（也可为 zip 资源包或 http(s):// URL）
+                // pma 指定按资源路径记住，热重载与撤销重新加载时沿用
+                if args.len() < 3 { return Err(usage_error(head)); }
+                let slot_idx = self.parse_slot(head, args[1])?;
+                let (path_args, pma) = match args.last().and_then(|arg| arg.strip_prefix("pma=")) {
+                    Some(value) => {
+                        let pma = match value.to_lowercase().as_str() {
+                            "on" => Some(true),
+                            "off" => Some(false),
+                            "auto" => None,
+                            _ => return Err(format!("LOAD: pma 的值必须是 on、off 或 auto，收到 \"{}\"", value)),
+                        };
+                        (&args[2..args.len() - 1], Some(pma))
+                    }
+                    None => (&args[2..], None),
+                };
+                if path_args.is_empty() { return Err(usage_error(head)); }
+                let path = path_args.join(" ");
+                if let Some(pma) = pma {
+                    match pma {
+                        Some(on) => self.pma_overrides.insert(path.clone(), on),
+                        None => self.pma_overrides.remove(&path),
+                    };
+                    self.asset_cache.remove(&path);  // 缓存中的纹理按旧设置解码，不能复用
+                }
+                let _ = tx.send(AppCommand::RequestLoad { slot_idx, path });
+            }
+            "loadall" => {
+                // 格式: loadall <清单.json>
+                // 清单: [{"slot": 0, "path": "a.atlas", "pos": "left", "scale": 1.2, "anim": "idle", "looping": true}, ...]
+                if args.len() < 2 { return Err(usage_error(head)); }
+                let manifest = args[1..].join(" ");
+                let text = std::fs::read_to_string(&manifest)
+                    .map_err(|e| format!("LOADALL: 无法读取 {}: {}", manifest, e))?;
+                let entries: Vec<LoadAllEntry> = serde_json::from_str(&text)
+                    .map_err(|e| format!("LOADALL: 清单格式错误: {}", e))?;
+                let mut seen = HashSet::new();
+                for entry in &entries {
+                    self.check_slot(entry.slot, false).map_err(|e| format!("LOADALL: {}", e))?;
+                    if !seen.insert(entry.slot) {
+                        return Err(format!("LOADALL: 槽位 {} 在清单中重复", entry.slot));
+                    }
+                }
+                let base = std::path::Path::new(&manifest).parent().map(std::path::Path::to_path_buf).unwrap_or_default();
+                self.loadall_pending.clear();
+                self.loadall_result = (0, 0);
+                self.console_logs.push(format!("[系统] LOADALL: 开始加载 {} 个槽位", entries.len()));
+                for entry in entries {
+                    let path = if is_url_path(&entry.path) || std::path::Path::new(&entry.path).is_absolute() {
+                        entry.path.clone()
+                    } else {
+                        base.join(&entry.path).display().to_string()
+                    };
+                    let slot_idx = entry.slot;
+                    self.loadall_pending.insert(slot_idx, entry);
+                    let _ = tx.send(AppCommand::RequestLoad { slot_idx, path });
+                }
+            }
+            "anim" => {
+                // 格式: anim 0 idle true  /  anim 0 attack times=3 then=idle
+                if args.len() < 3 { return Err(usage_error(head)); }
+                let idx = self.parse_slot(head, args[1])?;
+                let anim_name = args[2].to_string();
+                let mut loop_anim = true;
+                let mut times: Option<u32> = None;
+                let mut then_anim: Option<String> = None;
+                let mut track = 0usize;
+                let mut alpha: Option<f32> = None;
+                let mut additive: Option<bool> = None;
+                for opt in args.iter().skip(3) {
+                    let opt_lower = opt.to_lowercase();
+                    if let Some(v) = opt_lower.strip_prefix("times=") {
+                        times = Some(v.parse().map_err(|_| format!("ANIM: times 必须是非负整数，收到 \"{}\"", v))?);
+                    } else if opt_lower.starts_with("then=") {
+                        then_anim = Some(opt[5..].to_string());
+                    } else if let Some(v) = opt_lower.strip_prefix("track=") {
+                        track = v.parse().map_err(|_| format!("ANIM: track 必须是非负整数，收到 \"{}\"", v))?;
+                    } else if let Some(v) = opt_lower.strip_prefix("alpha=") {
+                        alpha = Some(v.parse().map_err(|_| format!("ANIM: alpha 必须是数字，收到 \"{}\"", v))?);
+                    } else if let Some(v) = opt_lower.strip_prefix("blend=") {
+                        additive = Some(v == "add");
+                    } else if opt_lower == "true" || opt_lower == "false" {
+                        loop_anim = opt_lower == "true";
+                    } else {
+                        return Err(format!("ANIM: 无法识别的参数 \"{}\"；{}", opt, usage_error(head)));
+                    }
+                }
+                
+                // 先下发轨道混合参数，保证新条目创建后即套用
+                if alpha.is_some() || additive.is_some() {
+                    let mix = TrackMix {
+                        alpha: alpha.unwrap_or(1.0),
+                        additive: additive.unwrap_or(false),
+                    };
+                    let _ = tx.send(AppCommand::SetTrackMix { slot_idx: idx, track, mix });
+                }
+                
+                match times {
+                    // times=0 视为无限循环
+                    Some(0) => { 
+                        let _ = tx.send(AppCommand::SetAnimation { slot_idx: idx, track, anim_name, loop_anim: true }); 
+                    }
+                    Some(n) => { 
+                        let _ = tx.send(AppCommand::PlayAnimationTimes { slot_idx: idx, track, anim_name, times: n, then_anim }); 
+                    }
+                    None => { 
+                        let _ = tx.send(AppCommand::SetAnimation { slot_idx: idx, track, anim_name, loop_anim }); 
+                    }
+                }
+            }
+            "cleartrack" => {
+                // 格式: cleartrack 0 1
+                if args.len() != 3 { return Err(usage_error(head)); }
+                let slot_idx = self.parse_slot(head, args[1])?;
+                let track = args[2].parse::<usize>()
+                    .map_err(|_| format!("CLEARTRACK: 轨道必须是非负整数，收到 \"{}\"", args[2]))?;
+                let _ = tx.send(AppCommand::ClearTrack { slot_idx, track });
+            }
+            "cls" | "clear" => {
+                // 格式: cls  /  clear log
+                if head == "clear" && (args.len() != 2 || !args[1].eq_ignore_ascii_case("log")) {
+                    return Err(usage_error(head));
+                }
+                if head == "cls" && args.len() != 1 { return Err(usage_error(head)); }
+                self.console_logs.clear();
+                self.log_times.clear();
+            }
+            "logsave" => {
+                // 格式: logsave <路径>
+                if args.len() < 2 { return Err(usage_error(head)); }
+                let path = args[1..].join(" ");
+                std::fs::write(&path, self.log_text() + "\n")
+                    .map_err(|e| format!("LOGSAVE: 无法写入 {}: {}", path, e))?;
+                self.console_logs.push(format!("[系统] 日志已保存至: {}", path));
+            }
+            "loglevel" => {
+                // 格式: loglevel verbose|info|warn|error
+                if args.len() != 2 { return Err(usage_error(head)); }
+                self.log_level = LogLevel::parse(args[1])
+                    .ok_or_else(|| format!("LOGLEVEL: 未知级别 {}（可用: verbose info warn error）", args[1]))?;
+            }
+            "loglimit" => {
+                // 格式: loglimit <条数>
+                if args.len() != 2 { return Err(usage_error(head)); }
+                self.log_limit = args[1].parse::<usize>().ok().filter(|n| *n > 0)
+                    .ok_or_else(|| format!("LOGLIMIT: 条数必须是正整数，收到 \"{}\"", args[1]))?;
+            }
+            "sched" => {
+                // 格式: sched  /  sched nice on|off （切换时重建线程池）  /  sched auto on|off  /  sched cores <N|auto>
+                //       sched reserve fixed <N> | fraction <F> | explicit <N> （重建线程池）
+                match args.len() {
+                    1 => self.console_logs.push(format!(
+                        "[状态] 调度器: {}, 当前并发 {}{}",
+                        self.scheduler.describe(),
+                        self.effective_concurrency(),
+                        match (self.sched_manual, self.sched_auto) {
+                            (Some(_), _) => " (手动)",
+                            (None, true) => " (自适应)",
+                            (None, false) => "",
+                        },
+                    )),
+                    3 if args[1].eq_ignore_ascii_case("auto") => {
+                        self.sched_auto = match args[2].to_lowercase().as_str() {
+                            "on" => true,
+                            "off" => false,
+                            _ => return Err(usage_error(head)),
+                        };
+                        self.sched_limit = self.scheduler.worker_count;
+                        self.sched_adapt_timer = 0.0;
+                    }
+                    3 if args[1].eq_ignore_ascii_case("cores") => {
+                        self.sched_manual = if args[2].eq_ignore_ascii_case("auto") {
+                            None
+                        } else {
+                            Some(args[2].parse::<usize>().ok().filter(|n| *n > 0)
+                                .ok_or_else(|| format!("SCHED: 并发数必须是正整数，收到 \"{}\"", args[2]))?)
+                        };
+                    }
+                    3 if args[1].eq_ignore_ascii_case("nice") => {
+                        let lower = match args[2].to_lowercase().as_str() {
+                            "on" => true,
+                            "off" => false,
+                            _ => return Err(usage_error(head)),
+                        };
+                        let (scheduler, log) = AefrScheduler::new_or_inline(lower, self.scheduler.policy);
+                        self.scheduler = scheduler;
+                        self.console_logs.push(log);
+                    }
+                    4 if args[1].eq_ignore_ascii_case("reserve") => {
+                        let policy = ReservePolicy::parse(args[2], args[3])
+                            .ok_or_else(|| format!("SCHED: 无效的保留策略 \"{} {}\"", args[2], args[3]))?;
+                        let (scheduler, log) = AefrScheduler::new_or_inline(self.scheduler.lower_priority, policy);
+                        self.scheduler = scheduler;
+                        self.sched_limit = self.scheduler.worker_count;
+                        self.console_logs.push(log);
+                    }
+                    _ => return Err(usage_error(head)),
+                }
+            }
+            "texbudget" => {
+                // 格式: texbudget  /  texbudget <MB>  /  texbudget off
+                match args.len() {
+                    1 => {
+                        let usage = self.texture_usage(None) as f64 / 1048576.0;
+                        match self.texture_budget {
+                            Some(budget) => self.console_logs.push(format!(
+                                "[状态] 角色纹理显存: {:.1} / {:.1} MB", usage, budget as f64 / 1048576.0,
+                            )),
+                            None => self.console_logs.push(format!("[状态] 角色纹理显存: {:.1} MB（不限制）", usage)),
+                        }
+                    }
+                    2 if args[1].eq_ignore_ascii_case("off") => {
+                        self.texture_budget = None;
+                        self.console_logs.push("[系统] 已取消显存预算".into());
+                    }
+                    2 => {
+                        let mb = args[1].parse::<usize>().ok().filter(|n| *n > 0)
+                            .ok_or_else(|| format!("TEXBUDGET: 预算必须是正整数（MB），收到 \"{}\"", args[1]))?;
+                        self.texture_budget = Some(mb * 1024 * 1024);
+                        self.console_logs.push(format!("[系统] 显存预算: {} MB（超出时回收最久未显示角色的纹理）", mb));
+                    }
+                    _ => return Err(usage_error(head)),
+                }
+            }
+            "retry" => {
+                // 格式: retry  /  retry <次数>
+                match args.len() {
+                    1 => {}
+                    2 => {
+                        self.load_retries = args[1].parse::<u32>()
+                            .map_err(|_| format!("RETRY: 次数必须是非负整数，收到 \"{}\"", args[1]))?;
+                    }
+                    _ => return Err(usage_error(head)),
+                }
+                self.console_logs.push(format!(
+                    "[状态] 加载重试: {} 次（首次间隔 {}ms，逐次翻倍）", self.load_retries, LOAD_RETRY_BASE_MS,
+                ));
+            }
+            "undo" | "redo" => {
+                // 格式: undo  /  redo
+                if args.len() != 1 { return Err(usage_error(head)); }
+                self.undo_dirty = true;
+                self.undo_checkpoint(false);
+                self.undo_redo(head == "undo");
+            }
+            "grid" => {
+                // 格式: grid <间距>  /  grid off
+                if args.len() != 2 { return Err(usage_error(head)); }
+                self.grid_size = if args[1].eq_ignore_ascii_case("off") {
+                    None
+                } else {
+                    Some(args[1].parse::<f32>().ok().filter(|v| *v >= 2.0)
+                        .ok_or_else(|| format!("GRID: 间距必须是不小于 2 的数字，收到 \"{}\"", args[1]))?)
+                };
+            }
+            "gizmo" => {
+                // 格式: gizmo on|off
+                if args.len() != 2 { return Err(usage_error(head)); }
+                self.show_gizmo = match args[1].to_lowercase().as_str() {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err(usage_error(head)),
+                };
+            }
+            "console" => {
+                // 格式: console dock|float
+                if args.len() != 2 { return Err(usage_error(head)); }
+                self.console_docked = match args[1].to_lowercase().as_str() {
+                    "dock" => true,
+                    "float" => false,
+                    _ => return Err(usage_error(head)),
+                };
+                self.console_logs.push(format!(
+                    "[系统] 控制台: {}（config save 后保留）", if self.console_docked { "停靠底部" } else { "浮动窗口" },
+                ));
+            }
+            "demo" => {
+                // 格式: demo on|off
+                if args.len() != 2 { return Err(usage_error(head)); }
+                self.demo_mode = match args[1].to_lowercase().as_str() {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err(usage_error(head)),
+                };
+            }
+            "focus" => {
+                // 格式: focus <槽位>  /  focus off
+                if args.len() != 2 { return Err(usage_error(head)); }
+                self.focus_slot = if args[1].eq_ignore_ascii_case("off") {
+                    None
+                } else {
+                    Some(self.parse_slot(head, args[1])?)
+                };
+            }
+            "tag" => {
+                // 格式: tag <槽位> <文字>  /  tag <槽位> off  /  tag on|off （总开关）
+                match args.len() {
+                    2 => {
+                        self.show_name_tags = match args[1].to_lowercase().as_str() {
+                            "on" => true,
+                            "off" => false,
+                            _ => return Err(usage_error(head)),
+                        };
+                    }
+                    n if n >= 3 => {
+                        let slot_idx = self.parse_slot(head, args[1])?;
+                        if n == 3 && args[2].eq_ignore_ascii_case("off") {
+                            self.name_tags.remove(&slot_idx);
+                        } else {
+                            self.name_tags.insert(slot_idx, args[2..].join(" "));
+                        }
+                    }
+                    _ => return Err(usage_error(head)),
+                }
+            }
+            "skip" => {
+                // 格式: skip  （切换）/  skip on|off
+                self.skip_toggle = match args.get(1).map(|a| a.to_lowercase()) {
+                    None => !self.skip_toggle,
+                    Some(a) if a == "on" && args.len() == 2 => true,
+                    Some(a) if a == "off" && args.len() == 2 => false,
+                    _ => return Err(usage_error(head)),
+                };
+                self.console_logs.push(format!("[系统] 快进已{}", if self.skip_toggle { "开启" } else { "关闭" }));
+            }
+            "freeze" | "unfreeze" => {
+                // 格式: freeze  /  unfreeze
+                if args.len() != 1 { return Err(usage_error(head)); }
+                self.set_frozen(head == "freeze");
+            }
+            "pingpong" => {
+                // 格式: pingpong <槽位> <动画A> <动画B>  /  pingpong <槽位> off
+                let slot_idx = match args.len() {
+                    3 | 4 => self.parse_slot(head, args[1])?,
+                    _ => return Err(usage_error(head)),
+                };
+                let pair = match args.len() {
+                    3 if args[2].eq_ignore_ascii_case("off") => None,
+                    4 => Some((args[2].to_string(), args[3].to_string())),
+                    _ => return Err(usage_error(head)),
+                };
+                let _ = tx.send(AppCommand::SetPingPong { slot_idx, pair });
+            }
+            "pacing" => {
+                // 格式: pacing  /  pacing cjk=1 latin=0.6 short=3 long=8  /  pacing reset
+                match args.get(1) {
+                    None => {}
+                    Some(arg) if args.len() == 2 && arg.eq_ignore_ascii_case("reset") => self.type_pacing = TypePacing::default(),
+                    Some(_) => {
+                        // 先在副本上校验全部参数，任一无效则整体不生效
+                        let mut pacing = self.type_pacing;
+                        for opt in &args[1..] {
+                            let (key, value) = opt.split_once('=').ok_or_else(|| usage_error(head))?;
+                            let value = value.parse::<f32>().map_err(|_| format!("PACING: {} 必须是数字，收到 \"{}\"", key, value))?;
+                            pacing.set(&key.to_lowercase(), value)?;
+                        }
+                        self.type_pacing = pacing;
+                    }
+                }
+                let p = self.type_pacing;
+                self.console_logs.push(format!(
+                    "[状态] 打字节奏: 基准 {:.0}ms, 全角 ×{} 其他 ×{}, 逗号后 +{} 句末后 +{}",
+                    self.type_interval * 1000.0, p.cjk, p.latin, p.pause_short, p.pause_long,
+                ));
+            }
+            "config" => {
+                // 格式: config save [路径]  /  config load [路径]   （默认 aefr.toml）
+                if args.len() != 2 && args.len() != 3 { return Err(usage_error(head)); }
+                let path = args.get(2).copied().unwrap_or(AppConfig::FILE);
+                match args[1].to_lowercase().as_str() {
+                    "save" => {
+                        self.current_config().save(path).map_err(|e| format!("CONFIG: {}", e))?;
+                        self.console_logs.push(format!("[系统] 配置已保存到 {}", path));
+                    }
+                    "load" => {
+                        let (config, unknown) = AppConfig::load(path).map_err(|e| format!("CONFIG: {}", e))?;
+                        for key in unknown {
+                            self.console_logs.push(format!("[警告] {}: 未知配置项 {}，已忽略", path, key));
+                        }
+                        let _ = tx.send(AppCommand::ApplyConfig(Box::new(config)));
+                    }
+                    _ => return Err(usage_error(head)),
+                }
+            }
+            "hide" | "show" => {
+                // 格式: hide <槽位> [pause]  /  show <槽位>   （隐藏不卸载；pause 同时暂停动画，show 时恢复）
+                let visible = head == "show";
+                let pause = match (args.len(), args.get(2)) {
+                    (2, _) if visible => Some(false),
+                    (2, _) => None,
+                    (3, Some(flag)) if !visible && flag.eq_ignore_ascii_case("pause") => Some(true),
+                    _ => return Err(usage_error(head)),
+                };
+                let slot_idx = self.parse_slot(head, args[1])?;
+                let _ = tx.send(AppCommand::SetVisible { slot_idx, visible, pause });
+            }
+            "reset" => {
+                // 格式: reset <槽位> [stop]   （默认重新播放默认动画，stop 则停在初始姿势）
+                if args.len() != 2 && args.len() != 3 { return Err(usage_error(head)); }
+                let slot_idx = self.parse_slot(head, args[1])?;
+                let restart = match args.get(2) {
+                    None => true,
+                    Some(flag) if flag.eq_ignore_ascii_case("stop") => false,
+                    Some(_) => return Err(usage_error(head)),
+                };
+                let _ = tx.send(AppCommand::ResetToSetup { slot_idx, restart });
+            }
+            "bgm" => {
+                // 格式: bgm "path/to/bgm.mp3"
+                if args.len() < 2 { return Err(usage_error(head)); }
+                let _ = tx.send(AppCommand::PlayBgm(args[1..].join(" ")));
+            }
+            "se" => {
+                // 格式: se "path/to/se.wav" [slot=0]
+                if args.len() < 2 { return Err(usage_error(head)); }
+                let mut path_args = &args[1..];
+                let mut slot_idx = None;
+                if let Some(last) = path_args.last() {
+                    if last.to_lowercase().starts_with("slot=") && path_args.len() > 1 {
+                        slot_idx = Some(self.parse_slot(head, &last[5..])?);
+                        path_args = &path_args[..path_args.len() - 1];
+                    }
+                }
+                let path = path_args.join(" ");
+                let _ = match slot_idx {
+                    Some(slot_idx) => tx.send(AppCommand::PlaySeAt { path, slot_idx }),
+                    None => tx.send(AppCommand::PlaySe(path)),
+                };
+            }
+            "stop" => {
+                // 格式: stop
+                let _ = tx.send(AppCommand::StopBgm);
+            }
+            "pausebgm" => {
+                // 格式: pausebgm
+                let _ = tx.send(AppCommand::PauseBgm);
+            }
+            "resumebgm" => {
+                // 格式: resumebgm
+                let _ = tx.send(AppCommand::ResumeBgm);
+            }
+            "talk" => {
+                // 格式: talk 名称|所属|内容[|color=#ffcc00][|pos=center]   （引号内的 | 不作为分隔符）
+                let p = split_unquoted(rest, '|');
+                if p.len() < 3 { return Err(usage_error(head)); }
+                let mut plate = NamePlate::default();
+                let mut voice = None;
+                for field in &p[3..] {
+                    match field.trim().strip_prefix("voice=") {
+                        Some(path) => voice = Some(path.trim().to_string()),
+                        None => plate.apply_field(field)?,
+                    }
+                }
+                let mut fields = p.into_iter();
+                let _ = tx.send(AppCommand::Dialogue { 
+                    name: fields.next().unwrap_or_default(), 
+                    affiliation: fields.next().unwrap_or_default(), 
+                    content: fields.next().unwrap_or_default(), 
+                    plate,
+                    voice,
+                });
+            }
+            "bg" => {
+                // 格式: bg "path/to/bg.png"  /  bg anim <帧目录> [fps=12]
+                if args.len() < 2 { return Err(usage_error(head)); }
+                if args[1].eq_ignore_ascii_case("anim") {
+                    let mut parts = &args[2..];
+                    let mut fps = BG_ANIM_DEFAULT_FPS;
+                    if let Some(last) = parts.last() {
+                        if let Some(v) = last.strip_prefix("fps=") {
+                            fps = v.parse::<f32>().ok().filter(|f| *f > 0.0)
+                                .ok_or_else(|| format!("BG: fps 必须是正数，收到 \"{}\"", v))?;
+                            parts = &parts[..parts.len() - 1];
+                        }
+                    }
+                    if parts.is_empty() { return Err(usage_error(head)); }
+                    let _ = tx.send(AppCommand::LoadAnimatedBackground { dir: parts.join(" "), fps });
+                } else if args[1].eq_ignore_ascii_case("layer") {
+                    // 格式: bg layer <路径>  /  bg layer clear
+                    match args.len() {
+                        2 => return Err(usage_error(head)),
+                        3 if args[2].eq_ignore_ascii_case("clear") => { let _ = tx.send(AppCommand::ClearBackgroundLayers); }
+                        _ => { let _ = tx.send(AppCommand::AddBackgroundLayer(args[2..].join(" "))); }
+                    }
+                } else {
+                    let _ = tx.send(AppCommand::LoadBackground(args[1..].join(" ")));
+                }
+            }
+            "bone" => {
+                // 格式: bone 0 [head]  /  bone 0 head rot 15
+                match args.len() {
+                    2 | 3 => {
+                        let idx = self.parse_slot(head, args[1])?;
+                        self.log_bone_info(idx, args.get(2).copied());
+                    }
+                    5 if args[3].eq_ignore_ascii_case("rot") => {
+                        let slot_idx = self.parse_slot(head, args[1])?;
+                        let degrees = args[4].parse::<f32>()
+                            .map_err(|_| format!("BONE: 角度必须是数字，收到 \"{}\"", args[4]))?;
+                        let _ = tx.send(AppCommand::SetBoneRotation { 
+                            slot_idx, 
+                            bone_name: args[2].to_string(), 
+                            degrees 
+                        });
+                    }
+                    _ => return Err(usage_error(head)),
+                }
+            }
+            "face" => {
+                // 格式: face 0 Mouth smile   (附件名为 none 或省略时隐藏插槽)
+                if args.len() != 3 && args.len() != 4 { return Err(usage_error(head)); }
+                let slot_idx = self.parse_slot(head, args[1])?;
+                let attachment_name = args.get(3)
+                    .filter(|a| !a.eq_ignore_ascii_case("none"))
+                    .map(|a| a.to_string());
+                let _ = tx.send(AppCommand::SetAttachment { 
+                    slot_idx, 
+                    slot_name: args[2].to_string(), 
+                    attachment_name 
+                });
+            }
+            "mix" => {
+                // 格式: mix 0  /  mix 0 attack idle 0.1
+                if args.len() != 2 && args.len() != 5 { return Err(usage_error(head)); }
+                let slot_idx = self.parse_slot(head, args[1])?;
+                if args.len() == 5 {
+                    let seconds = args[4].parse::<f32>().ok().filter(|s| *s >= 0.0)
+                        .ok_or_else(|| format!("MIX: 秒数必须是非负数，收到 \"{}\"", args[4]))?;
+                    let _ = tx.send(AppCommand::SetMix { slot_idx, from: args[2].to_string(), to: args[3].to_string(), seconds });
+                    return Ok(());
+                }
+                let rules = match self.characters.get(slot_idx) {
+                    Some(Some(char_arc)) => char_arc.lock()
+                        .map_err(|_| format!("MIX: 槽位 {} 状态锁定失败", slot_idx))?
+                        .mix_rules.clone(),
+                    _ => return Err(format!("MIX: 槽位 {} 没有角色", slot_idx)),
+                };
+                if rules.is_empty() {
+                    self.console_logs.push(format!("[列表] 槽位 {} 没有设置动画过渡", slot_idx));
+                }
+                for (from, to, seconds) in rules {
+                    self.console_logs.push(format!("[列表] 槽位 {}: {} -> {} {:.2}s", slot_idx, from, to, seconds));
+                }
+            }
+            "slot" => {
+                // 格式: slot 0  /  slot 0 blush hide  /  slot 0 blush show
+                if args.len() != 2 && args.len() != 4 { return Err(usage_error(head)); }
+                let slot_idx = self.parse_slot(head, args[1])?;
+                match args.len() {
+                    2 => {
+                        let names = match self.characters.get(slot_idx) {
+                            Some(Some(char_arc)) => char_arc.lock()
+                                .map_err(|_| format!("SLOT: 槽位 {} 状态锁定失败", slot_idx))?
+                                .hidden_slot_names(),
+                            _ => return Err(format!("SLOT: 槽位 {} 没有角色", slot_idx)),
+                        };
+                        if names.is_empty() {
+                            self.console_logs.push(format!("[列表] 槽位 {} 没有隐藏的插槽", slot_idx));
+                        } else {
+                            self.console_logs.push(format!("[列表] 槽位 {} 已隐藏插槽: {}", slot_idx, names.join(", ")));
+                        }
+                    }
+                    4 => {
+                        let hidden = match args[3].to_lowercase().as_str() {
+                            "hide" => true,
+                            "show" => false,
+                            _ => return Err(usage_error(head)),
+                        };
+                        let _ = tx.send(AppCommand::SetSlotHidden { slot_idx, slot_name: args[2].to_string(), hidden });
+                    }
+                    _ => return Err(usage_error(head)),
+                }
+            }
+            "lipsync" => {
+                // 格式: lipsync 0 Mouth mouth_open [mouth_close]  或  lipsync off
+                if args.len() == 2 && args[1].eq_ignore_ascii_case("off") {
+                    let _ = tx.send(AppCommand::SetLipSync(None));
+                } else if args.len() == 4 || args.len() == 5 {
+                    let idx = self.parse_slot(head, args[1])?;
+                    let _ = tx.send(AppCommand::SetLipSync(Some((
+                        idx,
+                        args[2].to_string(),
+                        args[3].to_string(),
+                        args.get(4).map(|s| s.to_string()),
+                    ))));
+                } else {
+                    return Err(usage_error(head));
+                }
+            }
+            "watch" => {
+                // 格式: watch 0 on|off
+                if args.len() != 3 { return Err(usage_error(head)); }
+                let idx = self.parse_slot(head, args[1])?;
+                match args[2].to_lowercase().as_str() {
+                    "on" => self.set_watch(idx, true)?,
+                    "off" => self.set_watch(idx, false)?,
+                    _ => return Err(usage_error(head)),
+                }
+            }
+            "export" => {
+                // 格式: export <目录> [copy]   （copy：复制资源并改写为相对路径）
+                if args.len() < 2 { return Err(usage_error(head)); }
+                let copy_assets = args.len() > 2 && args[args.len() - 1].eq_ignore_ascii_case("copy");
+                let dir_args = if copy_assets { &args[1..args.len() - 1] } else { &args[1..] };
+                let dir = PathBuf::from(dir_args.join(" "));
+                let scenario = self.scenario.clone();
+                let tx_cb = tx.clone();
+                
+                // 复制资源可能较慢，放到工作线程执行
+                thread::spawn(move || {
+                    let msg = match export_scenario(scenario, dir.clone(), copy_assets) {
+                        Ok((count, missing)) => {
+                            for m in &missing {
+                                let _ = tx_cb.send(AppCommand::Log(format!("[警告] 资源复制失败: {}", m)));
+                            }
+                            format!("[系统] 已导出至 {}（资源 {} 项，失败 {} 项）", dir.display(), count, missing.len())
+                        }
+                        Err(e) => format!("[错误] EXPORT: {}", e),
+                    };
+                    let _ = tx_cb.send(AppCommand::Log(msg));
+                });
+            }
+            "record" => {
+                // 格式: record <槽位> <目录> [fps=30] [duration=3]
+                if args.len() < 3 { return Err(usage_error(head)); }
+                let slot_idx = self.parse_slot(head, args[1])?;
+                let (mut fps, mut duration) = (30.0f32, 3.0f32);
+                let mut dir_parts = Vec::new();
+                for arg in &args[2..] {
+                    if let Some(v) = arg.strip_prefix("fps=") {
+                        fps = v.parse::<f32>().ok().filter(|f| *f > 0.0 && *f <= 120.0)
+                            .ok_or_else(|| format!("RECORD: fps 必须是 0~120 的数字，收到 \"{}\"", v))?;
+                    } else if let Some(v) = arg.strip_prefix("duration=") {
+                        duration = v.parse::<f32>().ok().filter(|d| *d > 0.0 && *d <= 60.0)
+                            .ok_or_else(|| format!("RECORD: duration 必须是 0~60 秒，收到 \"{}\"", v))?;
+                    } else {
+                        dir_parts.push(*arg);
+                    }
+                }
+                if dir_parts.is_empty() { return Err(usage_error(head)); }
+                
+                let job = match self.characters.get(slot_idx) {
+                    Some(Some(char_arc)) => {
+                        let char = char_arc.lock().map_err(|_| format!("RECORD: 槽位 {} 状态锁定失败", slot_idx))?;
+                        RecordJob {
+                            atlas_path: char.source_path.clone(),
+                            animation: char.current_animation_name(),
+                            looping: char.current_looping(),
+                            coord: char.coord,
+                            pma: self.pma_overrides.get(&char.source_path).copied(),
+                            dir: PathBuf::from(dir_parts.join(" ")),
+                            fps,
+                            duration,
+                        }
+                    }
+                    _ => return Err(format!("RECORD: 槽位 {} 没有角色", slot_idx)),
+                };
+                
+                let tx_cb = tx.clone();
+                self.console_logs.push(format!("[系统] 开始录制槽位 {}: {} 帧", slot_idx, (fps * duration).round().max(1.0)));
+                thread::spawn(move || {
+                    let dir = job.dir.clone();
+                    let msg = match record_animation(job, &tx_cb) {
+                        Ok((frames, [w, h])) => format!("[系统] 录制完成: {} 帧 ({}x{}) → {}", frames, w, h, dir.display()),
+                        Err(e) => format!("[错误] RECORD: {}", e),
+                    };
+                    let _ = tx_cb.send(AppCommand::Log(msg));
+                });
+            }
+            "typesfx" => {
+                // 格式: typesfx <路径>  /  typesfx off
+                if args.len() < 2 { return Err(usage_error(head)); }
+                if args.len() == 2 && args[1].eq_ignore_ascii_case("off") {
+                    self.type_sfx = None;
+                    return Ok(());
+                }
+                let path = args[1..].join(" ");
+                thread::spawn(move || {
+                    let _ = tx.send(match std::fs::read(&path) {
+                        Ok(d) => AppCommand::TypeSfxReady(d),
+                        Err(e) => AppCommand::Log(format!("[错误] 打字音效读取失败 {}: {}", path, e)),
+                    });
+                });
+            }
+            "crossfade" => {
+                // 格式: crossfade <秒>   （冻结当前角色层，随后的阵容变化以溶解方式过渡）
+                if args.len() != 2 { return Err(usage_error(head)); }
+                let duration = args[1].parse::<f32>().ok().filter(|d| *d > 0.0)
+                    .ok_or_else(|| format!("CROSSFADE: 时长必须是正秒数，收到 \"{}\"", args[1]))?;
+                let (shapes, _) = build_stage_shapes(&self.characters, false, self.focus_slot);
+                self.stage_snapshot = Some(StageSnapshot {
+                    shapes,
+                    _keep_alive: self.characters.iter().flatten().cloned().collect(),
+                    duration,
+                    elapsed: 0.0,
+                });
+            }
+            "seed" => {
+                // 格式: seed  /  seed <非负整数>
+                match args.len() {
+                    1 => self.console_logs.push(format!("[状态] 随机种子: {}", self.rng_seed)),
+                    2 => self.rng_seed = args[1].parse::<u64>()
+                        .map_err(|_| format!("SEED: 种子必须是非负整数，收到 \"{}\"", args[1]))?,
+                    _ => return Err(usage_error(head)),
+                }
+            }
+            "shake" => {
+                // 格式: shake <强度像素> <秒>  /  shake off
+                match args.len() {
+                    2 if args[1].eq_ignore_ascii_case("off") => self.shake = None,
+                    3 => {
+                        let strength = args[1].parse::<f32>().ok().filter(|v| *v >= 0.0)
+                            .ok_or_else(|| format!("SHAKE: 强度必须是非负数，收到 \"{}\"", args[1]))?;
+                        let duration = args[2].parse::<f32>().ok().filter(|v| *v > 0.0)
+                            .ok_or_else(|| format!("SHAKE: 时长必须是正秒数，收到 \"{}\"", args[2]))?;
+                        self.shake = Some(Shake { strength, duration, elapsed: 0.0 });
+                    }
+                    _ => return Err(usage_error(head)),
+                }
+            }
+            "letterbox" => {
+                // 格式: letterbox on [高度比例] [above]  /  letterbox off
+                if args.len() < 2 || args.len() > 4 { return Err(usage_error(head)); }
+                let target = match args[1].to_lowercase().as_str() {
+                    "on" => {
+                        let mut height = LETTERBOX_DEFAULT_HEIGHT;
+                        self.letterbox_above = false;
+                        for arg in &args[2..] {
+                            if arg.eq_ignore_ascii_case("above") {
+                                self.letterbox_above = true;
+                            } else {
+                                height = arg.parse::<f32>().ok().filter(|h| *h > 0.0 && *h <= 0.5)
+                                    .ok_or_else(|| format!("LETTERBOX: 高度必须是 0~0.5 的屏幕比例，收到 \"{}\"", arg))?;
+                            }
+                        }
+                        height
+                    }
+                    "off" => 0.0,
+                    _ => return Err(usage_error(head)),
+                };
+                // 从当前高度出发，中途切换不会跳变
+                self.letterbox = (self.letterbox_height(), target, 0.0);
+            }
+            "vignette" => {
+                // 格式: vignette <强度 0~1>   （0 关闭）
+                if args.len() != 2 { return Err(usage_error(head)); }
+                self.vignette_strength = args[1].parse::<f32>()
+                    .map_err(|_| format!("VIGNETTE: 强度必须是 0~1 的数字，收到 \"{}\"", args[1]))?
+                    .clamp(0.0, 1.0);
+            }
+            "grade" => {
+                // 格式: grade #RRGGBBAA  /  grade off
+                if args.len() != 2 { return Err(usage_error(head)); }
+                self.grade_tint = if args[1].eq_ignore_ascii_case("off") {
+                    None
+                } else {
+                    Some(parse_hex_color(args[1])
+                        .ok_or_else(|| format!("GRADE: 颜色格式应为 #RRGGBB 或 #RRGGBBAA，收到 \"{}\"", args[1]))?)
+                };
+            }
+            "title" | "resize" | "fullscreen" => {
+                // 格式: title <文本>  /  resize <宽> <高>  /  fullscreen on|off
+                // Android 窗口由系统管理，这些命令只记录日志
+                let cmd = match head {
+                    "title" if args.len() >= 2 => egui::ViewportCommand::Title(args[1..].join(" ")),
+                    "resize" if args.len() == 3 => {
+                        let parse = |s: &str| s.parse::<f32>().ok().filter(|v| *v >= 1.0)
+                            .ok_or_else(|| format!("RESIZE: 尺寸必须是正数，收到 \"{}\"", s));
+                        egui::ViewportCommand::InnerSize(Vec2::new(parse(args[1])?, parse(args[2])?))
+                    }
+                    "fullscreen" if args.len() == 2 => match args[1].to_lowercase().as_str() {
+                        "on" => egui::ViewportCommand::Fullscreen(true),
+                        "off" => egui::ViewportCommand::Fullscreen(false),
+                        _ => return Err(usage_error(head)),
+                    },
+                    _ => return Err(usage_error(head)),
+                };
+                if cfg!(target_os = "android") {
+                    self.console_logs.push(format!("[警告] {}: Android 窗口尺寸与标题由系统管理，已忽略", head.to_uppercase()));
+                } else {
+                    let _ = tx.send(AppCommand::Viewport(cmd));
+                }
+            }
+            "unfocused" => {
+                // 格式: unfocused  /  unfocused <帧率>
+                match args.len() {
+                    1 => {}
+                    2 => {
+                        self.unfocused_fps = args[1].parse::<u32>()
+                            .map_err(|_| format!("UNFOCUSED: 帧率必须是非负整数，收到 \"{}\"", args[1]))?;
+                    }
+                    _ => return Err(usage_error(head)),
+                }
+                self.console_logs.push(match self.unfocused_fps {
+                    0 => "[状态] 失去焦点时帧率: 不限制".into(),
+                    fps => format!("[状态] 失去焦点时帧率上限: {} FPS", fps),
+                });
+            }
+            "renderscale" => {
+                // 格式: renderscale  /  renderscale <倍数>
+                match args.len() {
+                    1 => self.console_logs.push(format!("[状态] 渲染缩放: ×{}", self.render_scale)),
+                    2 => {
+                        let factor = args[1].parse::<f32>().ok()
+                            .filter(|f| (RENDER_SCALE_MIN..=RENDER_SCALE_MAX).contains(f))
+                            .ok_or_else(|| format!(
+                                "RENDERSCALE: 倍数应在 {}~{} 之间，收到 \"{}\"", RENDER_SCALE_MIN, RENDER_SCALE_MAX, args[1],
+                            ))?;
+                        let _ = tx.send(AppCommand::SetRenderScale(factor));
+                    }
+                    _ => return Err(usage_error(head)),
+                }
+            }
+            "ruler" => {
+                // 格式: ruler on [间距像素]  /  ruler off
+                self.ruler_step = match (args.len(), args.get(1).map(|a| a.to_lowercase())) {
+                    (2, Some(a)) if a == "off" => None,
+                    (2, Some(a)) if a == "on" => Some(100.0),
+                    (3, Some(a)) if a == "on" => Some(args[2].parse::<f32>().ok().filter(|v| *v >= 10.0)
+                        .ok_or_else(|| format!("RULER: 间距必须 ≥ 10 像素，收到 \"{}\"", args[2]))?),
+                    _ => return Err(usage_error(head)),
+                };
+            }
+            "formation" => {
+                // 格式: formation  /  formation line|arc|v|staggered
+                match args.len() {
+                    1 => self.console_logs.push(format!("[状态] 当前阵型: {}", self.formation.name())),
+                    2 => self.formation = Formation::parse(args[1])
+                        .ok_or_else(|| format!("FORMATION: 未知阵型 {}（可用: line arc v staggered）", args[1]))?,
+                    _ => return Err(usage_error(head)),
+                }
+            }
+            "stage" => {
+                // 格式: stage #RRGGBB  /  stage transparent  /  stage reset
+                if args.len() != 2 { return Err(usage_error(head)); }
+                self.stage_color = match args[1].to_lowercase().as_str() {
+                    "transparent" => {
+                        if !self.transparent_window {
+                            self.console_logs.push(format!(
+                                "[警告] 窗口未启用透明，透明舞台将显示窗口默认底色；要透出桌面请在 {} 中设置 transparent_window = true 后重启",
+                                AppConfig::FILE,
+                            ));
+                        }
+                        None
+                    }
+                    "reset" => Some(STAGE_DEFAULT_COLOR),
+                    _ => Some(parse_hex_color(args[1])
+                        .ok_or_else(|| format!("STAGE: 颜色格式应为 #RRGGBB，收到 \"{}\"", args[1]))?),
+                };
+            }
+            "bgfit" => {
+                // 格式: bgfit stretch|cover|contain|tile
+                if args.len() != 2 { return Err(usage_error(head)); }
+                self.bg_fit = match args[1].to_lowercase().as_str() {
+                    "stretch" => BgFit::Stretch,
+                    "cover" => BgFit::Cover,
+                    "contain" => BgFit::Contain,
+                    "tile" => BgFit::Tile,
+                    _ => return Err(usage_error(head)),
+                };
+            }
+            "filter" => {
+                // 格式: filter nearest|linear
+                // 注：egui 0.27 的 TextureOptions 不支持 mipmap，仅能在最近邻与线性采样间切换
+                if args.len() != 2 { return Err(usage_error(head)); }
+                self.texture_options = match args[1].to_lowercase().as_str() {
+                    "nearest" => egui::TextureOptions::NEAREST,
+                    "linear" => egui::TextureOptions::LINEAR,
+                    _ => return Err(usage_error(head)),
+                };
+                self.reload_textures();
+            }
+            "safearea" => {
+                // 格式: safearea <上> <下> <左> <右>  /  safearea off
+                if args.len() == 2 && args[1].eq_ignore_ascii_case("off") {
+                    self.safe_insets = egui::Margin::ZERO;
+                } else {
+                    if args.len() != 5 { return Err(usage_error(head)); }
+                    let mut v = [0.0f32; 4];
+                    for (dst, a) in v.iter_mut().zip(&args[1..]) {
+                        *dst = a.parse::<f32>().ok().filter(|x| *x >= 0.0)
+                            .ok_or_else(|| format!("SAFEAREA: 内边距必须是非负数字，收到 \"{}\"", a))?;
+                    }
+                    self.safe_insets = egui::Margin { top: v[0], bottom: v[1], left: v[2], right: v[3] };
+                }
+                let m = self.safe_insets;
+                self.console_logs.push(format!(
+                    "[系统] 安全区内边距: 上 {} 下 {} 左 {} 右 {}", m.top, m.bottom, m.left, m.right
+                ));
+            }
+            "theme" => {
+                // 格式: theme <参数> <值>  /  theme load|save <路径>  /  theme reset
+                match (args.get(1).map(|a| a.to_lowercase()).as_deref(), args.len()) {
+                    (Some("reset"), 2) => self.dialogue_theme = DialogueTheme::default(),
+                    (Some("load"), n) if n >= 3 => {
+                        let path = args[2..].join(" ");
+                        let data = std::fs::read_to_string(&path)
+                            .map_err(|e| format!("THEME: 无法读取 {}: {}", path, e))?;
+                        self.dialogue_theme = serde_json::from_str(&data)
+                            .map_err(|e| format!("THEME: 主题JSON格式错误: {}", e))?;
+                        self.console_logs.push(format!("[系统] 已加载对话框主题: {}", path));
+                    }
+                    (Some("save"), n) if n >= 3 => {
+                        let path = args[2..].join(" ");
+                        let data = serde_json::to_string_pretty(&self.dialogue_theme)
+                            .map_err(|e| e.to_string())?;
+                        std::fs::write(&path, data)
+                            .map_err(|e| format!("THEME: 无法写入 {}: {}", path, e))?;
+                        self.console_logs.push(format!("[系统] 对话框主题已保存至: {}", path));
+                    }
+                    (Some(key), 3) => self.dialogue_theme.set_value(key, args[2])?,
+                    _ => return Err(usage_error(head)),
+                }
+            }
+            "stats" => {
+                // 格式: stats
+                self.show_stats = !self.show_stats;
+            }
+            "profile" => {
+                // 格式: profile  /  profile on|off
+                match args.get(1).map(|a| a.to_lowercase()).as_deref() {
+                    Some("on") => self.profile_chars = true,
+                    Some("off") => self.profile_chars = false,
+                    None => {}
+                    Some(_) => return Err(usage_error(head)),
+                }
+                self.log_profile_report();
+            }
+            "audio" => {
+                // 格式: audio reinit
+                if args.len() != 2 || !args[1].eq_ignore_ascii_case("reinit") { 
+                    return Err(usage_error(head)); 
+                }
+                self.reinit_audio(true);
+            }
+            "list" => {
+                // 格式: list
+                self.log_resource_list();
+            }
+            "drawcalls" => {
+                // 格式: drawcalls
+                let (before, after) = self.draw_call_stats;
+                self.console_logs.push(format!("[统计] 角色绘制调用: 合并前 {} -> 合并后 {}", before, after));
+            }
+            "status" => {
+                // 格式: status 0
+                if args.len() != 2 { return Err(usage_error(head)); }
+                let idx = self.parse_slot(head, args[1])?;
+                self.log_slot_status(idx);
+            }
+            "anims" => {
+                // 格式: anims <槽位>
+                if args.len() != 2 { return Err(usage_error(head)); }
+                let idx = self.parse_slot(head, args[1])?;
+                let (list, current) = match self.characters.get(idx) {
+                    Some(Some(char_arc)) => {
+                        let char = char_arc.lock().map_err(|_| format!("ANIMS: 槽位 {} 状态锁定失败", idx))?;
+                        (char.anim_durations(), char.current_animation_name())
+                    }
+                    _ => return Err(format!("ANIMS: 槽位 {} 没有角色", idx)),
+                };
+                if list.is_empty() {
+                    self.console_logs.push(format!("[列表] 槽位 {} 没有任何动画（静态立绘）", idx));
+                    return Ok(());
+                }
+                self.console_logs.push(format!("[列表] 槽位 {} 共 {} 个动画:", idx, list.len()));
+                for (name, duration) in list {
+                    let playing = if current.as_deref() == Some(name.as_str()) { "  ◀ 播放中" } else { "" };
+                    self.console_logs.push(format!("[列表]   {} ({:.2}s){}", name, duration, playing));
+                }
+            }
+            "dump" => {
+                // 格式: dump <槽位>
+                if args.len() != 2 { return Err(usage_error(head)); }
+                let idx = self.parse_slot(head, args[1])?;
+                let lines = match self.characters.get(idx) {
+                    Some(Some(char_arc)) => char_arc.lock()
+                        .map_err(|_| format!("DUMP: 槽位 {} 状态锁定失败", idx))?
+                        .dump_lines(),
+                    _ => return Err(format!("DUMP: 槽位 {} 没有角色", idx)),
+                };
+                self.console_logs.push(format!("[状态] 槽位 {} 结构:", idx));
+                self.console_logs.extend(lines);
+            }
+            "pos" => {
+                // 格式: pos <槽位> <锚点|auto>  /  pos <槽位> <x%> <y%>  /  pos <槽位> <x像素> <y像素>
+                if args.len() != 3 && args.len() != 4 { return Err(usage_error(head)); }
+                let slot_idx = self.parse_slot(head, args[1])?;
+                let placement = if args.len() == 3 {
+                    let anchor = args[2].to_lowercase();
+                    if anchor == "auto" {
+                        StagePlacement::Auto
+                    } else {
+                        let (_, x) = STAGE_ANCHORS.iter().find(|(name, _)| *name == anchor)
+                            .ok_or_else(|| format!(
+                                "POS: 未知锚点 {}（可用: {} auto）", 
+                                anchor, 
+                                STAGE_ANCHORS.iter().map(|(n, _)| *n).collect::<Vec<_>>().join(" ")
+                            ))?;
+                        StagePlacement::Relative(*x, None)
+                    }
+                } else {
+                    let parse = |s: &str| -> Result<(f32, bool), String> {
+                        let (num, is_percent) = match s.strip_suffix('%') {
+                            Some(n) => (n, true),
+                            None => (s, false),
+                        };
+                        num.parse::<f32>().map(|v| (v, is_percent))
+                            .map_err(|_| format!("POS: 坐标必须是数字或百分比，收到 \"{}\"", s))
+                    };
+                    match (parse(args[2])?, parse(args[3])?) {
+                        ((x, true), (y, true)) => StagePlacement::Relative(x / 100.0, Some(y / 100.0)),
+                        ((x, false), (y, false)) => StagePlacement::Absolute(Pos2::new(x, y)),
+                        _ => return Err("POS: x 与 y 必须同为百分比或同为像素".into()),
+                    }
+                };
+                match self.characters.get(slot_idx) {
+                    Some(Some(char_arc)) => {
+                        if let Ok(mut char) = char_arc.lock() {
+                            char.placement = placement;
+                            char.user_offset = Vec2::ZERO;  // 重新定位时清除手动拖动偏移
+                        }
+                    }
+                    _ => return Err(format!("POS: 槽位 {} 没有角色", slot_idx)),
+                }
+            }
+            "shadowblob" => {
+                // 格式: shadowblob <槽位>  /  shadowblob <槽位> on [宽度] [不透明度]  /  shadowblob <槽位> off
+                if !(2..=5).contains(&args.len()) { return Err(usage_error(head)); }
+                let slot_idx = self.parse_slot(head, args[1])?;
+                let shadow = match args.get(2).map(|a| a.to_lowercase()).as_deref() {
+                    None => None,
+                    Some("off") if args.len() == 3 => Some(None),
+                    Some("on") => {
+                        let mut blob = ShadowBlob::default();
+                        if let Some(size) = args.get(3) {
+                            blob.size = size.parse::<f32>().ok().filter(|v| *v > 0.0)
+                                .ok_or_else(|| format!("SHADOWBLOB: 宽度必须是正数，收到 \"{}\"", size))?;
+                        }
+                        if let Some(opacity) = args.get(4) {
+                            blob.opacity = opacity.parse::<f32>().ok().filter(|v| (0.0..=1.0).contains(v))
+                                .ok_or_else(|| format!("SHADOWBLOB: 不透明度应在 0~1 之间，收到 \"{}\"", opacity))?;
+                        }
+                        Some(Some(blob))
+                    }
+                    _ => return Err(usage_error(head)),
+                };
+                let current = match self.characters.get(slot_idx) {
+                    Some(Some(char_arc)) => {
+                        let mut char = char_arc.lock().map_err(|_| format!("SHADOWBLOB: 槽位 {} 状态锁定失败", slot_idx))?;
+                        if let Some(shadow) = shadow {
+                            char.shadow = shadow;
+                        }
+                        char.shadow
+                    }
+                    _ => return Err(format!("SHADOWBLOB: 槽位 {} 没有角色", slot_idx)),
+                };
+                if shadow.is_none() {
+                    self.console_logs.push(match current {
+                        Some(blob) => format!("[状态] 槽位 {} 脚下阴影: 宽度 {} 不透明度 {}", slot_idx, blob.size, blob.opacity),
+                        None => format!("[状态] 槽位 {} 脚下阴影: 关闭", slot_idx),
+                    });
+                }
+            }
+            "anchor" => {
+                // 格式: anchor <槽位>  /  anchor <槽位> root|feet|center
+                if args.len() != 2 && args.len() != 3 { return Err(usage_error(head)); }
+                let slot_idx = self.parse_slot(head, args[1])?;
+                let anchor = match args.get(2) {
+                    Some(a) => Some(StageAnchor::parse(a)
+                        .ok_or_else(|| format!("ANCHOR: 未知锚点 {}（可用: root feet center）", a))?),
+                    None => None,
+                };
+                let current = match self.characters.get(slot_idx) {
+                    Some(Some(char_arc)) => {
+                        let mut char = char_arc.lock().map_err(|_| format!("ANCHOR: 槽位 {} 状态锁定失败", slot_idx))?;
+                        if let Some(anchor) = anchor {
+                            char.anchor = anchor;
+                        }
+                        char.anchor
+                    }
+                    _ => return Err(format!("ANCHOR: 槽位 {} 没有角色", slot_idx)),
+                };
+                if anchor.is_none() {
+                    self.console_logs.push(format!("[状态] 槽位 {} 定位锚点: {}", slot_idx, current.name()));
+                }
+            }
+            "bind" => {
+                // 格式: bind  （列出）/  bind <advance|console|close|slots|freeze|skip|cycle|quickanims> <按键...>
+                if args.len() == 1 {
+                    let b = &self.key_bindings;
+                    for (name, keys) in [
+                        ("advance", &b.advance), ("console", &b.console), ("close", &b.close), ("slots", &b.slots),
+                        ("freeze", &b.freeze), ("skip", &b.skip), ("cycle", &b.cycle), ("quickanims", &b.quick_anims),
+                    ] {
+                        self.console_logs.push(format!("[快捷键] {} = {}", name, keys.join(" ")));
+                    }
+                    return Ok(());
+                }
+                if args.len() < 3 { return Err(usage_error(head)); }
+                if let Some(bad) = args[2..].iter().find(|k| egui::Key::from_name(k).is_none()) {
+                    return Err(format!("BIND: 无法识别的按键 {}", bad));
+                }
+                let keys = self.key_bindings.action_mut(&args[1].to_lowercase())
+                    .ok_or_else(|| format!("BIND: 未知动作 {}（可用: advance console close slots freeze skip cycle quickanims）", args[1]))?;
+                *keys = args[2..].iter().map(|k| k.to_string()).collect();
+            }
+            "tween" => {
+                // 格式: tween <槽位> <属性> <目标值> <秒> [缓动] [queue]
+                //       tween <槽位> cancel [属性]   （取消进行中与排队的补间）
+                if args.len() < 3 { return Err(usage_error(head)); }
+                let slot_idx = self.parse_slot(head, args[1])?;
+                
+                if args[2].eq_ignore_ascii_case("cancel") {
+                    let prop = match args.get(3) {
+                        Some(p) => Some(TweenProp::parse(&p.to_lowercase())
+                            .ok_or_else(|| format!("TWEEN: 未知属性 {}（可用: x y scale alpha rotation）", p))?),
+                        None => None,
+                    };
+                    let keep = |t: &Tween| t.slot_idx != slot_idx || prop.map_or(false, |p| p != t.prop);
+                    self.tweens.retain(keep);
+                    self.tween_queue.retain(keep);
+                    return Ok(());
+                }
+                
+                if args.len() < 5 || args.len() > 7 { return Err(usage_error(head)); }
+                let prop = TweenProp::parse(&args[2].to_lowercase())
+                    .ok_or_else(|| format!("TWEEN: 未知属性 {}（可用: x y scale alpha rotation）", args[2]))?;
+                let to = args[3].parse::<f32>()
+                    .map_err(|_| format!("TWEEN: 目标值必须是数字，收到 \"{}\"", args[3]))?;
+                let duration = args[4].parse::<f32>().ok().filter(|d| *d >= 0.0)
+                    .ok_or_else(|| format!("TWEEN: 时长必须是非负秒数，收到 \"{}\"", args[4]))?;
+                
+                let mut ease = Ease::InOut;
+                let mut queued = false;
+                for arg in &args[5..] {
+                    let arg = arg.to_lowercase();
+                    if arg == "queue" {
+                        queued = true;
+                    } else {
+                        ease = Ease::parse(&arg)
+                            .ok_or_else(|| format!("TWEEN: 未知缓动 {}（可用: linear in out inout）", arg))?;
+                    }
+                }
+                if !matches!(self.characters.get(slot_idx), Some(Some(_))) {
+                    return Err(format!("TWEEN: 槽位 {} 没有角色", slot_idx));
+                }
+                
+                let tween = Tween { slot_idx, prop, from: None, to, duration, elapsed: 0.0, ease };
+                if queued {
+                    self.tween_queue.push(tween);
+                } else {
+                    // 同一属性的新补间替换旧补间，从当前值继续
+                    self.tweens.retain(|t| t.slot_idx != slot_idx || t.prop != prop);
+                    self.tweens.push(tween);
+                }
+            }
+            "rot" => {
+                // 格式: rot <槽位> <角度>
+                if args.len() != 3 { return Err(usage_error(head)); }
+                let slot_idx = self.parse_slot(head, args[1])?;
+                let degrees = args[2].parse::<f32>()
+                    .map_err(|_| format!("ROT: 角度必须是数字，收到 \"{}\"", args[2]))?;
+                let _ = tx.send(AppCommand::SetRotation { slot_idx, degrees });
+            }
+            "attach" => {
+                // 格式: attach <槽位> <骨骼名> <图片路径|off>
+                if args.len() != 4 { return Err(usage_error(head)); }
+                let slot_idx = self.parse_slot(head, args[1])?;
+                let bone = args[2].to_string();
+                if args[3].eq_ignore_ascii_case("off") {
+                    let _ = tx.send(AppCommand::DetachProp { slot_idx, bone });
+                } else {
+                    let _ = tx.send(AppCommand::AttachProp { slot_idx, bone, path: args[3].to_string() });
+                }
+            }
+            "coord" => {
+                // 格式: coord <槽位> [flipy=on|off] [flipx=on|off]   （不带选项时仅显示当前设置）
+                if args.len() < 2 || args.len() > 4 { return Err(usage_error(head)); }
+                let slot_idx = self.parse_slot(head, args[1])?;
+                let (mut flip_x, mut flip_y) = (None, None);
+                for opt in &args[2..] {
+                    let (key, value) = opt.split_once('=').ok_or_else(|| usage_error(head))?;
+                    let on = match value.to_lowercase().as_str() {
+                        "on" => true,
+                        "off" => false,
+                        _ => return Err(format!("COORD: {} 的值必须是 on 或 off，收到 \"{}\"", key, value)),
+                    };
+                    match key.to_lowercase().as_str() {
+                        "flipx" => flip_x = Some(on),
+                        "flipy" => flip_y = Some(on),
+                        _ => return Err(format!("COORD: 未知选项 {}（可用: flipx flipy）", key)),
+                    }
+                }
+                let _ = tx.send(AppCommand::SetCoord { slot_idx, flip_x, flip_y });
+            }
+            "alpha" => {
+                // 格式: alpha <槽位> <0~1>
+                if args.len() != 3 { return Err(usage_error(head)); }
+                let slot_idx = self.parse_slot(head, args[1])?;
+                let alpha = args[2].parse::<f32>().ok().filter(|a| (0.0..=1.0).contains(a))
+                    .ok_or_else(|| format!("ALPHA: 不透明度必须是 0~1 的数字，收到 \"{}\"", args[2]))?;
+                let _ = tx.send(AppCommand::SetAlpha { slot_idx, alpha });
+            }
+            "enter" | "exit" => {
+                // 格式: enter <槽位> [from=left|right|bottom|fade] [秒]  /  exit <槽位> [to=...] [秒]
+                // 对空槽位 enter 时，过渡会在该槽位下一次加载完成后开始
+                if args.len() < 2 || args.len() > 4 { return Err(usage_error(head)); }
+                let slot_idx = self.parse_slot(head, args[1])?;
+                let is_enter = head == "enter";
+                let key = if is_enter { "from=" } else { "to=" };
+                
+                let mut offset = Vec2::ZERO;
+                let mut duration = 0.5;
+                for arg in &args[2..] {
+                    if let Some(dir) = arg.strip_prefix(key) {
+                        offset = transition_offset(&dir.to_lowercase())
+                            .ok_or_else(|| format!("{}: 方向只能是 left/right/bottom/fade，收到 \"{}\"", head.to_uppercase(), dir))?;
+                    } else {
+                        duration = arg.parse::<f32>().ok().filter(|d| *d >= 0.0)
+                            .ok_or_else(|| format!("{}: 时长必须是非负秒数，收到 \"{}\"", head.to_uppercase(), arg))?;
+                    }
+                }
+                
+                match self.characters.get(slot_idx) {
+                    Some(Some(char_arc)) => {
+                        if let Ok(mut char) = char_arc.lock() {
+                            if is_enter {
+                                char.start_transition((offset, 0.0), (Vec2::ZERO, 1.0), duration, false);
+                            } else {
+                                char.start_transition((Vec2::ZERO, 1.0), (offset, 0.0), duration, true);
+                            }
+                        }
+                    }
+                    _ if is_enter => {
+                        self.pending_enters.insert(slot_idx, (offset, duration));
+                    }
+                    _ => return Err(format!("EXIT: 槽位 {} 没有角色", slot_idx)),
+                }
+            }
+            "fitheight" => {
+                // 格式: fitheight <槽位> <比例 0~2>  /  fitheight <槽位> off
+                if args.len() != 3 { return Err(usage_error(head)); }
+                let slot_idx = self.parse_slot(head, args[1])?;
+                let fraction = if args[2].eq_ignore_ascii_case("off") {
+                    None
+                } else {
+                    Some(args[2].parse::<f32>().ok().filter(|f| *f > 0.0 && *f <= 2.0)
+                        .ok_or_else(|| format!("FITHEIGHT: 比例必须是 0~2 之间的数字，收到 \"{}\"", args[2]))?)
+                };
+                match self.characters.get(slot_idx) {
+                    Some(Some(char_arc)) => {
+                        if let Ok(mut char) = char_arc.lock() {
+                            char.fit_height = fraction;
+                        }
+                    }
+                    _ => return Err(format!("FITHEIGHT: 槽位 {} 没有角色", slot_idx)),
+                }
+            }
+            "set" => {
+                // 格式: set <名称> = <值>   （省略参数时列出全部变量）
+                if rest.is_empty() {
+                    let mut lines: Vec<String> = self.variables.iter()
+                        .map(|(k, v)| format!("[变量] {} = {}", k, v))
+                        .collect();
+                    lines.sort();
+                    if lines.is_empty() {
+                        self.console_logs.push("[系统] 没有已定义的变量".into());
+                    }
+                    self.console_logs.extend(lines);
+                    return Ok(());
+                }
+                let (name, value) = rest.split_once('=').ok_or_else(|| usage_error(head))?;
+                let name = name.trim();
+                if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                    return Err(format!("SET: 变量名只能包含字母、数字和下划线，收到 \"{}\"", name));
+                }
+                self.variables.insert(name.to_string(), value.trim().to_string());
+            }
+            "timeline" => {
+                // 格式: timeline  /  timeline on|off|clear  /  timeline save <路径>
+                match args.get(1).map(|a| a.to_lowercase()).as_deref() {
+                    None => self.console_logs.push(format!(
+                        "[状态] 时间线: {}，已录制 {} 条命令",
+                        if self.timeline_start.is_some() { "录制中" } else { "未录制" },
+                        self.timeline.len(),
+                    )),
+                    Some("on") if args.len() == 2 => {
+                        self.timeline.clear();
+                        self.timeline_start = Some(Instant::now());
+                        self.console_logs.push("[系统] 开始录制命令时间线".into());
+                    }
+                    Some("off") if args.len() == 2 => {
+                        self.timeline_start = None;
+                        self.console_logs.push(format!("[系统] 停止录制，共 {} 条命令", self.timeline.len()));
+                    }
+                    Some("clear") if args.len() == 2 => {
+                        self.timeline.clear();
+                        if let Some(start) = &mut self.timeline_start {
+                            *start = Instant::now();
+                        }
+                    }
+                    Some("save") if args.len() >= 3 => {
+                        let path = args[2..].join(" ");
+                        let mut script = String::from("# AEFR 命令时间线（play 命令回放）\n");
+                        let mut last = 0.0;
+                        for (time, cmd) in &self.timeline {
+                            if time - last >= TIMELINE_MIN_WAIT {
+                                script.push_str(&format!("wait {:.2}\n", time - last));
+                                last = *time;
+                            }
+                            script.push_str(cmd);
+                            script.push('\n');
+                        }
+                        std::fs::write(&path, script)
+                            .map_err(|e| format!("TIMELINE: 无法写入 {}: {}", path, e))?;
+                        self.console_logs.push(format!("[系统] 时间线已导出: {}（{} 条命令）", path, self.timeline.len()));
+                    }
+                    _ => return Err(usage_error(head)),
+                }
+            }
+            "play" => {
+                // 格式: play <脚本路径>  /  play stop
+                if args.len() < 2 { return Err(usage_error(head)); }
+                if args.len() == 2 && args[1].eq_ignore_ascii_case("stop") {
+                    let remaining = self.script_queue.len();
+                    self.script_queue.clear();
+                    self.script_wait = 0.0;
+                    self.console_logs.push(format!("[系统] 已停止回放，跳过剩余 {} 条命令", remaining));
+                    return Ok(());
+                }
+                let path = args[1..].join(" ");
+                let text = std::fs::read_to_string(&path)
+                    .map_err(|e| format!("PLAY: 无法读取 {}: {}", path, e))?;
+                self.script_queue = text.lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(String::from)
+                    .collect();
+                self.script_wait = 0.0;
+                self.console_logs.push(format!("[系统] 开始回放 {}（{} 条命令）", path, self.script_queue.len()));
+            }
+            "wait" => {
+                // 格式: wait <秒>   （由 play 回放的脚本逐帧扣减）
+                if args.len() != 2 { return Err(usage_error(head)); }
+                let secs = args[1].parse::<f32>().ok().filter(|s| *s >= 0.0)
+                    .ok_or_else(|| format!("WAIT: 秒数必须是非负数，收到 \"{}\"", args[1]))?;
+                self.script_wait += secs;
+            }
+            "unset" => {
+                // 格式: unset <名称>
+                if args.len() != 2 { return Err(usage_error(head)); }
+                if self.variables.remove(args[1]).is_none() {
+                    return Err(format!("UNSET: 变量 {} 不存在", args[1]));
+                }
+            }
+            "macro" | "alias" => {
+                // 格式: macro <名称> = 命令1 ; 命令2 ; ...   /  alias <名称> = <命令>
+                //       省略 "= ..." 时查看定义，"=" 后为空时删除
+                let is_macro = head == "macro";
+                let Some((name, body)) = rest.split_once('=') else {
+                    let name = rest.to_lowercase();
+                    let lines: Vec<String> = if is_macro {
+                        self.scenario.macros.iter()
+                            .filter(|(k, _)| name.is_empty() || **k == name)
+                            .map(|(k, v)| format!("[宏] {} = {}", k, v.join(" ; ")))
+                            .collect()
+                    } else {
+                        self.scenario.aliases.iter()
+                            .filter(|(k, _)| name.is_empty() || **k == name)
+                            .map(|(k, v)| format!("[别名] {} = {}", k, v))
+                            .collect()
+                    };
+                    if lines.is_empty() {
+                        self.console_logs.push(format!("[系统] 没有已定义的{}", if is_macro { "宏" } else { "别名" }));
+                    }
+                    self.console_logs.extend(lines);
+                    return Ok(());
+                };
+                
+                let name = name.trim().to_lowercase();
+                if name.is_empty() || name.contains(char::is_whitespace) {
+                    return Err(usage_error(head));
+                }
+                if is_macro && COMMAND_USAGES.iter().any(|(cmd, _, _)| *cmd == name) {
+                    return Err(format!("MACRO: 不能覆盖内置命令 {}", name));
+                }
+                
+                let body = body.trim();
+                if body.is_empty() {
+                    self.scenario.macros.remove(&name);
+                    self.scenario.aliases.remove(&name);
+                    self.console_logs.push(format!("[系统] 已删除: {}", name));
+                    return Ok(());
+                }
+                
+                if is_macro {
+                    let cmds: Vec<String> = split_commands(body).into_iter()
+                        .filter(|c| !c.trim().is_empty())
+                        .collect();
+                    // 直接自引用在定义时拒绝；间接循环由执行时的展开层数限制兜底
+                    let refers_self = cmds.iter().any(|c| {
+                        tokenize_command(c).first().map_or(false, |t| t.to_lowercase() == name)
+                    });
+                    if refers_self {
+                        return Err(format!("MACRO: 宏 {} 不能调用自身", name));
+                    }
+                    self.console_logs.push(format!("[系统] 已定义宏 {}（{} 条命令）", name, cmds.len()));
+                    self.scenario.aliases.remove(&name);
+                    self.scenario.macros.insert(name, cmds);
+                } else {
+                    // 目标可带预设参数（如 alias walk = anim 0 walk true），参数保留原大小写
+                    let target = body.to_string();
+                    validate_alias(&name, &target)?;
+                    self.console_logs.push(format!("[系统] 已定义别名 {} = {}", name, target));
+                    self.scenario.macros.remove(&name);
+                    self.scenario.aliases.insert(name, target);
+                }
+            }
+            "help" => {
+                // 格式: help [命令]
+                let lines: Vec<String> = COMMAND_USAGES.iter()
+                    .filter(|(name, _, _)| args.get(1).map_or(true, |a| a.eq_ignore_ascii_case(name)))
+                    .map(|(name, usage, desc)| format!("[帮助] {} {}  — {}", name, usage, desc))
+                    .collect();
+                if lines.is_empty() {
+                    return Err(format!("未知命令: {}", args[1]));
+                }
+                self.console_logs.extend(lines);
+            }
+            _ => return Err(format!("未知命令: {}（输入 help 查看可用命令）", head)),
+        }
+        Ok(())
+    }
+
+    /// 列出所有槽位（含空槽位）以及背景、BGM状态（只读）
+    fn log_resource_list(&mut self) {
+        let mut lines = Vec::new();
+        for (i, slot) in self.characters.iter().enumerate() {
+            lines.push(match slot {
+                Some(char_arc) => match char_arc.lock() {
+                    Ok(char) => format!(
+                        "[列表] 槽位 {}: {} | 动画={} 位置=({:.1}, {:.1}) 缩放={:.3}",
+                        i, char.source_path,
+                        char.animation_label(),
+                        char.position.x, char.position.y, char.scale,
+                    ),
+                    Err(_) => format!("[列表] 槽位 {}: (锁定失败)", i),
+                },
+                None => format!("[列表] 槽位 {}: (空)", i),
+            });
+        }
+        
+        let scene = &self.scenario.scenes[self.current_scene_idx];
+        lines.push(format!(
+            "[列表] 背景: {}{}", 
+            scene.bg_path.as_deref().unwrap_or("(无)"),
+            scene.bg_anim_fps.map(|fps| format!(" (序列帧 {} fps)", fps)).unwrap_or_default()
+        ));
+        lines.push(format!(
+            "[列表] BGM: {} ({})",
+            scene.bgm_path.as_deref().unwrap_or("(无)"),
+            self.audio_manager.as_ref().map_or("无音频设备", |mgr| mgr.bgm_state()),
+        ));
+        self.console_logs.extend(lines);
+    }
+
+    /// 本帧允许同时更新的角色组数：手动设置优先，其次为自适应上限，默认等于工作线程数
+    fn effective_concurrency(&self) -> usize {
+        let workers = self.scheduler.worker_count;
+        match self.sched_manual {
+            Some(n) => n.clamp(1, workers),
+            None if self.sched_auto => self.sched_limit.clamp(1, workers),
+            None => workers,
+        }
+    }
+    
+    /// 自适应调度：UI线程帧时间过长时逐级降低并发，恢复余量后逐级回升
+    /// 只限制每帧并行处理的角色组数，不重建线程池
+    fn adapt_scheduler(&mut self, dt: f32) {
+        if !self.sched_auto || self.sched_manual.is_some() {
+            return;
+        }
+        self.sched_adapt_timer += dt;
+        if self.sched_adapt_timer < SCHED_ADAPT_INTERVAL {
+            return;
+        }
+        
+        let current = self.effective_concurrency();
+        let next = if self.frame_time_avg > SCHED_STARVE_TIME {
+            current.saturating_sub(1).max(1)
+        } else if self.frame_time_avg < SCHED_HEADROOM_TIME {
+            (current + 1).min(self.scheduler.worker_count)
+        } else {
+            current
+        };
+        if next != current {
+            self.sched_limit = next;
+            self.sched_adapt_timer = 0.0;
+            self.console_logs.push(format!(
+                "[系统] 自适应调度: 并发 {} → {} (帧时间 {:.1} ms)",
+                current, next, self.frame_time_avg * 1000.0
+            ));
+        }
+    }
+    
+    /// 输出上一帧的更新耗时报告（并行墙钟时间 vs 各角色耗时之和）
+    fn log_profile_report(&mut self) {
+        let mut lines = vec![format!(
+            "[性能] {} {:.3} ms / {} 工作线程 (逐角色计时: {})",
+            if self.parallel_used { "并行更新" } else { "串行更新" },
+            self.parallel_time.as_secs_f64() * 1000.0,
+            self.scheduler.worker_count,
+            if self.profile_chars { "开" } else { "关" },
+        )];
+        if self.profile_chars {
+            for (i, slot) in self.characters.iter().enumerate() {
+                if let Some(Ok(char)) = slot.as_ref().map(|c| c.lock()) {
+                    lines.push(format!(
+                        "[性能]   槽位 {}: 更新 {:.3} ms / 网格 {:.3} ms",
+                        i,
+                        char.last_update_time.as_secs_f64() * 1000.0,
+                        char.last_build_time.as_secs_f64() * 1000.0,
+                    ));
+                }
+            }
+            lines.push(format!("[性能] 串行合计 {:.3} ms", self.serial_time_sum.as_secs_f64() * 1000.0));
+        }
+        self.console_logs.extend(lines);
+    }
+
+    /// 输出骨骼信息：未指定骨骼名时列出全部骨骼
+    fn log_bone_info(&mut self, idx: usize, bone_name: Option<&str>) {
+        let line = match self.characters.get(idx) {
+            Some(Some(char_arc)) => match char_arc.lock() {
+                Ok(char) => match bone_name {
+                    Some(name) => match char.get_bone_transform(name) {
+                        Some((x, y, rot, scale)) => format!(
+                            "[骨骼] {}: x={:.2} y={:.2} 旋转={:.2}° 缩放={:.3}", name, x, y, rot, scale
+                        ),
+                        None => format!("[错误] 槽位 {} 不存在骨骼: {}", idx, name),
+                    },
+                    None => format!("[骨骼] 槽位 {}: {}", idx, char.bone_names().join(", ")),
+                },
+                Err(_) => format!("[错误] 槽位 {} 状态锁定失败", idx),
+            },
+            _ => format!("[骨骼] 槽位 {}: (空)", idx),
         };
-        
-        // 5. 创建初始场景
-        let startup_text = "GNU:AEFR 已启动！\n正在等待指令......";
-        let mut first_scene = Scene::default();
-        first_scene.speaker_name = "OxidizedSchale".into();
-        first_scene.speaker_aff = "The Executive Committee of GNU:AEFR".into();
-        first_scene.dialogue_content = startup_text.into();
+        self.console_logs.push(line);
+    }
 
-        // 6. 返回应用实例
-        Self {
-            scheduler: AefrScheduler::new(),
-            is_auto_enabled: true, 
-            show_dialogue: true,
-            scenario: Scenario { scenes: vec![first_scene] },
-            current_scene_idx: 0,
-            target_chars: startup_text.chars().collect(), 
-            visible_count: 0, 
-            type_timer: 0.0,
-            console_open: false,
-            selected_slot: 0,
-            console_input: String::new(),
-            console_logs: vec!["[系统] 编辑器就绪。".into()],
-            show_anim_preview: false,
-            preview_anim_idx: 0,
-            // 初始化5个空角色槽位
-            characters: (0..5).map(|_| None).collect(),
-            background: None,
-            audio_manager,
-            tx, rx,
+    /// 带时间戳的完整日志文本（复制到剪贴板 / logsave 共用）
+    fn log_text(&self) -> String {
+        self.console_logs.iter().enumerate()
+            .map(|(i, line)| match self.log_times.get(i) {
+                Some(t) => format!("[{:>9.3}s] {}", t, line),
+                None => format!("[{:>9}] {}", "-", line),  // 本帧刚写入，尚未补记时间
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+    
+    /// 当前运行时设置导出为配置
+    fn current_config(&self) -> AppConfig {
+        AppConfig {
+            type_interval: self.type_interval,
+            pacing: self.type_pacing,
+            stage_color: self.stage_color.map_or_else(|| "transparent".into(), color_to_hex),
+            transparent_window: self.transparent_window,
+            font_path: self.font_path.clone(),
+            log_limit: self.log_limit,
+            log_level: self.log_level.name().into(),
+            sched_auto: self.sched_auto,
+            sched_nice: self.scheduler.lower_priority,
+            sched_cores: self.sched_manual.unwrap_or(0),
+            sched_reserve: self.scheduler.policy.name(),
+            texture_budget_mb: self.texture_budget.map_or(0, |bytes| bytes / (1024 * 1024)),
+            load_retries: self.load_retries,
+            render_scale: self.render_scale,
+            console_docked: self.console_docked,
+            unfocused_fps: self.unfocused_fps,
+            theme: self.dialogue_theme.clone(),
         }
     }
-
-    /// 同步当前场景到UI状态
-    /// 在场景切换时调用，重置对话状态
-    fn sync_scene_to_ui(&mut self) {
-        if let Some(scene) = self.scenario.scenes.get(self.current_scene_idx) {
-            self.target_chars = scene.dialogue_content.chars().collect();
-            // 🌟 修复打字机残影：切幕时必须归零
-            self.visible_count = 0;
-            self.type_timer = 0.0;
+    
+    /// LOADALL：套用清单项的位置/缩放/动画（位置与动画复用 pos/anim 命令的解析与校验）
+    fn apply_loadall_entry(&mut self, entry: LoadAllEntry) {
+        let idx = entry.slot;
+        if let Some(pos) = &entry.pos {
+            self.run_command(&format!("pos {} {}", idx, pos), 1);
+        }
+        if let (Some(scale), Some(Some(char_arc))) = (entry.scale, self.characters.get(idx)) {
+            if let Ok(mut char) = char_arc.lock() {
+                char.user_scale = scale.clamp(0.1, 10.0);
+            }
+        }
+        if let Some(anim) = &entry.anim {
+            self.run_command(&format!("anim {} \"{}\" {}", idx, anim, entry.looping), 1);
         }
+        self.loadall_result.0 += 1;
+        self.console_logs.push(format!("[系统] LOADALL: 槽位 {} 已就位", idx));
+        self.finish_loadall_if_done();
     }
-
-    /// 解析并执行控制台命令
-    /// 支持的命令格式：
-    /// - load <槽位> <路径>    # 加载Spine角色
-    /// - anim <槽位> <动画名> [循环] # 设置动画
-    /// - bgm <路径>           # 播放背景音乐
-    /// - se <路径>            # 播放音效
-    /// - talk 名称|所属|内容  # 发送对话
-    /// - bg <路径>            # 设置背景
-    fn parse_and_send_command(&mut self, input: &str) {
-        let input_trimmed = input.trim();
-        if input_trimmed.is_empty() { return; }
+    
+    /// LOADALL：全部槽位完成（成功或失败）后输出汇总
+    fn finish_loadall_if_done(&mut self) {
+        if self.loadall_pending.is_empty() {
+            let (ok, failed) = self.loadall_result;
+            self.console_logs.push(format!("[系统] LOADALL 完成: 成功 {}，失败 {}", ok, failed));
+        }
+    }
+    
+    /// 设置渲染缩放：pixels_per_point = 系统缩放 × factor
+    /// 舞台按逻辑点布局、角色缩放按屏幕高度换算，因此角色在画面中的占比与物理大小都不变；
+    /// 对话框文字、控制台等 UI 随之放大，字体按新的缩放重新栅格化。
+    /// 窗口帧缓冲的物理像素数不变，额外开销主要是更大的字体纹理与更细的文字/圆角细分
+    fn set_render_scale(&mut self, ctx: &egui::Context, factor: f32) {
+        let native = ctx.native_pixels_per_point().unwrap_or(1.0);
+        let physical = ctx.screen_rect().size() * ctx.pixels_per_point();
+        let ppp = native * factor;
+        ctx.set_pixels_per_point(ppp);
+        self.render_scale = factor;
+        self.console_logs.push(format!(
+            "[性能] 渲染缩放 ×{}: pixels_per_point {:.2}，物理 {:.0}x{:.0} 像素 / 逻辑 {:.0}x{:.0} 点（字体纹理将重新生成）",
+            factor, ppp, physical.x, physical.y, physical.x / ppp, physical.y / ppp,
+        ));
+    }
+    
+    /// 应用配置：逐项生效，无效的值给出警告并保留当前设置
+    fn apply_config(&mut self, ctx: &egui::Context, config: AppConfig) {
+        if config.type_interval > 0.0 {
+            self.type_interval = config.type_interval;
+        } else {
+            self.console_logs.push(format!("[警告] 配置: type_interval 必须大于 0，收到 {}", config.type_interval));
+        }
+        self.type_pacing = config.pacing;
         
-        // 记录命令到日志
-        self.console_logs.push(format!("> {}", input_trimmed));
+        if config.stage_color.eq_ignore_ascii_case("transparent") {
+            self.stage_color = None;
+        } else if let Some(color) = parse_hex_color(&config.stage_color) {
+            self.stage_color = Some(color);
+        } else {
+            self.console_logs.push(format!("[警告] 配置: stage_color 格式应为 #RRGGBB 或 transparent，收到 \"{}\"", config.stage_color));
+        }
+        if config.transparent_window != self.transparent_window {
+            self.console_logs.push("[系统] 配置: transparent_window 需要重启后生效".into());
+        }
         
-        let tx = self.tx.clone();
-        let cmd_lower = input_trimmed.to_lowercase();
-
-        // 命令分发
-        if cmd_lower.starts_with("load ") {
-            // 格式: load 0 "path/to/file.atlas"
-            let parts: Vec<&str> = input_trimmed.splitn(2, ' ').collect();
-            if parts.len() == 2 {
-                if let Ok(idx) = parts[0][5..].trim().parse::<usize>() {
-                   let _ = tx.send(AppCommand::RequestLoad { 
-                       slot_idx: idx, 
-                       path: parts[1].replace("\"", "") 
-                   });
-                }
-            }
-        } else if cmd_lower.starts_with("anim ") {
-            // 格式: anim 0 idle true
-            let parts: Vec<&str> = input_trimmed.split_whitespace().collect();
-            if parts.len() >= 2 {
-                if let Ok(idx) = parts[1].parse::<usize>() {
-                    let anim_name = parts[2].to_string();
-                    let loop_anim = parts.get(3).map_or(true, |s| s.to_lowercase() == "true");
-                    let _ = tx.send(AppCommand::SetAnimation { 
-                        slot_idx: idx, 
-                        anim_name, 
-                        loop_anim 
-                    });
-                }
+        if config.font_path != self.font_path {
+            match setup_fonts(ctx, &config.font_path) {
+                Ok(()) => self.font_path = config.font_path,
+                Err(e) => self.console_logs.push(format!("[警告] 配置: {}，保留当前字体", e)),
             }
-        } else if cmd_lower.starts_with("bgm ") {
-             // 格式: bgm "path/to/bgm.mp3"
-             let _ = tx.send(AppCommand::PlayBgm(
-                 input_trimmed[4..].trim().replace("\"", "")
-             ));
-        } else if cmd_lower.starts_with("se ") {
-             // 格式: se "path/to/se.wav"
-             let _ = tx.send(AppCommand::PlaySe(
-                 input_trimmed[3..].trim().replace("\"", "")
-             ));
-        } else if cmd_lower == "stop" {
-             // 格式: stop
-             let _ = tx.send(AppCommand::StopBgm);
-        } else if cmd_lower.starts_with("talk ") {
-            // 格式: talk 名称|所属|内容
-            let p: Vec<&str> = input_trimmed[5..].split('|').collect();
-            if p.len() == 3 {
-                let _ = tx.send(AppCommand::Dialogue { 
-                    name: p[0].into(), 
-                    affiliation: p[1].into(), 
-                    content: p[2].into() 
-                });
+        }
+        
+        if config.log_limit > 0 {
+            self.log_limit = config.log_limit;
+        }
+        match LogLevel::parse(&config.log_level) {
+            Some(level) => self.log_level = level,
+            None => self.console_logs.push(format!("[警告] 配置: 未知日志级别 {}", config.log_level)),
+        }
+        
+        self.sched_auto = config.sched_auto;
+        self.sched_manual = (config.sched_cores > 0).then_some(config.sched_cores);
+        let mut parts = config.sched_reserve.split_whitespace();
+        let policy = match (parts.next(), parts.next(), parts.next()) {
+            (Some(kind), Some(value), None) => ReservePolicy::parse(kind, value),
+            _ => None,
+        };
+        let policy = policy.unwrap_or_else(|| {
+            self.console_logs.push(format!("[警告] 配置: 无效的核心保留策略 {}", config.sched_reserve));
+            self.scheduler.policy
+        });
+        if config.sched_nice != self.scheduler.lower_priority || policy != self.scheduler.policy {
+            let (scheduler, log) = AefrScheduler::new_or_inline(config.sched_nice, policy);
+            self.scheduler = scheduler;
+            self.console_logs.push(log);
+        }
+        self.sched_limit = self.scheduler.worker_count;
+        
+        self.texture_budget = (config.texture_budget_mb > 0).then_some(config.texture_budget_mb * 1024 * 1024);
+        self.load_retries = config.load_retries;
+        if (RENDER_SCALE_MIN..=RENDER_SCALE_MAX).contains(&config.render_scale) {
+            if config.render_scale != self.render_scale {
+                self.set_render_scale(ctx, config.render_scale);
             }
-        } else if cmd_lower.starts_with("bg ") {
-            // 格式: bg "path/to/bg.png"
-            let _ = tx.send(AppCommand::LoadBackground(
-                input_trimmed[3..].trim().replace("\"", "")
+        } else {
+            self.console_logs.push(format!(
+                "[警告] 配置: render_scale 应在 {}~{} 之间，收到 {}", RENDER_SCALE_MIN, RENDER_SCALE_MAX, config.render_scale,
             ));
         }
+        self.console_docked = config.console_docked;
+        self.unfocused_fps = config.unfocused_fps;
+        self.dialogue_theme = config.theme;
+        self.console_logs.push("[系统] 配置已应用".into());
+    }
+    
+    /// 将指定槽位角色的当前状态输出到日志（只读）
+    fn log_slot_status(&mut self, idx: usize) {
+        let line = match self.characters.get(idx) {
+            Some(Some(char_arc)) => match char_arc.lock() {
+                Ok(char) => format!(
+                    "[状态] 槽位 {}: 动画={} 循环={} 播放次数={} 时间={:.2}s 缩放={:.3} 位置=({:.1}, {:.1}) 皮肤={} 预乘Alpha={} 顶点={} 三角形={}{}",
+                    idx,
+                    char.animation_label(),
+                    char.current_looping(),
+                    if char.loop_target > 0 { 
+                        format!("{}/{}", char.loop_count, char.loop_target) 
+                    } else { 
+                        char.loop_count.to_string() 
+                    },
+                    char.current_track_time(),
+                    char.scale,
+                    char.position.x, char.position.y,
+                    char.current_skin_name().unwrap_or_else(|| "(默认)".into()),
+                    char.premultiplied_alpha,
+                    char.mesh_stats().0, char.mesh_stats().1,
+                    match (char.visible, char.paused) {
+                        (true, false) => "",
+                        (false, false) => " [隐藏]",
+                        (true, true) => " [暂停]",
+                        (false, true) => " [隐藏+暂停]",
+                    },
+                ),
+                Err(_) => format!("[错误] 槽位 {} 状态锁定失败", idx),
+            },
+            _ => format!("[状态] 槽位 {}: (空)", idx),
+        };
+        self.console_logs.push(line);
     }
 
     /// 处理异步事件（命令模式）
     /// 从通道接收并处理工作线程发送的命令
     fn handle_async_events(&mut self, ctx: &egui::Context) {
         while let Ok(cmd) = self.rx.try_recv() {
+            // 针对角色槽位的命令统一校验，避免越界或空槽位时“什么都没发生”
+            if let Some((idx, require_character)) = cmd.target_slot() {
+                if let Err(e) = self.check_slot(idx, require_character) {
+                    self.console_logs.push(format!("[错误] {}，命令已忽略", e));
+                    continue;
+                }
+            }
             match cmd {
                 // 更新对话
-                AppCommand::Dialogue { name, affiliation, content } => {
+                AppCommand::Dialogue { name, affiliation, content, plate, voice } => {
                     let scene = &mut self.scenario.scenes[self.current_scene_idx];
                     scene.speaker_name = name; 
                     scene.speaker_aff = affiliation; 
+                    scene.speaker_plate = plate;
+                    scene.voice_path = voice;
                     scene.dialogue_content = content;
                     self.sync_scene_to_ui();  // 立即应用
                 }
@@ -884,86 +6522,562 @@ impl AefrApp {
                 
                 // 请求加载Spine资源
                 AppCommand::RequestLoad { slot_idx, path } => {
+                    // 资源缓存：同一路径已有角色在使用时直接共享，不再读取文件与上传纹理
+                    // 源文件已被修改（未开启 watch 时重新导出）则丢弃缓存项，按新文件加载
+                    if self.asset_cache.get(&path).is_some_and(SharedAsset::is_stale) {
+                        self.asset_cache.remove(&path);
+                        self.console_logs.push(format!("[系统] 资源文件已修改，重新加载: {}", path));
+                    }
+                    if let Some(asset) = self.asset_cache.get(&path) {
+                        match SpineObject::from_shared(asset, &path) {
+                            Ok((obj, anims)) => {
+                                self.console_logs.push(format!("[性能] 槽位 {} 复用已加载的资源: {}", slot_idx, path));
+                                let _ = self.tx.send(AppCommand::LoadSuccess(slot_idx, Box::new(obj), LoadedTexture::Shared, anims));
+                                continue;
+                            }
+                            Err(e) => self.console_logs.push(format!("[警告] 资源缓存不可用，重新加载: {}", e)),
+                        }
+                    }
+                    
                     let tx_cb = self.tx.clone(); 
                     self.console_logs.push(format!("[解析] {}", path));
                     
                     // 在工作线程中加载（避免阻塞UI）
+                    // 失败时按退避间隔重试：导出工具分步写文件、网络抖动等暂时性错误通常很快恢复
                     let path_clone = path.clone();
+                    let pma = self.pma_overrides.get(&path).copied();
+                    let retries = self.load_retries;
                     thread::spawn(move || {
-                        match SpineObject::load_async_no_gpu(&path_clone) {
-                            Ok((obj, img, page, anims)) => { 
-                                let _ = tx_cb.send(AppCommand::LoadSuccess(
-                                    slot_idx, Box::new(obj), img, page, anims
-                                )); 
-                            },
-                            Err(e) => { 
-                                let _ = tx_cb.send(AppCommand::Log(
-                                    format!("[错误] {}", e)
-                                )); 
+                        let mut attempt = 0;
+                        loop {
+                            match SpineObject::load_async_no_gpu(&path_clone, pma) {
+                                Ok((obj, img, page, anims)) => { 
+                                    let _ = tx_cb.send(AppCommand::LoadSuccess(
+                                        slot_idx, Box::new(obj), LoadedTexture::Decoded(img, page), anims
+                                    )); 
+                                },
+                                Err(e) if attempt < retries => {
+                                    let delay = LOAD_RETRY_BASE_MS << attempt.min(6);  // 间隔上限约 19 秒
+                                    attempt += 1;
+                                    let _ = tx_cb.send(AppCommand::Log(format!(
+                                        "[警告] 槽位 {} 加载失败，{}ms 后重试 ({}/{}): {}", slot_idx, delay, attempt, retries, e,
+                                    )));
+                                    thread::sleep(Duration::from_millis(delay));
+                                    continue;
+                                }
+                                Err(e) => { 
+                                    let _ = tx_cb.send(AppCommand::LoadFailed(slot_idx, e)); 
+                                }
                             }
+                            break;
                         }
                     });
                 }
                 
+                // Spine资源加载失败
+                AppCommand::LoadFailed(idx, e) => {
+                    self.pending_reloads.remove(&idx);  // 允许下一次文件变化再次触发热重载
+                    if self.pending_restores.remove(&idx).is_some() {
+                        self.console_logs.push(format!("[警告] 撤销/重做: 槽位 {} 无法重新加载", idx));
+                        self.undo_base = self.stage_edit_snapshot();
+                    }
+                    match e.strip_prefix(NETWORK_ERROR_PREFIX) {
+                        Some(net) => self.console_logs.push(format!("[错误] 网络: 槽位 {} 下载失败: {}", idx, net)),
+                        None => self.console_logs.push(format!("[错误] {}", e)),
+                    }
+                    if self.loadall_pending.remove(&idx).is_some() {
+                        self.loadall_result.1 += 1;
+                        self.console_logs.push(format!("[错误] LOADALL: 槽位 {} 加载失败", idx));
+                        self.finish_loadall_if_done();
+                    }
+                }
+                
                 // Spine资源加载成功
-                AppCommand::LoadSuccess(idx, obj, color_image, page_name, anims) => {
+                AppCommand::LoadSuccess(idx, obj, texture, anims) => {
+                    // 超出显存预算时先回收最久未绘制角色的纹理（共享纹理不占用新的显存）
+                    let incoming = match &texture {
+                        LoadedTexture::Decoded(color_image, _) => color_image.pixels.len() * 4,
+                        LoadedTexture::Shared => 0,
+                    };
+                    if incoming > 0 && idx < self.characters.len() {
+                        self.make_texture_room(incoming, idx);
+                    }
+                    
+                    // 静态立绘导出：没有动画也照常显示初始姿势
+                    if anims.is_empty() {
+                        self.console_logs.push(format!("[警告] 槽位 {} 的骨骼不含任何动画，按静态立绘显示初始姿势", idx));
+                    }
+                    
                     if let Some(slot) = self.characters.get_mut(idx) {
                         let mut loaded = *obj;
                         
-                        // 在主线程中加载纹理到GPU
-                        let handle = ctx.load_texture(
-                            page_name, 
-                            color_image, 
-                            egui::TextureOptions::LINEAR
-                        );
+                        // 在主线程中加载纹理到GPU，并登记到资源缓存供其他槽位共享
+                        if let LoadedTexture::Decoded(color_image, page_name) = texture {
+                            let handle = ctx.load_texture(
+                                page_name, 
+                                color_image, 
+                                self.texture_options
+                            );
+                            
+                            loaded.texture_bytes = incoming;
+                            loaded.texture_id = Some(handle.id()); 
+                            loaded._texture = Some(handle);
+                            if let Some(asset) = loaded.shared_asset() {
+                                self.asset_cache.insert(loaded.source_path.clone(), asset);
+                            }
+                        }
+                        
+                        // 该资源曾用 coord 修正过时，沿用修正结果
+                        if let Some(coord) = self.coord_presets.get(&loaded.source_path) {
+                            loaded.coord = *coord;
+                        }
+                        
+                        // 热重载：沿用旧对象的位置、缩放与当前动画
+                        if self.pending_reloads.remove(&idx) {
+                            if let Some(Ok(old)) = slot.as_ref().map(|c| c.lock()) {
+                                loaded.position = old.position;
+                                loaded.scale = old.scale;
+                                loaded.user_offset = old.user_offset;
+                                loaded.user_scale = old.user_scale;
+                                loaded.placement = old.placement;
+                                loaded.anchor = old.anchor;
+                                loaded.shadow = old.shadow;
+                                loaded.fit_height = old.fit_height;
+                                loaded.alpha = old.alpha;
+                                loaded.visible = old.visible;
+                                loaded.paused = old.paused;
+                                loaded.rotation = old.rotation;
+                                loaded.coord = old.coord;
+                                if let Some(anim) = old.current_animation_name() {
+                                    loaded.set_animation_by_name(0, &anim, old.current_looping());
+                                }
+                            }
+                            self.console_logs.push(format!("[热重载] 槽位 {} 已重新加载", idx));
+                        }
+                        
+                        // 撤销/重做：重新加载后写回快照中的属性
+                        if let Some(snapshot) = self.pending_restores.remove(&idx) {
+                            loaded.apply_edit_snapshot(&snapshot);
+                        }
+                        self.undo_dirty = true;
+                        
+                        // 等待中的登场过渡
+                        if let Some((offset, duration)) = self.pending_enters.remove(&idx) {
+                            loaded.start_transition((offset, 0.0), (Vec2::ZERO, 1.0), duration, false);
+                        }
+                        
+                        // 监视中的槽位：以新对象的源文件重建快照
+                        if self.watched_slots.contains_key(&idx) {
+                            self.watched_slots.insert(idx, snapshot_mtimes(&loaded.source_files));
+                        }
+                        
+                        // 同一角色重新加载（热重载/读档后加载）时恢复当前幕记录的隐藏插槽与动画过渡，换角色则清空
+                        let scene = &mut self.scenario.scenes[self.current_scene_idx];
+                        if scene.char_paths[idx].as_deref() == Some(loaded.source_path.as_str()) {
+                            for name in &scene.char_hidden_slots[idx] {
+                                if let Err(e) = loaded.set_slot_hidden(name, true) {
+                                    self.console_logs.push(format!("[警告] 槽位 {}: {}，已忽略", idx, e));
+                                }
+                            }
+                            for (from, to, seconds) in &scene.char_mixes[idx] {
+                                if let Err(e) = loaded.set_mix(from, to, *seconds) {
+                                    self.console_logs.push(format!("[警告] 槽位 {}: {}，已忽略", idx, e));
+                                }
+                            }
+                            scene.char_hidden_slots[idx] = loaded.hidden_slot_names();
+                            scene.char_mixes[idx] = loaded.mix_rules.clone();
+                        } else {
+                            scene.char_hidden_slots[idx].clear();
+                            scene.char_mixes[idx].clear();
+                        }
                         
-                        loaded.texture_id = Some(handle.id()); 
-                        loaded._texture = Some(handle);
+                        // 记录到当前幕，便于保存与查询
+                        scene.char_paths[idx] = Some(loaded.source_path.clone());
                         
                         // 🌟 用Arc<Mutex>包装，确保线程安全
+                        // 新纹理已在上方上传完成，旧对象（及其纹理）在替换后才释放，不会闪烁
                         *slot = Some(Arc::new(Mutex::new(loaded)));
                     }
+                    self.prune_asset_cache();
+                    
+                    // LOADALL：角色就位后套用清单中的属性
+                    if let Some(entry) = self.loadall_pending.remove(&idx) {
+                        self.apply_loadall_entry(entry);
+                    }
                 }
                 
                 // 移除角色
                 AppCommand::RemoveCharacter(idx) => { 
                     self.characters[idx] = None; 
+                    self.watched_slots.remove(&idx);
+                    self.props.retain(|p| p.slot_idx != idx);
+                    self.name_tags.remove(&idx);
+                    self.scenario.scenes[self.current_scene_idx].char_paths[idx] = None;
+                    self.scenario.scenes[self.current_scene_idx].char_hidden_slots[idx].clear();
+                    self.scenario.scenes[self.current_scene_idx].char_mixes[idx].clear();
+                    self.undo_dirty = true;
+                    self.prune_asset_cache();
+                }
+                
+                // 加载背景图片
+                AppCommand::LoadBackground(path) => {
+                    let tx_cb = self.tx.clone();
+                    let path_clone = path.clone();
+                    
+                    thread::spawn(move || {
+                        match decode_background_image(&path_clone) {
+                            Ok(c_img) => { let _ = tx_cb.send(AppCommand::LoadBackgroundSuccess(c_img)); }
+                            Err(e) => { let _ = tx_cb.send(AppCommand::Log(format!("[错误] {}", e))); }
+                        }
+                    });
+                    
+                    let scene = &mut self.scenario.scenes[self.current_scene_idx];
+                    scene.bg_path = Some(path);
+                    scene.bg_anim_fps = None;
+                }
+                
+                // 加载序列帧背景：在工作线程中解码全部帧
+                AppCommand::LoadAnimatedBackground { dir, fps } => {
+                    let tx_cb = self.tx.clone();
+                    let dir_clone = dir.clone();
+                    self.console_logs.push(format!("[解析] 序列帧背景 {}", dir));
+                    
+                    thread::spawn(move || {
+                        let mut paths = match collect_frame_paths(&dir_clone) {
+                            Ok(paths) => paths,
+                            Err(e) => {
+                                let _ = tx_cb.send(AppCommand::Log(format!("[错误] {}", e)));
+                                return;
+                            }
+                        };
+                        if paths.len() > BG_ANIM_MAX_FRAMES {
+                            let _ = tx_cb.send(AppCommand::Log(format!(
+                                "[警告] 序列帧共 {} 帧，超过上限 {}，多余帧已忽略", paths.len(), BG_ANIM_MAX_FRAMES
+                            )));
+                            paths.truncate(BG_ANIM_MAX_FRAMES);
+                        }
+                        
+                        let mut frames = Vec::with_capacity(paths.len());
+                        for p in &paths {
+                            match image::open(p) {
+                                Ok(img) => frames.push(egui::ColorImage::from_rgba_unmultiplied(
+                                    [img.width() as _, img.height() as _], 
+                                    img.to_rgba8().as_raw()
+                                )),
+                                Err(e) => {
+                                    let _ = tx_cb.send(AppCommand::Log(format!("[错误] 帧 {} 解码失败: {}", p.display(), e)));
+                                    return;
+                                }
+                            }
+                        }
+                        let _ = tx_cb.send(AppCommand::LoadAnimatedBackgroundSuccess(frames, fps));
+                    });
+                    
+                    let scene = &mut self.scenario.scenes[self.current_scene_idx];
+                    scene.bg_path = Some(dir);
+                    scene.bg_anim_fps = Some(fps);
+                }
+                
+                // 序列帧背景加载成功：上传全部帧纹理
+                AppCommand::LoadAnimatedBackgroundSuccess(images, fps) => {
+                    let options = egui::TextureOptions { wrap_mode: egui::TextureWrapMode::Repeat, ..self.texture_options };
+                    let frames: Vec<TextureHandle> = images.into_iter()
+                        .enumerate()
+                        .map(|(i, img)| ctx.load_texture(format!("bg_{}", i), img, options))
+                        .collect();
+                    self.console_logs.push(format!("[系统] 序列帧背景已加载: {} 帧 @ {} fps", frames.len(), fps));
+                    self.background = Some(Background::Animated { frames, fps, time: 0.0 });
+                }
+                
+                // 绑定骨骼道具：先校验骨骼，再在后台解码贴图
+                AppCommand::AttachProp { slot_idx, bone, path } => {
+                    let bone_exists = match self.characters.get(slot_idx) {
+                        Some(Some(char_arc)) => char_arc.lock().map(|c| c.world_point_of_bone(&bone).is_some()).unwrap_or(false),
+                        _ => {
+                            self.console_logs.push(format!("[错误] ATTACH: 槽位 {} 没有角色", slot_idx));
+                            continue;
+                        }
+                    };
+                    if !bone_exists {
+                        self.console_logs.push(format!("[错误] ATTACH: 槽位 {} 骨骼不存在: {}", slot_idx, bone));
+                        continue;
+                    }
+                    
+                    let tx_cb = self.tx.clone();
+                    thread::spawn(move || {
+                        match image::open(&path) {
+                            Ok(img) => {
+                                let image = egui::ColorImage::from_rgba_unmultiplied(
+                                    [img.width() as _, img.height() as _], 
+                                    img.to_rgba8().as_raw()
+                                );
+                                let _ = tx_cb.send(AppCommand::PropReady { slot_idx, bone, path, image });
+                            }
+                            Err(e) => {
+                                let _ = tx_cb.send(AppCommand::Log(format!("[错误] ATTACH: 无法读取贴图 {}: {}", path, e)));
+                            }
+                        }
+                    });
+                }
+                
+                // 道具贴图就绪：同一骨骼上的旧道具被替换
+                AppCommand::PropReady { slot_idx, bone, path, image } => {
+                    let texture = ctx.load_texture(format!("prop_{}_{}", slot_idx, bone), image, self.texture_options);
+                    self.props.retain(|p| !(p.slot_idx == slot_idx && p.bone == bone));
+                    self.console_logs.push(format!("[系统] 槽位 {} 骨骼 {} 已绑定道具: {}", slot_idx, bone, path));
+                    self.props.push(Prop { slot_idx, bone, path, texture });
+                }
+                
+                // 解除骨骼道具
+                AppCommand::DetachProp { slot_idx, bone } => {
+                    let before = self.props.len();
+                    self.props.retain(|p| !(p.slot_idx == slot_idx && p.bone == bone));
+                    if self.props.len() == before {
+                        self.console_logs.push(format!("[警告] 槽位 {} 骨骼 {} 上没有道具", slot_idx, bone));
+                    }
+                }
+                
+                // 背景图片加载成功
+                AppCommand::LoadBackgroundSuccess(c_img) => {
+                    self.background = Some(Background::Static(ctx.load_texture(
+                        "bg", 
+                        c_img, 
+                        // 背景始终以重复方式环绕，供平铺模式使用
+                        egui::TextureOptions { wrap_mode: egui::TextureWrapMode::Repeat, ..self.texture_options }
+                    )));
+                }
+                
+                // 叠加背景图层：先占位保证图层顺序，解码完成后填入纹理
+                AppCommand::AddBackgroundLayer(path) => {
+                    self.spawn_bg_layer_decode(path.clone());
+                    self.bg_layers.push((path.clone(), None));
+                    self.scenario.scenes[self.current_scene_idx].bg_layers.push(path);
+                }
+                
+                // 背景图层解码完成
+                AppCommand::BackgroundLayerReady(path, image) => {
+                    let options = egui::TextureOptions { wrap_mode: egui::TextureWrapMode::Repeat, ..self.texture_options };
+                    if let Some((_, tex)) = self.bg_layers.iter_mut().find(|(p, tex)| *p == path && tex.is_none()) {
+                        *tex = Some(ctx.load_texture(format!("bg_layer:{}", path), image, options));
+                    }
+                }
+                
+                // 清除背景图层
+                AppCommand::ClearBackgroundLayers => {
+                    self.bg_layers.clear();
+                    self.scenario.scenes[self.current_scene_idx].bg_layers.clear();
+                }
+                
+                // 设置动画
+                AppCommand::SetAnimation { slot_idx, track, anim_name, loop_anim } => {
+                     if let Some(Some(char_arc)) = self.characters.get(slot_idx) {
+                         if let Ok(mut char) = char_arc.lock() {
+                             if !char.set_animation_by_name(track, &anim_name, loop_anim) {
+                                 self.console_logs.push(format!("[错误] 槽位 {} {}", slot_idx, char.missing_animation(&anim_name)));
+                             }
+                         }
+                     }
+                }
+                
+                // 设置轨道混合参数
+                AppCommand::SetTrackMix { slot_idx, track, mix } => {
+                    if let Some(Some(char_arc)) = self.characters.get(slot_idx) {
+                        if let Ok(mut char) = char_arc.lock() {
+                            char.set_track_mix(track, mix);
+                        }
+                    }
+                }
+                
+                // 清空动画轨道
+                AppCommand::ClearTrack { slot_idx, track } => {
+                    if let Some(Some(char_arc)) = self.characters.get(slot_idx) {
+                        if let Ok(mut char) = char_arc.lock() {
+                            char.clear_track(track);
+                        }
+                    }
+                }
+                
+                // 窗口控制
+                AppCommand::Viewport(cmd) => {
+                    ctx.send_viewport_cmd(cmd);
+                }
+                
+                // 交替播放
+                AppCommand::SetPingPong { slot_idx, pair } => {
+                    if let Some(Some(char_arc)) = self.characters.get(slot_idx) {
+                        if let Ok(mut char) = char_arc.lock() {
+                            if let Err(e) = char.set_pingpong(pair) {
+                                self.console_logs.push(format!("[错误] 槽位 {} {}", slot_idx, e));
+                            }
+                        }
+                    }
+                }
+                
+                // 应用配置
+                AppCommand::ApplyConfig(config) => self.apply_config(ctx, *config),
+                
+                // 渲染缩放
+                AppCommand::SetRenderScale(factor) => self.set_render_scale(ctx, factor),
+                
+                // 隐藏/显示角色
+                AppCommand::SetVisible { slot_idx, visible, pause } => {
+                    if let Some(Some(char_arc)) = self.characters.get(slot_idx) {
+                        if let Ok(mut char) = char_arc.lock() {
+                            char.visible = visible;
+                            if let Some(pause) = pause {
+                                char.paused = pause;
+                            }
+                            self.console_logs.push(format!(
+                                "[系统] 槽位 {} 已{}{}",
+                                slot_idx,
+                                if visible { "显示" } else { "隐藏" },
+                                if char.paused { "（动画暂停）" } else { "" },
+                            ));
+                        }
+                    }
+                }
+                
+                // 重置到初始姿势
+                AppCommand::ResetToSetup { slot_idx, restart } => {
+                    if let Some(Some(char_arc)) = self.characters.get(slot_idx) {
+                        if let Ok(mut char) = char_arc.lock() {
+                            char.reset_to_setup(restart);
+                            self.console_logs.push(format!("[系统] 槽位 {} 已重置到初始姿势", slot_idx));
+                        }
+                    }
+                }
+                
+                // 覆盖骨骼旋转
+                AppCommand::SetBoneRotation { slot_idx, bone_name, degrees } => {
+                    if let Some(Some(char_arc)) = self.characters.get(slot_idx) {
+                        if let Ok(mut char) = char_arc.lock() {
+                            if !char.set_bone_rotation(&bone_name, degrees) {
+                                self.console_logs.push(format!("[错误] 槽位 {} 不存在骨骼: {}", slot_idx, bone_name));
+                            }
+                        }
+                    }
+                }
+                
+                // 设置角色不透明度
+                AppCommand::SetAlpha { slot_idx, alpha } => {
+                    if let Some(Some(char_arc)) = self.characters.get(slot_idx) {
+                        if let Ok(mut char) = char_arc.lock() {
+                            char.alpha = alpha.clamp(0.0, 1.0);
+                        }
+                    }
+                }
+                
+                // 设置角色旋转
+                AppCommand::SetRotation { slot_idx, degrees } => {
+                    if let Some(Some(char_arc)) = self.characters.get(slot_idx) {
+                        if let Ok(mut char) = char_arc.lock() {
+                            char.rotation = degrees;
+                        }
+                    }
+                }
+                
+                // 修正坐标翻转，并按资源路径记住
+                AppCommand::SetCoord { slot_idx, flip_x, flip_y } => {
+                    if let Some(Some(char_arc)) = self.characters.get(slot_idx) {
+                        if let Ok(mut char) = char_arc.lock() {
+                            if let Some(v) = flip_x { char.coord.flip_x = v; }
+                            if let Some(v) = flip_y { char.coord.flip_y = v; }
+                            if char.coord == CoordFlags::default() {
+                                self.coord_presets.remove(&char.source_path);
+                            } else {
+                                self.coord_presets.insert(char.source_path.clone(), char.coord);
+                            }
+                            self.console_logs.push(format!(
+                                "[状态] 槽位 {} 坐标: flipx={} flipy={}",
+                                slot_idx,
+                                if char.coord.flip_x { "on" } else { "off" },
+                                if char.coord.flip_y { "on" } else { "off" },
+                            ));
+                        }
+                    }
+                }
+                
+                // 替换附件
+                AppCommand::SetAttachment { slot_idx, slot_name, attachment_name } => {
+                    if let Some(Some(char_arc)) = self.characters.get(slot_idx) {
+                        if let Ok(mut char) = char_arc.lock() {
+                            if let Err(e) = char.set_attachment(&slot_name, attachment_name.as_deref()) {
+                                self.console_logs.push(format!("[错误] 槽位 {}: {}", slot_idx, e));
+                            }
+                        }
+                    }
+                }
+                
+                // 隐藏/显示插槽，并记录到当前幕以便 SAVE 后恢复
+                AppCommand::SetSlotHidden { slot_idx, slot_name, hidden } => {
+                    if let Some(Some(char_arc)) = self.characters.get(slot_idx) {
+                        if let Ok(mut char) = char_arc.lock() {
+                            match char.set_slot_hidden(&slot_name, hidden) {
+                                Ok(()) => self.scenario.scenes[self.current_scene_idx].char_hidden_slots[slot_idx] = char.hidden_slot_names(),
+                                Err(e) => self.console_logs.push(format!("[错误] 槽位 {}: {}", slot_idx, e)),
+                            }
+                        }
+                    }
+                }
+                
+                // 设置动画对过渡时长，并记录到当前幕以便 SAVE 后恢复
+                AppCommand::SetMix { slot_idx, from, to, seconds } => {
+                    if let Some(Some(char_arc)) = self.characters.get(slot_idx) {
+                        if let Ok(mut char) = char_arc.lock() {
+                            match char.set_mix(&from, &to, seconds) {
+                                Ok(()) => self.scenario.scenes[self.current_scene_idx].char_mixes[slot_idx] = char.mix_rules.clone(),
+                                Err(e) => self.console_logs.push(format!("[错误] 槽位 {}: {}", slot_idx, e)),
+                            }
+                        }
+                    }
+                }
+                
+                // 绑定/解除口型同步
+                AppCommand::SetLipSync(binding) => {
+                    self.clear_lip_sync();
+                    if let Some((slot_idx, mouth_slot, open_attachment, closed_attachment)) = binding {
+                        if let Some(Some(char_arc)) = self.characters.get(slot_idx) {
+                            if let Ok(mut char) = char_arc.lock() {
+                                // 借用附件替换的校验逻辑，确认插槽与附件均存在
+                                let check = char.set_attachment(&mouth_slot, Some(&open_attachment))
+                                    .and_then(|_| match &closed_attachment {
+                                        Some(c) => char.set_attachment(&mouth_slot, Some(c)),
+                                        None => Ok(()),
+                                    });
+                                char.clear_attachment_override(&mouth_slot);
+                                match check {
+                                    Ok(()) => {
+                                        self.console_logs.push(format!("[系统] 槽位 {} 口型同步已绑定: {}", slot_idx, mouth_slot));
+                                        self.lip_sync = Some(LipSyncBinding {
+                                            slot_idx, mouth_slot, open_attachment, closed_attachment, is_open: false,
+                                        });
+                                    }
+                                    Err(e) => self.console_logs.push(format!("[错误] 槽位 {}: {}", slot_idx, e)),
+                                }
+                            }
+                        }
+                    }
                 }
                 
-                // 加载背景图片
-                AppCommand::LoadBackground(path) => {
-                    let tx_cb = self.tx.clone();
-                    let path_clone = path.clone();
-                    
-                    thread::spawn(move || {
-                        if let Ok(img) = image::open(&path_clone) {
-                            let c_img = egui::ColorImage::from_rgba_unmultiplied(
-                                [img.width() as _, img.height() as _], 
-                                img.to_rgba8().as_raw()
-                            );
-                            let _ = tx_cb.send(AppCommand::LoadBackgroundSuccess(c_img));
+                // 限次播放动画
+                AppCommand::PlayAnimationTimes { slot_idx, track, anim_name, times, then_anim } => {
+                    if let Some(Some(char_arc)) = self.characters.get(slot_idx) {
+                        if let Ok(mut char) = char_arc.lock() {
+                            if let Err(e) = char.play_animation_times(track, &anim_name, times, then_anim.as_deref()) {
+                                self.console_logs.push(format!("[错误] 槽位 {}: {}", slot_idx, e));
+                            }
                         }
-                    });
-                    
-                    self.scenario.scenes[self.current_scene_idx].bg_path = Some(path);
+                    }
                 }
                 
-                // 背景图片加载成功
-                AppCommand::LoadBackgroundSuccess(c_img) => {
-                    self.background = Some(ctx.load_texture(
-                        "bg", 
-                        c_img, 
-                        egui::TextureOptions::LINEAR
-                    ));
+                // 动画播放完成
+                AppCommand::AnimComplete { slot_idx, anim_name } => {
+                    self.console_logs.push(format!("[事件] 槽位 {} 动画播放完成: {}", slot_idx, anim_name));
                 }
                 
-                // 设置动画
-                AppCommand::SetAnimation { slot_idx, anim_name, loop_anim } => {
-                     if let Some(Some(char_arc)) = self.characters.get(slot_idx) {
-                         if let Ok(mut char) = char_arc.lock() {
-                             let _ = char.set_animation_by_name(&anim_name, loop_anim);
-                         }
-                     }
+                // 属性补间完成
+                AppCommand::TweenComplete { slot_idx, prop } => {
+                    self.console_logs.push(format!("[事件] 槽位 {} 补间完成: {}", slot_idx, prop));
                 }
                 
                 // 播放BGM
@@ -992,6 +7106,42 @@ impl AefrApp {
                     });
                 }
                 
+                // 按角色位置声像播放音效（BGM始终居中）
+                AppCommand::PlaySeAt { path, slot_idx } => {
+                    let width = ctx.screen_rect().width().max(1.0);
+                    let pan = match self.characters.get(slot_idx) {
+                        Some(Some(char_arc)) => char_arc.lock()
+                            .map_or(0.0, |c| (c.position.x / width) * 2.0 - 1.0),
+                        _ => 0.0,
+                    };
+                    let tx_cb = self.tx.clone();
+                    thread::spawn(move || { 
+                        if let Ok(d) = std::fs::read(&path) { 
+                            let _ = tx_cb.send(AppCommand::AudioReadyPanned(d, pan)); 
+                        } 
+                    });
+                }
+                
+                // 带声像的音效数据就绪
+                AppCommand::AudioReadyPanned(data, pan) => {
+                    if let Some(mgr) = &self.audio_manager { 
+                        mgr.play_positioned(data, pan);
+                    }
+                }
+                
+                // 打字音效就绪
+                AppCommand::TypeSfxReady(data) => {
+                    self.type_sfx = Some(data);
+                    self.console_logs.push("[系统] 打字音效已启用".into());
+                }
+                
+                // 台词语音就绪（仅播放当前台词的语音）
+                AppCommand::VoiceReady(generation, data) => {
+                    if let (Some(mgr), true) = (&self.audio_manager, generation == self.voice_generation) {
+                        mgr.play_voice(data);
+                    }
+                }
+                
                 // 音频数据就绪
                 AppCommand::AudioReady(data, is_bgm) => {
                     if let Some(mgr) = &self.audio_manager { 
@@ -1008,6 +7158,21 @@ impl AefrApp {
                     if let Some(mgr) = &self.audio_manager { 
                         mgr.stop_bgm(); 
                     } 
+                    self.clear_lip_sync();  // BGM停止时解除口型同步
+                }
+                
+                // 暂停BGM
+                AppCommand::PauseBgm => {
+                    if let Some(mgr) = &self.audio_manager { 
+                        mgr.pause_bgm(); 
+                    }
+                }
+                
+                // 继续BGM
+                AppCommand::ResumeBgm => {
+                    if let Some(mgr) = &self.audio_manager { 
+                        mgr.resume_bgm(); 
+                    }
                 }
                 
                 // 剧本加载完成
@@ -1027,140 +7192,667 @@ impl AefrApp {
 // 主应用循环实现
 // ============================================================================
 impl eframe::App for AefrApp {
+    /// 窗口清屏色：透明窗口时完全透明（实际底色由中央面板按 stage_color 绘制），
+    /// 否则与 eframe 默认清屏色一致
+    fn clear_color(&self, _visuals: &egui::Visuals) -> [f32; 4] {
+        if self.transparent_window {
+            egui::Rgba::TRANSPARENT.to_array()
+        } else {
+            Color32::from_rgba_unmultiplied(12, 12, 12, 180).to_normalized_gamma_f32()
+        }
+    }
+    
     /// 主更新循环，每帧调用
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // 0. 窗口最小化：只低频处理异步事件（加载结果等），跳过更新、并行计算与绘制
+        let (minimized, focused) = ctx.input(|i| (i.viewport().minimized == Some(true), i.focused));
+        if minimized {
+            if !self.suspended {
+                self.suspended = true;
+                self.console_logs.push("[性能] 窗口已最小化，暂停更新".into());
+            }
+            self.handle_async_events(ctx);
+            ctx.request_repaint_after(Duration::from_secs_f32(SUSPEND_POLL_INTERVAL));
+            return;
+        }
+        let throttled = !focused && self.unfocused_fps > 0;
+        let resumed = std::mem::take(&mut self.suspended);
+        if resumed {
+            self.console_logs.push("[性能] 窗口已恢复，继续更新".into());
+        }
+        
         // 1. 处理异步事件
         self.handle_async_events(ctx);
         
+        // 1.0 撤销检查点（编辑操作的结果已在上方生效）
+        let gesture_active = ctx.input(|i| i.pointer.any_down() || i.any_touches());
+        self.undo_checkpoint(gesture_active);
+        
         // 2. 获取帧时间
-        let dt = ctx.input(|i| i.stable_dt);
+        // 冻结时模拟时间为 0，设备检查/热重载等维护计时仍使用真实时间
+        // 从最小化恢复的第一帧限制帧时间，动画从暂停处平滑继续
+        let real_dt = ctx.input(|i| i.stable_dt);
+        let real_dt = if resumed { real_dt.min(RESUME_MAX_DT) } else { real_dt };
+        let dt = if self.frozen { 0.0 } else { real_dt };
+        
+        // 帧时间指数平滑（性能面板用，避免数字剧烈跳动）
+        // 恢复帧的真实间隔包含整段暂停时间，不计入平滑值；限帧期间的长帧也不让自适应调度误判为卡顿
+        if !resumed {
+            let raw_dt = ctx.input(|i| i.unstable_dt);
+            self.frame_time_avg += (raw_dt - self.frame_time_avg) * 0.1;
+            if !throttled {
+                self.adapt_scheduler(raw_dt);
+            }
+        }
+        
+        // 1.1 为新日志补记时间戳，并裁剪：丢弃最旧的条目，长时间运行时内存保持有界
+        let now = ctx.input(|i| i.time);
+        self.log_times.resize(self.console_logs.len(), now);
+        if self.console_logs.len() > self.log_limit {
+            let excess = self.console_logs.len() - self.log_limit;
+            self.console_logs.drain(..excess);
+            self.log_times.drain(..excess);
+        }
+        
+        // 2.0 快捷键（输入框获得焦点时不响应，避免输入命令时误触）
+        if !ctx.wants_keyboard_input() {
+            self.handle_shortcuts(ctx);
+        } else {
+            self.skip_held = false;
+        }
+        
+        // 2.1 音频设备热插拔检查
+        self.audio_check_timer += real_dt;
+        if self.audio_check_timer >= AUDIO_RETRY_INTERVAL {
+            self.audio_check_timer = 0.0;
+            self.check_audio_device();
+        }
+        
+        // 2.2 热重载：轮询被监视槽位的源文件
+        if !self.watched_slots.is_empty() {
+            self.watch_timer += real_dt;
+            if self.watch_timer >= WATCH_POLL_INTERVAL {
+                self.watch_timer = 0.0;
+                self.poll_watched_files();
+            }
+        }
+        
+        // 2.2.1 显存预算：纹理被回收的角色再次显示时按需重新加载
+        self.reload_evicted_textures();
+        
+        // 2.3 序列帧背景播放
+        if let Some(bg) = &mut self.background {
+            bg.advance(dt);
+        }
+        
+        // 2.4 口型同步（在骨骼更新前切换附件）
+        self.update_lip_sync();
+        
+        // 2.5 脚本回放：等待结束后连续执行，直到下一条 wait 或脚本结束
+        if !self.script_queue.is_empty() {
+            self.script_wait -= real_dt;
+            while self.script_wait <= 0.0 {
+                let Some(line) = self.script_queue.pop_front() else {
+                    self.console_logs.push("[系统] 脚本回放结束".into());
+                    break;
+                };
+                self.run_command(&line, 0);
+            }
+        }
+        
+        // 3. 更新打字机效果与"继续"指示器计时
+        if self.visible_count >= self.target_chars.len() {
+            self.indicator_timer += dt;
+        } else {
+            self.indicator_timer = 0.0;  // 下一句显示完毕时从"亮"开始闪烁
+        }
+        self.type_sfx_timer += dt;
+        
+        // 快进：立即显示整句，并按固定节奏连续推进到后续各幕（到最后一幕为止）
+        if self.skip_toggle || self.skip_held {
+            self.visible_count = self.target_chars.len();
+            if self.current_scene_idx + 1 < self.scenario.scenes.len() {
+                self.skip_timer += dt;
+                if self.skip_timer >= SKIP_SCENE_INTERVAL {
+                    self.skip_timer = 0.0;
+                    self.current_scene_idx += 1;
+                    self.sync_scene_to_ui();
+                    self.visible_count = self.target_chars.len();
+                }
+            }
+        } else {
+            self.skip_timer = 0.0;
+        }
         
-        // 3. 更新打字机效果
         if self.show_dialogue && self.visible_count < self.target_chars.len() {
             self.type_timer += dt;
             
             // 🌟 解决计时器精度漂移：使用减法而非归零
-            while self.type_timer >= TYPEWRITER_INTERVAL {
+            let mut typed_visible_char = false;
+            // 每个字符的间隔按字符类别与前一个字符（标点停顿）缩放
+            while let Some(&c) = self.target_chars.get(self.visible_count) {
+                let prev = self.visible_count.checked_sub(1).map(|i| self.target_chars[i]);
+                let delay = self.type_interval * self.type_pacing.factor(prev, c);
+                if self.type_timer < delay {
+                    break;
+                }
+                if !c.is_whitespace() {
+                    typed_visible_char = true;
+                }
                 self.visible_count += 1; 
-                self.type_timer -= TYPEWRITER_INTERVAL;
+                self.type_timer -= delay;
+            }
+            
+            // 打字音效：本帧有可见字符出现且距上次足够久时播放一声
+            if typed_visible_char && self.type_sfx_timer >= TYPE_SFX_MIN_INTERVAL {
+                if let (Some(mgr), Some(data)) = (&self.audio_manager, &self.type_sfx) {
+                    mgr.play_type_click(data);
+                    self.type_sfx_timer = 0.0;
+                }
+            }
+        }
+
+        // 3.0 电影黑边滑动与角色层交叉淡化
+        self.letterbox.2 += dt;
+        if let Some(snapshot) = &mut self.stage_snapshot {
+            snapshot.elapsed += dt;
+            if snapshot.elapsed >= snapshot.duration {
+                self.stage_snapshot = None;  // 淡化结束，释放旧角色与网格
             }
         }
 
+        // 3.1 属性补间（在布局前写入偏移/缩放/透明度）
+        if !self.tweens.is_empty() || !self.tween_queue.is_empty() {
+            self.update_tweens(dt);
+        }
+
         // 4. 计算屏幕缩放
+        // egui 0.27 不提供系统安全区信息，需通过 safearea 命令手动设置内边距
         let screen = ctx.screen_rect();
+        let safe = self.safe_insets.shrink_rect(screen);
         let scale_factor = screen.height() / BASE_HEIGHT;
         
-        // 5. 更新角色位置
+        // 4.1 画面震动偏移（作用于背景与角色）
+        let shake_offset = match &mut self.shake {
+            Some(shake) => {
+                shake.elapsed += dt;
+                let offset = shake.offset(self.rng_seed);
+                if shake.elapsed >= shake.duration {
+                    self.shake = None;
+                }
+                offset
+            }
+            None => Vec2::ZERO,
+        };
+        self.shake_offset = shake_offset;
+        
+        // 5. 更新角色位置（含登场/退场过渡）
+        // 参与自动排布的槽位（未手动定位），按槽位顺序决定阵型中的次序
+        let auto_slots: Vec<usize> = self.characters.iter().enumerate()
+            .filter(|(_, slot)| slot.as_ref()
+                .and_then(|c| c.lock().ok())
+                .is_some_and(|c| matches!(c.placement, StagePlacement::Auto)))
+            .map(|(i, _)| i)
+            .collect();
+        let mut exited_slots = Vec::new();
         for (i, slot) in self.characters.iter().enumerate() {
             if let Some(char_arc) = slot {
                 if let Ok(mut char) = char_arc.lock() {
-                    // 应用DPI缩放
-                    char.scale = CHAR_BASE_SCALE * scale_factor;
+                    // 应用缩放：指定 fitheight 时按屏幕高度比例换算，否则按基础高度做DPI缩放
+                    let base_scale = match (char.fit_height, char.natural_height) {
+                        (Some(fraction), Some(h)) if h > 0.0 => fraction * screen.height() / h,
+                        _ => CHAR_BASE_SCALE * scale_factor,
+                    };
+                    char.scale = base_scale * char.user_scale;
                     
-                    // 计算水平位置（等距分布）
-                    let x_percent = CHAR_X_START_PERCENT + (i as f32 * CHAR_X_STEP_PERCENT);
-                    char.position = Pos2::new(
-                        screen.width() * x_percent, 
-                        screen.bottom() + (30.0 * scale_factor)  // 底部留白
-                    );
+                    // 计算位置：水平方向在安全区内按比例定位，脚底默认贴合舞台底部
+                    // 比例位置随窗口尺寸重新计算，角色在缩放窗口后保持相对位置
+                    let foot_y = screen.bottom() + (30.0 * scale_factor);  // 底部留白
+                    let base = match char.placement {
+                        StagePlacement::Auto => {
+                            let rank = auto_slots.iter().position(|&s| s == i).unwrap_or(0);
+                            self.formation.place(rank, auto_slots.len(), safe, foot_y, screen.height())
+                        }
+                        StagePlacement::Relative(x, y) => Pos2::new(
+                            safe.left() + safe.width() * x,
+                            y.map_or(foot_y, |y| screen.top() + screen.height() * y),
+                        ),
+                        StagePlacement::Absolute(pos) => pos,
+                    };
+                    let (transition_offset, exited) = char.advance_transition(dt);
+                    if exited {
+                        exited_slots.push(i);
+                    }
+                    char.position = base + char.anchor_offset() + char.user_offset + transition_offset * screen.size() + shake_offset;
                 }
             }
         }
 
+        for idx in exited_slots {
+            let _ = self.tx.send(AppCommand::RemoveCharacter(idx));
+        }
+
         // 6. 🌟 并行更新所有角色的骨骼动画
         // 使用调度器确保不占用UI/音频线程资源
-        self.scheduler.run_parallel(|| {
-            // 使用Rayon并行迭代器
-            self.characters.par_iter().for_each(|slot| {
-                if let Some(char_arc) = slot { 
-                    // 获取Mutex锁（线程安全）
-                    if let Ok(mut char) = char_arc.lock() {
+        let profile_chars = self.profile_chars;
+        let characters = &self.characters;
+        let stage_rect = ctx.screen_rect();
+        let update_slot = |slot: &Option<Arc<Mutex<SpineObject>>>| {
+            if let Some(char_arc) = slot { 
+                // 获取Mutex锁（线程安全）
+                if let Ok(mut char) = char_arc.lock() {
+                    if profile_chars {
+                        let start = Instant::now();
                         char.update_parallel(dt);  // 并行计算骨骼变形
+                        char.last_update_time = start.elapsed();
+                    } else {
+                        char.update_parallel(dt);
+                    }
+                    
+                    // 完全位于舞台之外的角色不生成网格（仍照常推进动画）
+                    char.culled = !char.cull_bounds().intersects(stage_rect);
+                    if char.culled {
+                        char.prebuilt_shapes.clear();
+                    }
+                    
+                    // 紧接着在同一线程生成网格（顶点变换是CPU密集部分），UI线程只需提交
+                    else if char.texture_id.is_some() {
+                        let start = Instant::now();
+                        char.prebuilt_shapes = char.build_mesh();
+                        if profile_chars {
+                            char.last_build_time = start.elapsed();
+                        }
+                        if !char.prebuilt_shapes.is_empty() {
+                            char.last_painted = Some(start);
+                        }
                     }
                 }
+            }
+        };
+        // 只有一个角色时，投递到线程池的开销大于收益，直接在UI线程串行更新
+        let active = characters.iter().flatten().count();
+        self.parallel_used = self.scheduler.is_parallel() && active >= PARALLEL_MIN_CHARS;
+        if self.parallel_used {
+            // 按并发上限把槽位分组：组间并行，组内串行
+            let group_size = characters.len().div_ceil(self.effective_concurrency()).max(1);
+            self.parallel_time = self.scheduler.run_parallel(|| {
+                // 使用Rayon并行迭代器
+                characters.par_chunks(group_size).for_each(|group| group.iter().for_each(update_slot));
             });
-        });
+        } else {
+            let start = Instant::now();
+            characters.iter().for_each(update_slot);
+            self.parallel_time = start.elapsed();
+        }
+        
+        if profile_chars {
+            self.serial_time_sum = self.characters.iter().flatten()
+                .filter_map(|c| c.lock().ok().map(|c| c.last_update_time + c.last_build_time))
+                .sum();
+        }
+
+        // 6.1 转发动画完成事件（仅真正结束的播放，循环动画每轮都会触发）
+        for (i, slot) in self.characters.iter().enumerate() {
+            if let Some(char_arc) = slot {
+                if let Ok(mut char) = char_arc.lock() {
+                    for ev in char.take_completed_events() {
+                        if ev.finished {
+                            let _ = self.tx.send(AppCommand::AnimComplete { 
+                                slot_idx: i, 
+                                anim_name: ev.anim_name 
+                            });
+                        }
+                    }
+                }
+            }
+        }
 
         // 7. 主绘制区域
         egui::CentralPanel::default()
-            .frame(egui::Frame::none().fill(Color32::BLACK))  // 黑色背景
+            .frame(egui::Frame::none().fill(self.stage_color.unwrap_or(Color32::TRANSPARENT)))  // 舞台底色
             .show(ctx, |ui| {
                 let rect = ui.max_rect();
+                let safe_rect = self.safe_insets.shrink_rect(rect);  // 交互UI使用的区域
+                
+                // 舞台手势区域：最先注册，对话框/按钮等后注册的控件会优先获得输入，
+                // 控制台窗口位于更高图层，因此在它们上方的手势不会移动角色
+                let stage_resp = ui.interact(rect, ui.id().with("stage"), egui::Sense::drag());
                 
                 // 7.1 绘制背景
                 if let Some(bg) = &self.background {
-                    let img_size = bg.size_vec2();
-                    let scale = (rect.width() / img_size.x).max(rect.height() / img_size.y);
-                    ui.painter().image(
-                        bg.id(), 
-                        Rect::from_center_size(rect.center(), img_size * scale), 
-                        Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0)),  // 完整纹理
-                        Color32::WHITE
+                    let bg = bg.current();
+                    let (dest, uv) = self.bg_fit.placement(rect, bg.size_vec2());
+                    ui.painter().image(bg.id(), dest.translate(self.shake_offset), uv, Color32::WHITE);
+                }
+                
+                // 7.1.1 背景图层：与背景使用相同的适配方式，透明区域露出下层与舞台底色
+                for tex in self.bg_layers.iter().filter_map(|(_, tex)| tex.as_ref()) {
+                    let (dest, uv) = self.bg_fit.placement(rect, tex.size_vec2());
+                    ui.painter().image(tex.id(), dest.translate(self.shake_offset), uv, Color32::WHITE);
+                }
+                
+                // 7.2 绘制所有角色：相邻且共享纹理的网格合并为一次绘制
+                // 角色层（含道具）裁剪到舞台区域，拖出屏幕的部分不会溢出到其他区域；对话框与控制台使用各自的绘制器
+                let stage_painter = ui.painter().with_clip_rect(rect);
+                
+                // 脚下阴影：背景之上、所有角色之下
+                for char_arc in self.characters.iter().flatten() {
+                    let Ok(char) = char_arc.lock() else { continue };
+                    if let Some(mesh) = char.shadow_mesh() {
+                        stage_painter.add(Shape::mesh(mesh));
+                    }
+                }
+                
+                let (mut shapes, unbatched) = build_stage_shapes(&self.characters, true, self.focus_slot);
+                self.draw_call_stats = (unbatched, shapes.len());
+                self.mesh_totals = shapes.iter().fold((0, 0), |acc, shape| match shape {
+                    Shape::Mesh(m) => (acc.0 + m.vertices.len(), acc.1 + m.indices.len()),
+                    _ => acc,
+                });
+                
+                // 整体交叉淡化：旧阵容快照淡出，当前阵容淡入
+                if let Some(snapshot) = &self.stage_snapshot {
+                    let t = snapshot.progress();
+                    let mut old = snapshot.shapes.clone();
+                    fade_shapes(&mut old, 1.0 - t);
+                    fade_shapes(&mut shapes, t);
+                    stage_painter.extend(old);
+                }
+                stage_painter.extend(shapes);
+                
+                // 7.2.0 骨骼道具：贴图中心对齐骨骼位置，随角色缩放与不透明度变化
+                for prop in &self.props {
+                    let Some(Some(char_arc)) = self.characters.get(prop.slot_idx) else { continue };
+                    let Ok(char) = char_arc.lock() else { continue };
+                    if !char.visible {
+                        continue;
+                    }
+                    // 重新加载后骨骼可能已不存在，此时静默跳过
+                    let Some(center) = char.world_point_of_bone(&prop.bone) else { continue };
+                    let size = prop.texture.size_vec2() * char.scale;
+                    let tint = Color32::WHITE.gamma_multiply(char.alpha * char.transition_alpha);
+                    stage_painter.image(
+                        prop.texture.id(),
+                        Rect::from_center_size(center, size),
+                        Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0)),
+                        tint,
                     );
                 }
                 
-                // 7.2 绘制所有角色
-                for char_arc in self.characters.iter().flatten() { 
-                    if let Ok(mut char) = char_arc.lock() {
-                        char.paint(ui);  // 渲染Spine动画
+                // 7.2.0 头顶名牌：随角色移动，不进入对话框区域
+                if self.show_name_tags && !self.name_tags.is_empty() {
+                    let box_top = if self.show_dialogue {
+                        safe_rect.bottom() - self.dialogue_theme.box_height_for(safe_rect)
+                    } else {
+                        safe_rect.bottom()
+                    };
+                    let limit = Rect::from_min_max(safe_rect.min, Pos2::new(safe_rect.right(), box_top));
+                    for (&idx, text) in &self.name_tags {
+                        let Some(Some(char_arc)) = self.characters.get(idx) else { continue };
+                        let Ok(char) = char_arc.lock() else { continue };
+                        if char.is_visible() && !char.culled {
+                            draw_name_tag(ui, char.head_point(), text, limit);
+                        }
                     }
                 }
                 
+                // 7.2.0 后期叠加：角色之上、UI之下
+                if self.vignette_strength > 0.0 {
+                    ui.painter().add(Shape::mesh(build_vignette_mesh(rect, self.vignette_strength)));
+                }
+                if let Some(tint) = self.grade_tint {
+                    ui.painter().rect_filled(rect, 0.0, tint);
+                }
+                if !self.letterbox_above {
+                    draw_letterbox(ui, rect, self.letterbox_height());
+                }
+                
+                // 7.2.1 变换手柄（后于舞台注册，手柄上的拖动优先于舞台手势）
+                if self.show_gizmo {
+                    self.draw_gizmo(ui);
+                }
+                
+                // 7.2.2 对齐参考线（由上一次舞台手势更新）
+                self.draw_snap_overlay(ui, rect);
+                
                 // 7.3 绘制右上角按钮
-                draw_top_right_buttons(ui, rect, &mut self.is_auto_enabled);
+                draw_top_right_buttons(ui, safe_rect, &mut self.is_auto_enabled);
                 
                 // 7.4 绘制对话框
+                let mut dialogue_consumed = false;
                 if self.show_dialogue {
                     let committed_text: String = self.target_chars.iter().collect();
                     if !committed_text.trim().is_empty() {
                         let scene = &self.scenario.scenes[self.current_scene_idx];
-                        let text: String = self.target_chars.iter()
-                            .take(self.visible_count)
-                            .collect();
                         
                         // 绘制《蔚蓝档案》风格对话框
-                        if draw_ba_dialogue(
-                            ui, rect, 
+                        let (clicked, consumed) = draw_ba_dialogue(
+                            ui, safe_rect, 
+                            &self.dialogue_theme,
                             &scene.speaker_name, 
                             &scene.speaker_aff, 
-                            &text, 
-                            self.visible_count >= self.target_chars.len()  // 是否显示完成指示器
-                        ) { 
+                            scene.speaker_plate,
+                            &committed_text, 
+                            self.visible_count,
+                            // "继续"指示器：本句显示完毕且未开启自动播放时显示
+                            (self.visible_count >= self.target_chars.len() && !self.is_auto_enabled)
+                                .then_some(self.indicator_timer)
+                        );
+                        if clicked { 
                             // 点击对话框快速完成打字效果
                             self.visible_count = self.target_chars.len(); 
                         }
+                        dialogue_consumed = consumed;
                     }
                 }
                 
+                // 7.4.0 舞台手势：拖动移动 / 双指捏合缩放当前选中角色
+                // 在对话框之后处理：对话框消费了指针输入时不移动角色，角色只响应对话框之外的操作
+                if dialogue_consumed {
+                    self.drag_raw_offset = None;
+                    self.snap_guides = (None, None);
+                } else {
+                    self.handle_stage_gestures(ctx, &stage_resp);
+                }
+                
+                // 7.4.1 置于对话框之上的电影黑边
+                if self.letterbox_above {
+                    draw_letterbox(ui, rect, self.letterbox_height());
+                }
+                
+                // 7.4.2 坐标标尺
+                if let Some(step) = self.ruler_step {
+                    let selected = self.characters.get(self.selected_slot)
+                        .and_then(|slot| slot.as_ref())
+                        .and_then(|c| c.lock().ok().map(|c| (self.selected_slot, c.position, c.scale)));
+                    draw_ruler(ui, rect, step, ctx.pointer_hover_pos(), selected);
+                }
+                
+                // 7.4.2.1 演示模式 HUD
+                if self.demo_mode {
+                    self.draw_demo_hud(ui, safe_rect);
+                }
+                
+                // 7.4.3 冻结/快进提示
+                if self.skip_toggle || self.skip_held {
+                    ui.painter().text(
+                        Pos2::new(safe_rect.center().x, safe_rect.top() + 44.0),
+                        egui::Align2::CENTER_TOP,
+                        "⏩ 快进中",
+                        egui::FontId::proportional(18.0),
+                        Color32::from_rgb(255, 210, 120),
+                    );
+                }
+                if self.frozen {
+                    ui.painter().text(
+                        Pos2::new(safe_rect.center().x, safe_rect.top() + 20.0),
+                        egui::Align2::CENTER_TOP,
+                        "已冻结",
+                        egui::FontId::proportional(18.0),
+                        Color32::from_rgb(120, 200, 255),
+                    );
+                }
+                
                 // 7.5 控制台按钮
                 if ui.put(
-                    Rect::from_min_size(Pos2::new(10.0, 10.0), Vec2::new(60.0, 30.0)), 
+                    Rect::from_min_size(safe_rect.min + Vec2::new(10.0, 10.0), Vec2::new(60.0, 30.0)), 
                     egui::Button::new("CMD")
                 ).clicked() { 
                     self.console_open = !self.console_open;  // 切换控制台显示
                 }
                 
+                // 7.5.1 性能面板按钮
+                if ui.put(
+                    Rect::from_min_size(safe_rect.min + Vec2::new(80.0, 10.0), Vec2::new(60.0, 30.0)), 
+                    egui::Button::new("STATS")
+                ).clicked() { 
+                    self.show_stats = !self.show_stats;
+                }
+                if self.show_stats {
+                    draw_stats_overlay(ui, safe_rect.min, self);
+                }
+                
                 // 7.6 创作者面板（控制台）
-                if self.console_open { 
+                if self.console_open || self.console_docked { 
                     draw_creator_panel(ctx, self); 
                 }
             });
         
-        // 8. 请求下一帧重绘
-        ctx.request_repaint();
+        // 8. 请求下一帧重绘（失去焦点时按 unfocused_fps 限制帧率，有输入时 egui 仍会立即重绘）
+        if throttled {
+            ctx.request_repaint_after(Duration::from_secs_f32(1.0 / self.unfocused_fps as f32));
+        } else {
+            ctx.request_repaint();
+        }
     }
 }
 
 // ============================================================================
 // UI 组件函数
 // ============================================================================
+/// 生成整个角色层的绘制列表，返回 (合批后的Shape, 合批前的Mesh数)
+/// prebuilt 为 true 时取走计算线程预生成的网格（每帧绘制），否则按当前姿势重新生成（快照）
+/// focus 为焦点槽位时，其余角色做景深近似处理（见 defocus_shapes）
+fn build_stage_shapes(characters: &[Option<Arc<Mutex<SpineObject>>>], prebuilt: bool, focus: Option<usize>) -> (Vec<Shape>, usize) {
+    let mut shapes: Vec<Shape> = Vec::new();
+    let mut unbatched = 0;
+    for (idx, char_arc) in characters.iter().enumerate() {
+        let Some(char_arc) = char_arc else { continue };
+        if let Ok(mut char) = char_arc.lock() {
+            if char.texture_id.is_none() { 
+                continue;  // 纹理未就绪
+            }
+            let mut built = if prebuilt { std::mem::take(&mut char.prebuilt_shapes) } else { char.build_mesh() };
+            if focus.is_some_and(|f| f != idx) {
+                built = defocus_shapes(built);
+            }
+            for shape in built {
+                unbatched += 1;
+                push_batched(&mut shapes, shape);
+            }
+        }
+    }
+    (shapes, unbatched)
+}
+
+/// 非焦点角色的景深近似：egui 没有着色器模糊，这里只做
+/// 1. 颜色向灰度混合并略微压暗；2. 以两次小幅错位、半透明的绘制代替一次绘制，边缘变软
+/// 局限：不是真正的模糊（内部细节依然清晰，偏移过大会出现重影），且错位绘制使该角色的
+/// 顶点数与填充量翻倍，并让角色整体略微透明；目标只是拉开焦点层次
+fn defocus_shapes(shapes: Vec<Shape>) -> Vec<Shape> {
+    let mut out = Vec::with_capacity(shapes.len() * 2);
+    for offset in [Vec2::splat(-FOCUS_BLUR_OFFSET), Vec2::splat(FOCUS_BLUR_OFFSET)] {
+        for shape in &shapes {
+            let Shape::Mesh(mesh) = shape else { continue };
+            let mut mesh = mesh.clone();
+            for v in &mut mesh.vertices {
+                v.pos += offset;
+                // 预乘颜色：先在预乘空间向灰度混合，再整体乘以不透明度
+                let [r, g, b, a] = v.color.to_array().map(|c| c as f32);
+                let gray = r * 0.299 + g * 0.587 + b * 0.114;
+                let mix = |c: f32| (c + (gray - c) * FOCUS_DESATURATE) * FOCUS_DARKEN * FOCUS_TAP_ALPHA;
+                v.color = Color32::from_rgba_premultiplied(
+                    mix(r) as u8, mix(g) as u8, mix(b) as u8, (a * FOCUS_TAP_ALPHA) as u8,
+                );
+            }
+            out.push(Shape::mesh(mesh));
+        }
+    }
+    out
+}
+
+/// 按系数整体淡化Shape列表（顶点颜色为预乘格式，四个通道同比缩放）
+fn fade_shapes(shapes: &mut [Shape], factor: f32) {
+    for shape in shapes {
+        if let Shape::Mesh(mesh) = shape {
+            for v in &mut mesh.vertices {
+                v.color = v.color.gamma_multiply(factor);
+            }
+        }
+    }
+}
+
+/// 将Shape追加到绘制列表：与上一个Mesh使用同一纹理时合并，减少绘制调用
+/// 本管线的混合模式已编码在预乘顶点颜色中（加色混合 = Alpha 0），
+/// GPU 侧混合状态一致，因此纹理是唯一的合批约束；纹理不同时保持独立Mesh
+fn push_batched(shapes: &mut Vec<Shape>, shape: Shape) {
+    match (shapes.last_mut(), shape) {
+        (Some(Shape::Mesh(last)), Shape::Mesh(next)) if last.texture_id == next.texture_id => {
+            last.append(next);
+        }
+        (_, shape) => shapes.push(shape),
+    }
+}
+
+/// 绘制性能面板（左上角，仅文字，开销可忽略）
+fn draw_stats_overlay(ui: &mut egui::Ui, origin: Pos2, app: &AefrApp) {
+    let frame_ms = app.frame_time_avg * 1000.0;
+    let fps = if app.frame_time_avg > 0.0 { 1.0 / app.frame_time_avg } else { 0.0 };
+    let active = app.characters.iter().filter(|c| c.is_some()).count();
+    let mut text = format!(
+        "FPS {:.0} ({:.2} ms)\n工作线程 {}\n并行更新 {:.2} ms{}\n活动槽位 {}/{}\n顶点 {} / 索引 {}\n绘制调用 {} -> {}",
+        fps, frame_ms,
+        app.scheduler.worker_count,
+        app.parallel_time.as_secs_f64() * 1000.0,
+        if app.profile_chars { 
+            format!(" (串行合计 {:.2} ms)", app.serial_time_sum.as_secs_f64() * 1000.0) 
+        } else { 
+            String::new() 
+        },
+        active, app.characters.len(),
+        app.mesh_totals.0, app.mesh_totals.1,
+        app.draw_call_stats.0, app.draw_call_stats.1,
+    );
+    
+    // 逐角色网格规模，便于找出开销最大的角色
+    for (idx, slot) in app.characters.iter().enumerate() {
+        if let Some(Ok(char)) = slot.as_ref().map(|c| c.try_lock()) {
+            let (vertices, triangles) = char.mesh_stats();
+            text.push_str(&format!("\n  槽位 {}: 顶点 {} / 三角形 {}", idx, vertices, triangles));
+        }
+    }
+    
+    let galley = ui.painter().layout_no_wrap(
+        text, 
+        egui::FontId::monospace(14.0), 
+        Color32::from_rgb(120, 255, 120)
+    );
+    let pos = origin + Vec2::new(10.0, 50.0);
+    ui.painter().rect_filled(
+        Rect::from_min_size(pos, galley.size()).expand(6.0), 
+        4.0, 
+        Color32::from_black_alpha(180)
+    );
+    ui.painter().galley(pos, galley, Color32::WHITE);
+}
+
 /// 绘制右上角控制按钮（AUTO/MENU）
 fn draw_top_right_buttons(ui: &mut egui::Ui, screen: Rect, is_auto: &mut bool) {
     let (btn_w, btn_h, margin) = (90.0, 32.0, 20.0);
     
     // AUTO按钮
     let auto_rect = Rect::from_min_size(
-        Pos2::new(screen.right() - btn_w * 2.0 - margin - 10.0, margin), 
+        Pos2::new(screen.right() - btn_w * 2.0 - margin - 10.0, screen.top() + margin), 
         Vec2::new(btn_w, btn_h)
     );
     
@@ -1189,48 +7881,213 @@ fn draw_top_right_buttons(ui: &mut egui::Ui, screen: Rect, is_auto: &mut bool) {
         Color32::from_rgb(20, 30, 50)  // 深蓝色文字
     );
     
-    // MENU按钮（装饰性）
-    ui.painter().rect_filled(
-        Rect::from_min_size(
-            Pos2::new(screen.right() - btn_w - margin, margin), 
-            Vec2::new(btn_w, btn_h)
-        ), 
-        4.0, 
-        Color32::WHITE
-    );
+    // MENU按钮（装饰性）
+    ui.painter().rect_filled(
+        Rect::from_min_size(
+            Pos2::new(screen.right() - btn_w - margin, screen.top() + margin), 
+            Vec2::new(btn_w, btn_h)
+        ), 
+        4.0, 
+        Color32::WHITE
+    );
+    
+    ui.painter().text(
+        Pos2::new(screen.right() - btn_w / 2.0 - margin, screen.top() + margin + btn_h / 2.0), 
+        egui::Align2::CENTER_CENTER, 
+        "MENU", 
+        egui::FontId::proportional(18.0), 
+        Color32::from_rgb(20, 30, 50)
+    );
+}
+
+/// 绘制上下电影黑边（height 为每条黑边占屏幕高度的比例）
+fn draw_letterbox(ui: &egui::Ui, screen: Rect, height: f32) {
+    if height <= 0.0 {
+        return;
+    }
+    let bar_h = screen.height() * height;
+    let painter = ui.painter();
+    painter.rect_filled(Rect::from_min_size(screen.min, Vec2::new(screen.width(), bar_h)), 0.0, Color32::BLACK);
+    painter.rect_filled(
+        Rect::from_min_max(Pos2::new(screen.left(), screen.bottom() - bar_h), screen.max), 
+        0.0, 
+        Color32::BLACK
+    );
+}
+
+/// 拖动吸附：先吸附到其他角色的锚点（水平方向对齐中心、竖直方向对齐脚底基线），
+/// 未对齐的方向再吸附到网格；返回吸附后的位置与需要显示的参考线 (竖线x, 横线y)
+fn snap_position(
+    wanted: Pos2,
+    characters: &[Option<Arc<Mutex<SpineObject>>>],
+    dragging: usize,
+    grid: Option<f32>,
+) -> (Pos2, (Option<f32>, Option<f32>)) {
+    let mut best = (None::<f32>, None::<f32>);
+    let mut best_dist = (SNAP_GUIDE_DISTANCE, SNAP_GUIDE_DISTANCE);
+    for (idx, slot) in characters.iter().enumerate() {
+        if idx == dragging {
+            continue;
+        }
+        let Some(other) = slot.as_ref().and_then(|c| c.lock().ok()).map(|c| c.position) else { continue };
+        if (other.x - wanted.x).abs() <= best_dist.0 {
+            best_dist.0 = (other.x - wanted.x).abs();
+            best.0 = Some(other.x);
+        }
+        if (other.y - wanted.y).abs() <= best_dist.1 {
+            best_dist.1 = (other.y - wanted.y).abs();
+            best.1 = Some(other.y);
+        }
+    }
+    let to_grid = |v: f32| grid.map_or(v, |step| (v / step).round() * step);
+    let snapped = Pos2::new(best.0.unwrap_or_else(|| to_grid(wanted.x)), best.1.unwrap_or_else(|| to_grid(wanted.y)));
+    (snapped, best)
+}
+
+/// 角色头顶名牌：圆角底板 + 文字，底边中点对齐 anchor 上方，并限制在 limit 之内
+fn draw_name_tag(ui: &egui::Ui, anchor: Pos2, text: &str, limit: Rect) {
+    let painter = ui.painter();
+    let galley = painter.layout_no_wrap(text.to_string(), egui::FontId::proportional(18.0), Color32::WHITE);
+    let size = galley.size() + Vec2::new(20.0, 8.0);
+    let mut tag = Rect::from_min_size(Pos2::new(anchor.x - size.x / 2.0, anchor.y - size.y - 8.0), size);
+    
+    // 夹在可用区域内：头顶出屏时贴住上边，角色靠下时不压住对话框
+    let dx = (limit.left() - tag.left()).max(0.0) + (limit.right() - tag.right()).min(0.0);
+    let dy = (limit.top() - tag.top()).max(0.0) + (limit.bottom() - tag.bottom()).min(0.0);
+    tag = tag.translate(Vec2::new(dx, dy));
+    
+    painter.rect_filled(tag, 6.0, Color32::from_rgba_unmultiplied(12, 18, 28, 200));
+    painter.galley(tag.center() - galley.size() / 2.0, galley, Color32::WHITE);
+}
+
+/// 坐标标尺叠加层：浅色网格 + 像素坐标刻度 + 指针与选中角色信息
+/// 只有线段与少量文字，开启时的绘制开销可忽略；默认关闭
+fn draw_ruler(ui: &egui::Ui, screen: Rect, step: f32, pointer: Option<Pos2>, selected: Option<(usize, Pos2, f32)>) {
+    let painter = ui.painter();
+    let line = egui::Stroke::new(1.0, Color32::from_white_alpha(28));
+    let label_color = Color32::from_white_alpha(140);
+    let font = egui::FontId::monospace(10.0);
+    
+    let mut x = screen.left();
+    while x <= screen.right() {
+        painter.vline(x, screen.y_range(), line);
+        painter.text(Pos2::new(x + 2.0, screen.top() + 2.0), egui::Align2::LEFT_TOP, format!("{:.0}", x), font.clone(), label_color);
+        x += step;
+    }
+    let mut y = screen.top();
+    while y <= screen.bottom() {
+        painter.hline(screen.x_range(), y, line);
+        painter.text(Pos2::new(screen.left() + 2.0, y + 2.0), egui::Align2::LEFT_TOP, format!("{:.0}", y), font.clone(), label_color);
+        y += step;
+    }
+    
+    // 指针位置（像素与百分比，可直接写入 pos 命令）
+    let info_color = Color32::from_rgb(255, 220, 120);
+    if let Some(p) = pointer.filter(|p| screen.contains(*p)) {
+        let rel = (p - screen.min) / screen.size();
+        painter.text(
+            p + Vec2::new(12.0, 12.0),
+            egui::Align2::LEFT_TOP,
+            format!("({:.0}, {:.0})  {:.1}% {:.1}%", p.x, p.y, rel.x * 100.0, rel.y * 100.0),
+            egui::FontId::monospace(12.0),
+            info_color,
+        );
+    }
+    if let Some((slot, pos, scale)) = selected {
+        painter.circle_stroke(pos, 6.0, egui::Stroke::new(1.5, info_color));
+        painter.text(
+            pos + Vec2::new(10.0, -10.0),
+            egui::Align2::LEFT_BOTTOM,
+            format!("槽位 {}: ({:.0}, {:.0}) 缩放 {:.3}", slot, pos.x, pos.y, scale),
+            egui::FontId::monospace(12.0),
+            info_color,
+        );
+    }
+}
+
+/// 构建暗角网格：内椭圆透明、外椭圆（经过屏幕四角）为半透明黑色的环形渐变
+/// 顶点数固定（2 × 分段数），每帧仅一次小额分配
+fn build_vignette_mesh(screen: Rect, strength: f32) -> Mesh {
+    const SEGMENTS: u32 = 48;
+    const INNER_RATIO: f32 = 0.55;  // 透明区域占外椭圆的比例
+    
+    let center = screen.center();
+    let outer = screen.size() * 0.5 * std::f32::consts::SQRT_2;
+    let inner = outer * INNER_RATIO;
+    let clear = Color32::TRANSPARENT;
+    let dark = Color32::from_black_alpha((strength.clamp(0.0, 1.0) * 255.0) as u8);
+    
+    let mut mesh = Mesh::default();
+    mesh.vertices.reserve(SEGMENTS as usize * 2);
+    mesh.indices.reserve(SEGMENTS as usize * 6);
+    for i in 0..SEGMENTS {
+        let angle = i as f32 / SEGMENTS as f32 * std::f32::consts::TAU;
+        let dir = Vec2::new(angle.cos(), angle.sin());
+        mesh.vertices.push(Vertex { pos: center + dir * inner, uv: Pos2::ZERO, color: clear });
+        mesh.vertices.push(Vertex { pos: center + dir * outer, uv: Pos2::ZERO, color: dark });
+        
+        let (a, b) = (i * 2, ((i + 1) % SEGMENTS) * 2);
+        mesh.add_triangle(a, a + 1, b + 1);
+        mesh.add_triangle(a, b + 1, b);
+    }
+    mesh
+}
+
+/// 椭圆软阴影：中心最暗，经中间环衰减到外圈完全透明
+fn build_shadow_blob_mesh(center: Pos2, radius: Vec2, alpha: f32) -> Mesh {
+    const SEGMENTS: u32 = 32;
+    const MID_RATIO: f32 = 0.6;   // 中间环位置（半径比例）
+    const MID_ALPHA: f32 = 0.6;   // 中间环相对中心的不透明度
+    
+    let shade = |a: f32| Color32::from_black_alpha((a.clamp(0.0, 1.0) * 255.0) as u8);
+    let (dark, mid, clear) = (shade(alpha), shade(alpha * MID_ALPHA), Color32::TRANSPARENT);
     
-    ui.painter().text(
-        Pos2::new(screen.right() - btn_w / 2.0 - margin, margin + btn_h / 2.0), 
-        egui::Align2::CENTER_CENTER, 
-        "MENU", 
-        egui::FontId::proportional(18.0), 
-        Color32::from_rgb(20, 30, 50)
-    );
+    let mut mesh = Mesh::default();
+    mesh.vertices.reserve(1 + SEGMENTS as usize * 2);
+    mesh.indices.reserve(SEGMENTS as usize * 9);
+    mesh.vertices.push(Vertex { pos: center, uv: Pos2::ZERO, color: dark });
+    for i in 0..SEGMENTS {
+        let angle = i as f32 / SEGMENTS as f32 * std::f32::consts::TAU;
+        let dir = Vec2::new(angle.cos() * radius.x, angle.sin() * radius.y);
+        mesh.vertices.push(Vertex { pos: center + dir * MID_RATIO, uv: Pos2::ZERO, color: mid });
+        mesh.vertices.push(Vertex { pos: center + dir, uv: Pos2::ZERO, color: clear });
+        
+        let (a, b) = (1 + i * 2, 1 + ((i + 1) % SEGMENTS) * 2);
+        mesh.add_triangle(0, a, b);
+        mesh.add_triangle(a, a + 1, b + 1);
+        mesh.add_triangle(a, b + 1, b);
+    }
+    mesh
 }
 
 /// 绘制《蔚蓝档案》风格对话框
-/// 返回：是否被点击（用于快速完成打字效果）
+/// 返回：(是否被点击（用于快速完成打字效果）, 是否消费了本帧的指针输入)
+/// 输入优先级：对话框范围内的按下/拖动归对话框，调用方据此跳过舞台手势（角色拖动/缩放）
 fn draw_ba_dialogue(
     ui: &mut egui::Ui, 
     screen: Rect, 
+    theme: &DialogueTheme,
     name: &str, 
     affiliation: &str, 
+    plate: NamePlate,
     content: &str, 
-    is_finished: bool
-) -> bool {
+    visible_chars: usize,
+    indicator_time: Option<f32>
+) -> (bool, bool) {
     // 1. 计算对话框尺寸
-    let box_h = screen.height() * DIALOGUE_BOX_RATIO;
+    let box_h = theme.box_height_for(screen);
     let box_rect = Rect::from_min_max(
         Pos2::new(screen.left(), screen.bottom() - box_h), 
         screen.max
     );
     let line_y = box_rect.top() + (box_h * 0.30);  // 分隔线Y坐标
     
-    // 2. 绘制半透明深蓝色背景
-    let dark_blue_opaque = Color32::from_rgba_unmultiplied(12, 18, 28, 252);
+    // 2. 绘制半透明背景（默认深蓝色）
+    let [bg_r, bg_g, bg_b, bg_a] = theme.bg_color;
+    let dark_blue_opaque = Color32::from_rgba_unmultiplied(bg_r, bg_g, bg_b, bg_a);
     ui.painter().rect_filled(
         Rect::from_min_max(Pos2::new(screen.left(), line_y), screen.max), 
-        0.0, 
+        theme.corner_radius, 
         dark_blue_opaque
     );
     
@@ -1238,8 +8095,8 @@ fn draw_ba_dialogue(
     let gradient_rect = Rect::from_min_max(box_rect.left_top(), Pos2::new(screen.right(), line_y));
     let mut mesh = Mesh::default();
     
-    let color_bottom = Color32::from_rgba_unmultiplied(12, 18, 28, 245);  // 底部较实
-    let color_top = Color32::from_rgba_unmultiplied(12, 18, 28, 0);        // 顶部透明
+    let color_bottom = Color32::from_rgba_unmultiplied(bg_r, bg_g, bg_b, bg_a.min(245));  // 底部较实
+    let color_top = Color32::from_rgba_unmultiplied(bg_r, bg_g, bg_b, 0);                // 顶部透明
     
     // 渐变四边形顶点
     mesh.vertices.push(Vertex { pos: gradient_rect.left_top(), uv: Pos2::ZERO, color: color_top });
@@ -1252,71 +8109,99 @@ fn draw_ba_dialogue(
     mesh.add_triangle(0, 2, 3);
     ui.painter().add(Shape::mesh(mesh));
 
-    // 4. 对话框点击区域
-    let resp = ui.allocate_rect(box_rect, egui::Sense::click());
+    // 4. 对话框点击区域（同时感知拖动，避免在对话框上的拖动穿透到舞台手势）
+    let resp = ui.allocate_rect(box_rect, egui::Sense::click_and_drag());
     
     // 5. 绘制分隔线
-    let pad_x = (screen.width() * 0.08).max(100.0);
+    let pad_x = (screen.width() * 0.08).max(theme.padding);
     ui.painter().line_segment(
-        [Pos2::new(pad_x, line_y), Pos2::new(screen.right() - pad_x, line_y)], 
+        [Pos2::new(screen.left() + pad_x, line_y), Pos2::new(screen.right() - pad_x, line_y)], 
         Stroke::new(1.5, Color32::from_rgb(100, 120, 150))
     );
 
-    // 6. 绘制说话者名称和所属
+    // 6. 绘制说话者名牌（名称 + 所属）
     if !name.is_empty() {
-        let n_size = (box_h * 0.16).clamp(22.0, 30.0);
+        let n_size = theme.name_font_size.unwrap_or((box_h * 0.16).clamp(22.0, 30.0));
         let n_gal = ui.painter().layout_no_wrap(
             name.into(), 
             egui::FontId::proportional(n_size), 
             Color32::WHITE
         );
         
+        let [ar, ag, ab, aa] = theme.affiliation_color;
+        let aff_color = Color32::from_rgba_unmultiplied(ar, ag, ab, aa);
+        let aff_gal = (!affiliation.is_empty()).then(|| ui.painter().layout_no_wrap(
+            affiliation.into(), 
+            egui::FontId::proportional(n_size * theme.affiliation_scale), 
+            aff_color
+        ));
+        
         let n_width = n_gal.rect.width();
         let n_height = n_gal.rect.height();
-        let margin_bottom = 4.0;
-        let n_pos = Pos2::new(box_rect.left() + pad_x, line_y - n_height - margin_bottom);
-
-        // 绘制所属（如果存在）
-        if !affiliation.is_empty() {
-            let aff_size = n_size * 0.75;
-            let aff_gal = ui.painter().layout_no_wrap(
-                affiliation.into(), 
-                egui::FontId::proportional(aff_size), 
-                Color32::from_rgb(100, 200, 255)  // 青色
-            );
-            
-            let aff_height = aff_gal.rect.height();
-            let visual_compensation = 0.0;  // 视觉微调
-            let y_offset = n_height - aff_height + visual_compensation; 
-            
-            // 绘制名称
-            ui.painter().galley(n_pos, n_gal.clone(), Color32::WHITE);
-            
-            // 绘制所属（在名称右侧）
+        let aff_gap = 15.0;
+        let total_w = n_width + aff_gal.as_ref().map_or(0.0, |g| aff_gap + g.rect.width());
+        
+        // 名牌内边距与位置
+        let plate_pad = Vec2::new(n_height * 0.5, n_height * 0.12);
+        let margin_bottom = 4.0 + plate_pad.y;
+        let n_x = match plate.align {
+            PlateAlign::Left => box_rect.left() + pad_x,
+            PlateAlign::Center => box_rect.center().x - total_w * 0.5,
+            PlateAlign::Right => box_rect.right() - pad_x - total_w,
+        };
+        let n_pos = Pos2::new(n_x, line_y - n_height - margin_bottom);
+        
+        // 绘制胶囊形底板
+        let plate_rect = Rect::from_min_size(n_pos, Vec2::new(total_w, n_height)).expand2(plate_pad);
+        let [pr, pg, pb, pa] = plate.color.unwrap_or(theme.plate_color);
+        ui.painter().rect_filled(
+            plate_rect, 
+            plate_rect.height() * 0.5, 
+            Color32::from_rgba_unmultiplied(pr, pg, pb, pa)
+        );
+        
+        // 绘制名称
+        ui.painter().galley(n_pos, n_gal, Color32::WHITE);
+        
+        // 绘制所属（在名称右侧，底部对齐）
+        if let Some(aff_gal) = aff_gal {
+            let y_offset = n_height - aff_gal.rect.height(); 
             ui.painter().galley(
-                n_pos + Vec2::new(n_width + 15.0, y_offset), 
+                n_pos + Vec2::new(n_width + aff_gap, y_offset), 
                 aff_gal, 
-                Color32::from_rgb(100, 200, 255)
+                aff_color
             );
-        } else {
-            ui.painter().galley(n_pos, n_gal, Color32::WHITE);
         }
     }
     
     // 7. 绘制对话内容
-    ui.painter().text(
-        Pos2::new(box_rect.left() + pad_x, line_y + box_h * 0.05), 
-        egui::Align2::LEFT_TOP, 
-        content, 
-        egui::FontId::proportional((box_h * 0.13).clamp(18.0, 25.0)), 
-        Color32::WHITE
+    // 按完整文本排版换行（支持 \n，CJK 可在字间断行），未显示的字符以透明色绘制，
+    // 这样打字机推进时已显示的文字不会因换行位置变化而跳动
+    let font = egui::FontId::proportional(theme.content_font_size.unwrap_or((box_h * 0.13).clamp(18.0, 25.0)));
+    let text_area = Rect::from_min_max(
+        Pos2::new(box_rect.left() + pad_x, line_y + box_h * 0.05),
+        Pos2::new(box_rect.right() - pad_x, box_rect.bottom() - box_h * 0.08),
     );
+    let split = content.char_indices().nth(visible_chars).map_or(content.len(), |(i, _)| i);
+    let mut job = egui::text::LayoutJob::default();
+    job.wrap.max_width = text_area.width().max(1.0);
+    job.append(&content[..split], 0.0, egui::TextFormat::simple(font.clone(), Color32::WHITE));
+    job.append(&content[split..], 0.0, egui::TextFormat::simple(font, Color32::TRANSPARENT));
+    let galley = ui.fonts(|f| f.layout_job(job));
+    
+    // 超出对话框高度时向上滚动，始终露出正在显示的最后一行
+    let cursor_bottom = galley.pos_from_ccursor(egui::text::CCursor::new(visible_chars)).bottom();
+    let scroll = (cursor_bottom - text_area.height()).max(0.0);
+    ui.painter()
+        .with_clip_rect(text_area.expand(2.0))
+        .galley(text_area.min - Vec2::new(0.0, scroll), galley, Color32::WHITE);
     
-    // 8. 绘制完成指示器（闪烁三角形）
-    if is_finished {
+    // 8. 绘制"继续"指示器（闪烁并上下浮动的三角形）
+    // 以 0.8 秒为周期：前 0.55 秒显示，其余时间隐藏
+    if let Some(t) = indicator_time.filter(|t| t % 0.8 < 0.55) {
         let tri_center = Pos2::new(
             screen.right() - pad_x, 
-            screen.bottom() - (box_h * 0.15) + (ui.input(|i| i.time) * 3.0).sin() as f32 * 3.0
+            screen.bottom() - (box_h * 0.15) + (t * 3.0).sin() * 3.0
         );
         
         let ts = box_h * 0.04;  // 三角形大小
@@ -1331,199 +8216,231 @@ fn draw_ba_dialogue(
         ));
     }
     
-    // 返回是否被点击
-    resp.clicked()
+    // 返回是否被点击，以及指针是否在对话框上按下/拖动
+    let consumed = resp.is_pointer_button_down_on() || resp.dragged() || resp.clicked();
+    (resp.clicked(), consumed)
 }
 
 /// 绘制创作者面板（主控制台）
 fn draw_creator_panel(ctx: &egui::Context, app: &mut AefrApp) {
     let mut cmd_to_send = None;  // 待发送命令
     
-    egui::Window::new("创作者面板 - GNU:AEFR")
-        .default_size([500.0, 600.0])
-        .show(ctx, |ui| {
-            // 1. 剧本幕数管理
-            ui.heading("🎬 剧本幕数管理");
-            ui.horizontal(|ui| {
-                // 上一幕按钮
-                if ui.button("⬅ 上一幕").clicked() && app.current_scene_idx > 0 {
-                    app.current_scene_idx -= 1; 
-                    app.sync_scene_to_ui(); 
-                    app.visible_count = app.target_chars.len();  // 立即显示全文
-                }
-                
-                // 当前幕数显示
-                ui.label(format!(" 第 {} / {} 幕 ", 
-                    app.current_scene_idx + 1, 
-                    app.scenario.scenes.len()
-                ));
-                
-                // 下一幕按钮
-                if ui.button("下一幕 ➡").clicked() && 
-                   app.current_scene_idx < app.scenario.scenes.len() - 1 
-                {
-                    app.current_scene_idx += 1; 
-                    app.sync_scene_to_ui(); 
-                    app.visible_count = app.target_chars.len();
-                }
-                
-                ui.separator();
-                
-                // 增加一幕
-                if ui.button("➕ 增加一幕").clicked() {
-                    let mut new_scene = app.scenario.scenes[app.current_scene_idx].clone();
-                    new_scene.dialogue_content.clear();  // 清空对话
-                    app.scenario.scenes.insert(app.current_scene_idx + 1, new_scene);
-                    app.current_scene_idx += 1; 
-                    app.sync_scene_to_ui();
-                }
-                
-                // 删除当前幕
-                if ui.button("❌ 删除").clicked() && app.scenario.scenes.len() > 1 {
-                    app.scenario.scenes.remove(app.current_scene_idx);
-                    app.current_scene_idx = app.current_scene_idx.min(app.scenario.scenes.len() - 1);
-                    app.sync_scene_to_ui();
-                }
-            });
+    // 停靠模式：收起时也要绘制，直到滑出动画结束
+    let docked = app.console_docked;
+    let slide = ctx.animate_bool_with_time(egui::Id::new("console_dock_slide"), app.console_open, CONSOLE_SLIDE_TIME);
+    if (docked && slide <= 0.0) || (!docked && !app.console_open) {
+        return;
+    }
+    
+    let contents = |ui: &mut egui::Ui| {
+        // 1. 剧本幕数管理
+        ui.heading("🎬 剧本幕数管理");
+        ui.horizontal(|ui| {
+            // 上一幕按钮
+            if ui.button("⬅ 上一幕").clicked() && app.current_scene_idx > 0 {
+                app.current_scene_idx -= 1; 
+                app.sync_scene_to_ui(); 
+                app.visible_count = app.target_chars.len();  // 立即显示全文
+            }
             
-            // 幕数跳转输入
-            ui.horizontal(|ui| {
-                ui.label("跳转:");
-                let mut jump = app.current_scene_idx + 1;
-                let len = app.scenario.scenes.len();
-                
-                if ui.add(egui::DragValue::new(&mut jump).clamp_range(1..=len)).changed() {
-                    app.current_scene_idx = jump - 1; 
-                    app.sync_scene_to_ui(); 
-                    app.visible_count = app.target_chars.len();
-                }
-            });
-
-            ui.separator();
+            // 当前幕数显示
+            ui.label(format!(" 第 {} / {} 幕 ", 
+                app.current_scene_idx + 1, 
+                app.scenario.scenes.len()
+            ));
+            
+            // 下一幕按钮
+            if ui.button("下一幕 ➡").clicked() && 
+               app.current_scene_idx < app.scenario.scenes.len() - 1 
+            {
+                app.current_scene_idx += 1; 
+                app.sync_scene_to_ui(); 
+                app.visible_count = app.target_chars.len();
+            }
             
-            // 2. 剧本文件操作
-            ui.horizontal(|ui| {
-                if ui.button("💾 保存剧本").clicked() {
-                    if let Ok(json_data) = serde_json::to_string_pretty(&app.scenario) { 
-                        file_picker::save_scenario(app.tx.clone(), json_data); 
-                    }
-                }
-                if ui.button("📂 重载剧本").clicked() { 
-                    file_picker::load_scenario(app.tx.clone()); 
-                }
-            });
-
             ui.separator();
             
-            // 3. 资源管理
-            ui.heading("📂 资源管理");
-            ui.horizontal(|ui| {
-                ui.label("槽位:");
-                // 5个角色槽位选择按钮
-                for i in 0..5 { 
-                    if ui.radio_value(&mut app.selected_slot, i, format!("[{}]", i)).clicked() { 
-                        app.preview_anim_idx = 0;  // 重置预览索引
-                    } 
-                }
-            });
+            // 增加一幕
+            if ui.button("➕ 增加一幕").clicked() {
+                let mut new_scene = app.scenario.scenes[app.current_scene_idx].clone();
+                new_scene.dialogue_content.clear();  // 清空对话
+                app.scenario.scenes.insert(app.current_scene_idx + 1, new_scene);
+                app.current_scene_idx += 1; 
+                app.sync_scene_to_ui();
+            }
             
-            ui.horizontal(|ui| {
-                // Spine导入
-                if ui.button("📥 导入 Spine 立绘").clicked() { 
-                    file_picker::pick_spine(app.tx.clone(), app.selected_slot); 
-                }
-                
-                // 背景导入
-                if ui.button("🖼 背景").clicked() { 
-                    file_picker::pick_bg(app.tx.clone()); 
-                }
-                
-                // 立绘移除（红色按钮）
-                if ui.add(egui::Button::new("🗑 立绘移除")
-                    .fill(Color32::from_rgb(150, 40, 40))).clicked() 
-                { 
-                    cmd_to_send = Some(AppCommand::RemoveCharacter(app.selected_slot)); 
-                }
-                
-                // 动画预览
-                if ui.button("🏃 动作预览").clicked() { 
-                    app.show_anim_preview = true; 
-                }
-            });
-
-            ui.separator();
+            // 删除当前幕
+            if ui.button("❌ 删除").clicked() && app.scenario.scenes.len() > 1 {
+                app.scenario.scenes.remove(app.current_scene_idx);
+                app.current_scene_idx = app.current_scene_idx.min(app.scenario.scenes.len() - 1);
+                app.sync_scene_to_ui();
+            }
+        });
+        
+        // 幕数跳转输入
+        ui.horizontal(|ui| {
+            ui.label("跳转:");
+            let mut jump = app.current_scene_idx + 1;
+            let len = app.scenario.scenes.len();
             
-            // 4. 音频管理
-            ui.heading("🎵 音频管理");
-            ui.horizontal(|ui| {
-                if ui.button("🔁 导入音乐(循环)").clicked() { 
-                    file_picker::pick_bgm(app.tx.clone()); 
-                }
-                if ui.button("🔊 音效").clicked() { 
-                    file_picker::pick_se(app.tx.clone()); 
-                }
-                if ui.add(egui::Button::new("⏹ 停止音乐")
-                    .fill(Color32::from_rgb(150, 40, 40))).clicked() 
-                { 
-                    cmd_to_send = Some(AppCommand::StopBgm); 
+            if ui.add(egui::DragValue::new(&mut jump).clamp_range(1..=len)).changed() {
+                app.current_scene_idx = jump - 1; 
+                app.sync_scene_to_ui(); 
+                app.visible_count = app.target_chars.len();
+            }
+        });
+
+        ui.separator();
+        
+        // 2. 剧本文件操作
+        ui.horizontal(|ui| {
+            if ui.button("💾 保存剧本").clicked() {
+                if let Ok(json_data) = serde_json::to_string_pretty(&app.scenario) { 
+                    file_picker::save_scenario(app.tx.clone(), json_data); 
                 }
-            });
+            }
+            if ui.button("📂 重载剧本").clicked() { 
+                file_picker::load_scenario(app.tx.clone()); 
+            }
+        });
 
-            ui.separator();
-            
-            // 5. 对话编辑
-            ui.heading("💬 对话 (当前幕)");
-            let scene = &mut app.scenario.scenes[app.current_scene_idx];
+        ui.separator();
+        
+        // 3. 资源管理
+        ui.heading("📂 资源管理");
+        ui.horizontal(|ui| {
+            ui.label("槽位:");
+            // 5个角色槽位选择按钮
+            for i in 0..SLOT_COUNT { 
+                if ui.radio_value(&mut app.selected_slot, i, format!("[{}]", i)).clicked() { 
+                    app.preview_anim_idx = 0;  // 重置预览索引
+                } 
+            }
+        });
+        
+        ui.horizontal(|ui| {
+            // Spine导入
+            if ui.button("📥 导入 Spine 立绘").clicked() { 
+                file_picker::pick_spine(app.tx.clone(), app.selected_slot); 
+            }
             
-            ui.horizontal(|ui| {
-                ui.label("名称:");
-                ui.add(egui::TextEdit::singleline(&mut scene.speaker_name)
-                    .desired_width(80.0));
-                
-                ui.label("所属:");
-                ui.add(egui::TextEdit::singleline(&mut scene.speaker_aff)
-                    .desired_width(80.0));
-            });
+            // 背景导入
+            if ui.button("🖼 背景").clicked() { 
+                file_picker::pick_bg(app.tx.clone()); 
+            }
             
-            // 多行对话编辑
-            ui.add(egui::TextEdit::multiline(&mut scene.dialogue_content)
-                .desired_width(f32::INFINITY));
+            // 立绘移除（红色按钮）
+            if ui.add(egui::Button::new("🗑 立绘移除")
+                .fill(Color32::from_rgb(150, 40, 40))).clicked() 
+            { 
+                cmd_to_send = Some(AppCommand::RemoveCharacter(app.selected_slot)); 
+            }
             
-            if ui.button("▶ 发送对话 (TALK)").clicked() { 
-                app.sync_scene_to_ui();  // 立即应用编辑
+            // 动画预览
+            if ui.button("🏃 动作预览").clicked() { 
+                app.show_anim_preview = true; 
             }
+        });
 
-            ui.separator();
+        ui.separator();
+        
+        // 4. 音频管理
+        ui.heading("🎵 音频管理");
+        ui.horizontal(|ui| {
+            if ui.button("🔁 导入音乐(循环)").clicked() { 
+                file_picker::pick_bgm(app.tx.clone()); 
+            }
+            if ui.button("🔊 音效").clicked() { 
+                file_picker::pick_se(app.tx.clone()); 
+            }
+            if ui.add(egui::Button::new("⏹ 停止音乐")
+                .fill(Color32::from_rgb(150, 40, 40))).clicked() 
+            { 
+                cmd_to_send = Some(AppCommand::StopBgm); 
+            }
+        });
+
+        ui.separator();
+        
+        // 5. 对话编辑
+        ui.heading("💬 对话 (当前幕)");
+        let scene = &mut app.scenario.scenes[app.current_scene_idx];
+        
+        ui.horizontal(|ui| {
+            ui.label("名称:");
+            ui.add(egui::TextEdit::singleline(&mut scene.speaker_name)
+                .desired_width(80.0));
             
-            // 6. 控制台命令行
-            ui.horizontal(|ui| {
-                let res = ui.add(egui::TextEdit::singleline(&mut app.console_input)
-                    .hint_text("CMD..."));
-                
-                // 回车或点击发送
-                if ui.button("发送").clicked() || 
-                   (res.lost_focus() && ctx.input(|i| i.key_pressed(egui::Key::Enter))) 
-                {
-                    let input = app.console_input.clone(); 
-                    app.parse_and_send_command(&input); 
-                    app.console_input.clear(); 
-                    res.request_focus();  // 保持焦点
-                }
-            });
+            ui.label("所属:");
+            ui.add(egui::TextEdit::singleline(&mut scene.speaker_aff)
+                .desired_width(80.0));
+        });
+        
+        // 多行对话编辑
+        ui.add(egui::TextEdit::multiline(&mut scene.dialogue_content)
+            .desired_width(f32::INFINITY));
+        
+        if ui.button("▶ 发送对话 (TALK)").clicked() { 
+            app.sync_scene_to_ui();  // 立即应用编辑
+        }
+
+        ui.separator();
+        
+        // 6. 控制台命令行
+        ui.horizontal(|ui| {
+            let res = ui.add(egui::TextEdit::singleline(&mut app.console_input)
+                .hint_text("CMD..."));
             
-            // 7. 日志显示（自动滚动到底部）
-            egui::ScrollArea::vertical()
-                .stick_to_bottom(true)
-                .max_height(60.0)
-                .show(ui, |ui| { 
-                    for log in &app.console_logs { 
-                        ui.label(log); 
-                    } 
-                });
+            // 回车或点击发送
+            if ui.button("发送").clicked() || 
+               (res.lost_focus() && ctx.input(|i| i.key_pressed(egui::Key::Enter))) 
+            {
+                let input = app.console_input.clone(); 
+                app.parse_and_send_command(&input); 
+                app.console_input.clear(); 
+                res.request_focus();  // 保持焦点
+            }
+            
+            if ui.button("复制日志").clicked() {
+                let text = app.log_text();
+                ctx.output_mut(|o| o.copied_text = text);
+            }
         });
+        
+        // 7. 日志显示（自动滚动到底部）
+        egui::ScrollArea::vertical()
+            .stick_to_bottom(true)
+            .max_height(60.0)
+            .show(ui, |ui| { 
+                for log in app.console_logs.iter().filter(|l| LogLevel::of(l) >= app.log_level) { 
+                    ui.label(log); 
+                } 
+            });
+    };
+    
+    if docked {
+        // 下拉式停靠：铺满屏幕宽度、占下方 CONSOLE_DOCK_HEIGHT，从底边滑入/滑出，上方舞台保持可见
+        let screen = ctx.screen_rect();
+        let height = screen.height() * CONSOLE_DOCK_HEIGHT;
+        let inner = egui::vec2(screen.width(), height) - ctx.style().spacing.window_margin.sum();
+        egui::Window::new("创作者面板 - GNU:AEFR")
+            .id(egui::Id::new("console_docked"))
+            .title_bar(false)
+            .collapsible(false)
+            .resizable(false)
+            .fixed_pos(egui::pos2(screen.left(), screen.bottom() - height * slide))
+            .fixed_size(inner)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical().auto_shrink([false, false]).show(ui, contents);
+            });
+    } else {
+        egui::Window::new("创作者面板 - GNU:AEFR")
+            .default_size([500.0, 600.0])
+            .show(ctx, contents);
+    }
 
-    // 8. 动画预览窗口
-    if app.show_anim_preview {
+    // 8. 动画预览窗口（随控制台一起显示，停靠控制台滑出期间不显示）
+    if app.show_anim_preview && app.console_open {
         egui::Window::new("动作")
             .open(&mut app.show_anim_preview)
             .show(ctx, |ui| {
@@ -1547,6 +8464,7 @@ fn draw_creator_panel(ctx: &egui::Context, app: &mut AefrApp) {
                                     app.preview_anim_idx = (app.preview_anim_idx + anims.len() - 1) % anims.len(); 
                                     cmd_to_send = Some(AppCommand::SetAnimation { 
                                         slot_idx: app.selected_slot, 
+                                        track: 0, 
                                         anim_name: anims[app.preview_anim_idx].clone(), 
                                         loop_anim: true 
                                     }); 
@@ -1557,11 +8475,14 @@ fn draw_creator_panel(ctx: &egui::Context, app: &mut AefrApp) {
                                     app.preview_anim_idx = (app.preview_anim_idx + 1) % anims.len(); 
                                     cmd_to_send = Some(AppCommand::SetAnimation { 
                                         slot_idx: app.selected_slot, 
+                                        track: 0, 
                                         anim_name: anims[app.preview_anim_idx].clone(), 
                                         loop_anim: true 
                                     }); 
                                 }
                             });
+                        } else {
+                            ui.label("该角色没有任何动画（静态立绘）");
                         }
                     }
                  }
@@ -1579,6 +8500,22 @@ const FONT_DATA: &[u8] = include_bytes!("SarasaTermSCNerd-Regular.ttf");
 
 /// 设置嵌入式字体
 fn setup_embedded_font(ctx: &egui::Context) {
+    ctx.set_fonts(font_definitions(None));
+}
+
+/// 设置字体：path 为空时仅使用内置字体，否则外部字体优先、内置字体补足缺字
+fn setup_fonts(ctx: &egui::Context, path: &str) -> Result<(), String> {
+    if path.is_empty() {
+        setup_embedded_font(ctx);
+        return Ok(());
+    }
+    let data = std::fs::read(path).map_err(|e| format!("无法读取字体 {}: {}", path, e))?;
+    ctx.set_fonts(font_definitions(Some(data)));
+    Ok(())
+}
+
+/// 构建字体定义：内置字体置于默认字体之前，外部字体（若有）再置于最前
+fn font_definitions(custom: Option<Vec<u8>>) -> FontDefinitions {
     let mut fonts = FontDefinitions::default();
     
     // 注册字体数据
@@ -1586,17 +8523,414 @@ fn setup_embedded_font(ctx: &egui::Context) {
         "sarasa_font".to_owned(), 
         FontData::from_static(FONT_DATA)  // 从二进制数据加载
     );
+    let mut names = vec!["sarasa_font".to_owned()];
+    if let Some(data) = custom {
+        fonts.font_data.insert("custom_font".to_owned(), FontData::from_owned(data));
+        names.insert(0, "custom_font".to_owned());
+    }
+    
+    // 设置为默认比例字体与等宽字体
+    for family in [FontFamily::Proportional, FontFamily::Monospace] {
+        let list = fonts.families.get_mut(&family).unwrap();
+        for (i, name) in names.iter().enumerate() {
+            list.insert(i, name.clone());
+        }
+    }
+    fonts
+}
+
+// ============================================================================
+// 单元测试
+// ============================================================================
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// 加权网格夹具：一个 100×100 的四边形网格（Spine 3.8 JSON），骨骼 left (-50,0) / right (50,0)
+    /// 顶点 0 只绑定 left，顶点 1 只绑定 right，顶点 2 为 left/right 各 0.5，顶点 3 为 left 0.75 / right 0.25；
+    /// 动画 lift 在 1 秒内把 right 线性上移 40
+    const WEIGHTED_ATLAS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/test/weighted.atlas");
+    const POS_TOLERANCE: f32 = 1e-3;
+    
+    /// 骨骼坐标 -> 无窗口加载时的屏幕坐标（position 为原点、Y 轴翻转、基础缩放）
+    fn screen(x: f32, y: f32) -> Pos2 {
+        Pos2::new(x * CHAR_BASE_SCALE, -y * CHAR_BASE_SCALE)
+    }
+    
+    fn single_mesh(shapes: &[Shape]) -> &Mesh {
+        assert_eq!(shapes.len(), 1, "夹具只有一个插槽，应生成一个 Mesh");
+        match &shapes[0] {
+            Shape::Mesh(mesh) => mesh,
+            _ => panic!("render_headless 应只返回 Shape::Mesh"),
+        }
+    }
+    
+    fn assert_pos(actual: Pos2, expected: Pos2) {
+        assert!(
+            (actual - expected).length() <= POS_TOLERANCE,
+            "顶点位置 {:?}，期望 {:?}", actual, expected,
+        );
+    }
+    
+    // ---- 无窗口渲染 ----
+    
+    #[test]
+    fn headless_render_reports_counts_and_positions() {
+        let mut obj = SpineObject::load_headless(WEIGHTED_ATLAS).expect("加载夹具");
+        assert!(obj.set_animation_headless("lift", false));
+        
+        // 固定时间步推进到 0.5 秒（单步不超过 MAX_DT）
+        let mesh = single_mesh(&obj.render_headless(&[0.025; 20])).clone();
+        assert_eq!(mesh.vertices.len(), 4);
+        assert_eq!(mesh.indices, vec![0, 1, 2, 2, 3, 0]);
+        
+        // 单骨骼绑定的顶点：left 不动，right 上移 20
+        assert_pos(mesh.vertices[0].pos, screen(-50.0, 0.0));
+        assert_pos(mesh.vertices[1].pos, screen(50.0, 20.0));
+    }
+    
+    #[test]
+    fn weighted_mesh_vertices_blend_bone_positions() {
+        let mut obj = SpineObject::load_headless(WEIGHTED_ATLAS).expect("加载夹具");
+        assert!(obj.set_animation_headless("lift", false));
+        
+        // 参考值：顶点世界坐标 = Σ 权重 × (骨骼世界变换 × 绑定时的骨骼局部坐标)
+        // right 在 t 秒时位于 (50, 40t)：顶点 2 = (50, 100 + 20t)，顶点 3 = (-50, 100 + 10t)
+        for (steps, t) in [(10, 0.25), (10, 0.5)] {
+            for _ in 0..steps {
+                obj.update_parallel(0.025);
+            }
+            let shapes = obj.build_mesh();
+            let mesh = single_mesh(&shapes);
+            let expected = [
+                screen(-50.0, 0.0),
+                screen(50.0, 40.0 * t),
+                screen(50.0, 100.0 + 20.0 * t),
+                screen(-50.0, 100.0 + 10.0 * t),
+            ];
+            assert_eq!(mesh.vertices.len(), expected.len());
+            for (vertex, expected) in mesh.vertices.iter().zip(expected) {
+                assert_pos(vertex.pos, expected);
+            }
+        }
+    }
+    
+    // ---- 网格容量预留 ----
+    
+    /// 计数分配器：按线程统计 alloc/realloc 次数，测试之间并行运行互不干扰
+    struct CountingAlloc;
+    
+    thread_local! {
+        static ALLOCATIONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+    
+    unsafe impl std::alloc::GlobalAlloc for CountingAlloc {
+        unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+            std::alloc::System.alloc(layout)
+        }
+        
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+            std::alloc::System.dealloc(ptr, layout)
+        }
+        
+        unsafe fn realloc(&self, ptr: *mut u8, layout: std::alloc::Layout, new_size: usize) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+            std::alloc::System.realloc(ptr, layout, new_size)
+        }
+    }
+    
+    #[global_allocator]
+    static GLOBAL: CountingAlloc = CountingAlloc;
+    
+    fn count_allocations(f: impl FnOnce()) -> usize {
+        let before = ALLOCATIONS.with(|n| n.get());
+        f();
+        ALLOCATIONS.with(|n| n.get()) - before
+    }
+    
+    /// 按 build_mesh 的方式逐段填充：每段用 reserved_mesh 新建后逐个推入顶点与索引
+    fn fill_segments(sizes: &[(usize, usize)], last_sizes: &[(usize, usize)]) -> Vec<Mesh> {
+        sizes.iter().enumerate().map(|(i, &(vertices, indices))| {
+            let mut mesh = reserved_mesh(TextureId::default(), last_sizes, i);
+            for _ in 0..vertices {
+                mesh.vertices.push(Vertex { pos: Pos2::ZERO, uv: Pos2::ZERO, color: Color32::WHITE });
+            }
+            for i in 0..indices {
+                mesh.indices.push(i as u32);
+            }
+            mesh
+        }).collect()
+    }
+    
+    #[test]
+    fn reserved_mesh_allocates_each_segment_once() {
+        // 两段（混合模式切分）：按各自上一帧规模预留后，每段的顶点与索引缓冲各只分配一次
+        let sizes = [(2000, 3000), (400, 600)];
+        let mut meshes = Vec::new();
+        let cold = count_allocations(|| meshes = fill_segments(&sizes, &[]));
+        // 只按总数预留给第一段时，后续各段仍从零开始扩容
+        let total_only = count_allocations(|| meshes = fill_segments(&sizes, &[(2400, 3600)]));
+        let warm = count_allocations(|| meshes = fill_segments(&sizes, &sizes));
+        println!("两段网格分配次数: 不预留 {} / 仅首段按总数预留 {} / 逐段预留 {}", cold, total_only, warm);
+        assert_eq!(warm, 1 + 2 * sizes.len(), "Vec<Mesh> 本身 1 次 + 每段顶点/索引各 1 次");
+        assert!(total_only > warm && cold > total_only);
+        for (mesh, &(vertices, indices)) in meshes.iter().zip(&sizes) {
+            assert_eq!((mesh.vertices.capacity(), mesh.indices.capacity()), (vertices, indices));
+        }
+    }
+    
+    #[test]
+    fn build_mesh_reserves_previous_frame_size() {
+        let mut obj = SpineObject::load_headless(WEIGHTED_ATLAS).expect("加载夹具");
+        obj.build_mesh();
+        let mesh = single_mesh(&obj.build_mesh()).clone();
+        assert_eq!(mesh.vertices.capacity(), mesh.vertices.len());
+        assert_eq!(mesh.indices.capacity(), mesh.indices.len());
+        assert_eq!(obj.mesh_stats(), (4, 2));
+    }
+    
+    // ---- 绘制合批 ----
+    
+    /// 夹具角色 × count，第 i 个角色使用纹理 texture_of(i)
+    fn stage_of(count: usize, texture_of: impl Fn(usize) -> u64) -> Vec<Option<Arc<Mutex<SpineObject>>>> {
+        (0..count)
+            .map(|i| {
+                let mut obj = SpineObject::load_headless(WEIGHTED_ATLAS).expect("加载夹具");
+                obj.texture_id = Some(TextureId::Managed(texture_of(i)));
+                obj.position = Pos2::new(i as f32 * 100.0, 0.0);
+                Some(Arc::new(Mutex::new(obj)))
+            })
+            .collect()
+    }
+    
+    fn quad(texture: u64) -> Shape {
+        let mut mesh = Mesh::with_texture(TextureId::Managed(texture));
+        mesh.add_colored_rect(Rect::from_min_size(Pos2::ZERO, Vec2::splat(10.0)), Color32::WHITE);
+        Shape::mesh(mesh)
+    }
+    
+    #[test]
+    fn push_batched_merges_only_same_texture() {
+        let mut shapes = Vec::new();
+        for _ in 0..5 {
+            push_batched(&mut shapes, quad(1));
+        }
+        assert_eq!(shapes.len(), 1);
+        let Shape::Mesh(mesh) = &shapes[0] else { panic!("合批结果应为 Mesh") };
+        assert_eq!(mesh.vertices.len(), 20);
+        
+        let mut shapes = Vec::new();
+        for texture in [1, 2, 2, 1, 3] {
+            push_batched(&mut shapes, quad(texture));
+        }
+        assert_eq!(shapes.len(), 4);  // 只有相邻的同纹理 Mesh 合并
+    }
+    
+    #[test]
+    fn five_characters_sharing_a_texture_batch_into_one_draw_call() {
+        let (shapes, unbatched) = build_stage_shapes(&stage_of(5, |_| 1), false, None);
+        assert_eq!(unbatched, 5);
+        assert_eq!(shapes.len(), 1);
+        
+        let (shapes, unbatched) = build_stage_shapes(&stage_of(5, |i| i as u64 + 1), false, None);
+        assert_eq!(unbatched, 5);
+        assert_eq!(shapes.len(), 5);  // 纹理各不相同时保持独立 Mesh
+    }
+    
+    // ---- 基准（默认忽略）：cargo test --release -- --ignored --nocapture ----
+    
+    /// 单角色每帧更新：UI线程串行 vs 投递到线程池（PARALLEL_MIN_CHARS 的依据）
+    #[test]
+    #[ignore]
+    fn bench_single_character_serial_vs_pool() {
+        const FRAMES: u32 = 5000;
+        let characters = stage_of(1, |_| 1);
+        let update = |slot: &Option<Arc<Mutex<SpineObject>>>| {
+            let Some(char_arc) = slot else { return };
+            if let Ok(mut char) = char_arc.lock() {
+                char.update_parallel(1.0 / 60.0);
+                char.prebuilt_shapes = char.build_mesh();
+            }
+        };
+        let scheduler = AefrScheduler::new(false, ReservePolicy::default()).expect("创建线程池");
+        
+        let start = Instant::now();
+        for _ in 0..FRAMES {
+            characters.iter().for_each(update);
+        }
+        let serial = start.elapsed();
+        let pooled: Duration = (0..FRAMES)
+            .map(|_| scheduler.run_parallel(|| characters.par_chunks(1).for_each(|group| group.iter().for_each(update))))
+            .sum();
+        
+        let per_frame = |total: Duration| total.as_secs_f64() * 1e6 / FRAMES as f64;
+        println!(
+            "单角色每帧: 串行 {:.2} µs / 线程池 {:.2} µs ({} 工作线程)",
+            per_frame(serial), per_frame(pooled), scheduler.worker_count,
+        );
+    }
+    
+    // ---- 图集 pma 识别与纹理预乘 ----
+    
+    #[test]
+    fn atlas_pma_fixtures() {
+        assert!(atlas_page_is_pma(include_str!("../resources/test/pma.atlas")));
+        assert!(!atlas_page_is_pma(include_str!("../resources/test/straight.atlas")));
+        assert!(!atlas_page_is_pma(include_str!("../resources/test/weighted.atlas")));
+    }
+    
+    #[test]
+    fn atlas_pma_reads_only_first_page_header() {
+        // 3.8：页头后紧跟区域，区域属性不属于页头
+        assert!(!atlas_page_is_pma("page.png\nsize: 4,4\nbody\n  pma: true\n"));
+        // 第二页的 pma 不影响第一页
+        assert!(!atlas_page_is_pma("a.png\nsize:4,4\nbody\nbounds:0,0,4,4\n\nb.png\nsize:4,4\npma:true\n"));
+        assert!(atlas_page_is_pma("\n\na.png\nsize:4,4\npma: TRUE\nbody\nbounds:0,0,4,4\n"));
+        assert!(!atlas_page_is_pma("a.png\npma: false\n"));
+        assert!(!atlas_page_is_pma(""));
+    }
+    
+    #[test]
+    fn page_color_image_premultiplies_straight_alpha_only() {
+        let rgba = [255, 0, 0, 128, 10, 20, 30, 255];
+        let straight = page_color_image([2, 1], &rgba, false);
+        assert_eq!(straight.size, [2, 1]);
+        // egui 在线性空间预乘：半透明像素的颜色分量被压低，不透明像素不变
+        assert_eq!(straight.pixels[0], Color32::from_rgba_unmultiplied(255, 0, 0, 128));
+        assert!(straight.pixels[0].r() < 255);
+        assert_eq!(straight.pixels[1], Color32::from_rgba_premultiplied(10, 20, 30, 255));
+        
+        // 已预乘的数据原样使用，不再二次预乘
+        let pma = page_color_image([2, 1], &rgba, true);
+        assert_eq!(pma.pixels[0], Color32::from_rgba_premultiplied(255, 0, 0, 128));
+        assert_eq!(pma.pixels[1], Color32::from_rgba_premultiplied(10, 20, 30, 255));
+    }
+    
+    // ---- 别名 ----
+    
+    #[test]
+    fn alias_expands_target_with_preset_arguments() {
+        assert_eq!(expand_alias("anim 0 walk", "true"), "anim 0 walk true");
+        assert_eq!(expand_alias("BG", "\"C:\\bg 1.png\""), "BG \"C:\\bg 1.png\"");
+        assert_eq!(expand_alias("stop", ""), "stop");
+    }
+    
+    #[test]
+    fn alias_validation_allows_builtin_only_onto_itself() {
+        assert!(validate_alias("bg", "BG").is_ok());
+        assert!(validate_alias("bg", "bg anim frames fps=24").is_ok());
+        assert!(validate_alias("walk", "anim 0 walk true").is_ok());
+        assert_eq!(
+            validate_alias("bg", "talk").unwrap_err(),
+            "ALIAS: 不能覆盖内置命令 bg（同名别名只能指向 bg 本身）",
+        );
+        assert_eq!(validate_alias("walk", "WALK 1").unwrap_err(), "ALIAS: 别名 walk 不能指向自身");
+    }
+    
+    // ---- 下载缓存 ----
+    
+    #[test]
+    fn url_file_name_strips_query_and_decodes() {
+        assert_eq!(url_file_name("https://a.b/c/hero.atlas?v=3#top"), "hero.atlas");
+        assert_eq!(url_file_name("https://a.b/c/my%20hero%E8%A7%92.png"), "my hero角.png");
+        assert_eq!(url_file_name("https://a.b/c/100%.png"), "100%.png");
+        assert_eq!(url_file_name("https://a.b/c/%2E%2E%2Fx.png"), ".._x.png");
+        assert_eq!(url_file_name("https://a.b/c/%2E%2E"), "asset");
+        assert_eq!(url_file_name("https://a.b/c/?x=1"), "asset");
+    }
+    
+    #[test]
+    fn sibling_url_keeps_query_out_of_the_path() {
+        let url = "https://a.b/c/hero.atlas?v=3#top";
+        assert_eq!(split_url_query(url), ("https://a.b/c/hero.atlas", "?v=3"));
+        assert_eq!(sibling_url(url, "hero.skel"), "https://a.b/c/hero.skel?v=3");
+        assert_eq!(sibling_url(url, "hero%20page.png"), "https://a.b/c/hero%20page.png?v=3");
+        assert_eq!(sibling_url("https://a.b/c/hero.atlas", "hero.json"), "https://a.b/c/hero.json");
+        assert_eq!(split_url_query("https://a.b/x/?q=a/b"), ("https://a.b/x/", "?q=a/b"));
+    }
+    
+    #[test]
+    fn write_atomic_replaces_file_without_leftovers() {
+        let dir = std::env::temp_dir().join(format!("aefr_test_atomic_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("asset.png");
+        write_atomic(&path, b"old").unwrap();
+        write_atomic(&path, b"new").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"new");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1, "不应残留 .part 临时文件");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    
+    // ---- 登场/退场过渡 ----
+    
+    /// 按 EXIT 的方式开始退场，逐帧推进并像舞台一样卸载报告结束的槽位；返回卸载所用帧数
+    fn run_exit(duration: f32) -> usize {
+        let mut stage = stage_of(2, |_| 1);
+        for slot in stage.iter().flatten() {
+            slot.lock().unwrap().start_transition((Vec2::ZERO, 1.0), (Vec2::new(-1.0, 0.0), 0.0), duration, true);
+        }
+        for frame in 1..=100 {
+            let mut exited = Vec::new();
+            for (i, slot) in stage.iter().enumerate() {
+                if let Some(char_arc) = slot {
+                    if char_arc.lock().unwrap().advance_transition(1.0 / 60.0).1 {
+                        exited.push(i);
+                    }
+                }
+            }
+            for i in exited {
+                assert!(stage[i].take().is_some(), "槽位 {} 的退场重复报告", i);
+            }
+            if stage.iter().all(Option::is_none) {
+                return frame;
+            }
+        }
+        panic!("退场 {} 秒后角色仍未卸载", duration);
+    }
+    
+    #[test]
+    fn exit_unloads_slot_for_zero_and_positive_duration() {
+        assert_eq!(run_exit(0.0), 1);
+        assert!((30..=31).contains(&run_exit(0.5)), "0.5 秒退场应在约 30 帧后卸载");
+    }
+    
+    // ---- 命令切分 ----
+    
+    #[test]
+    fn tokenize_keeps_spaces_and_backslashes_inside_quotes() {
+        assert_eq!(
+            tokenize_command(r#"LOAD 0 "C:\My Chars\hoshino.atlas""#),
+            vec!["LOAD", "0", r"C:\My Chars\hoshino.atlas"],
+        );
+    }
+    
+    #[test]
+    fn tokenize_empty_quotes_yield_empty_token() {
+        assert_eq!(tokenize_command(r#"alias greet """#), vec!["alias", "greet", ""]);
+        assert_eq!(tokenize_command(r#""" x"#), vec!["", "x"]);
+    }
     
-    // 设置为默认比例字体
-    fonts.families.get_mut(&FontFamily::Proportional)
-        .unwrap()
-        .insert(0, "sarasa_font".to_owned());
+    #[test]
+    fn tokenize_unterminated_quote_runs_to_end() {
+        assert_eq!(
+            tokenize_command(r#"load 1 "My Chars/a b.atlas"#),
+            vec!["load", "1", "My Chars/a b.atlas"],
+        );
+    }
     
-    // 设置为默认等宽字体
-    fonts.families.get_mut(&FontFamily::Monospace)
-        .unwrap()
-        .insert(0, "sarasa_font".to_owned());
+    #[test]
+    fn split_talk_keeps_separator_inside_quotes() {
+        let rest = r#"星野|"阿拜多斯|对策委员会"|"嗯|大叔我啊……"|pos=left"#;
+        assert_eq!(
+            split_unquoted(rest, '|'),
+            vec!["星野", "阿拜多斯|对策委员会", "嗯|大叔我啊……", "pos=left"],
+        );
+    }
     
-    // 应用字体设置
-    ctx.set_fonts(fonts);
+    #[test]
+    fn split_empty_quotes_and_unterminated_quote() {
+        assert_eq!(split_unquoted(r#"a|""|c"#, '|'), vec!["a", "", "c"]);
+        assert_eq!(split_unquoted(r#"a|"b|c"#, '|'), vec!["a", "b|c"]);
+    }
 }